@@ -0,0 +1,220 @@
+use std::sync::Arc;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use iced::{
+    pure::{button, column, container, pick_list, row, text, widget::Row},
+    Alignment, Length,
+};
+
+use crate::{parsed_input, FieldOptions, IcedFormValueError, IcedFormValueResult, Selector};
+
+/// shown in the text input
+pub const DATE_TIME_DISPLAY_FORMAT: &str = "%d.%m.%Y %H:%M";
+pub const DATE_DISPLAY_FORMAT: &str = "%d.%m.%Y";
+/// Polish formats first, then the ones found in older files and spreadsheets
+const DATE_TIME_FORMATS: [&str; 6] = [
+    DATE_TIME_DISPLAY_FORMAT,
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y, %H:%M",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S%.f",
+];
+const DATE_FORMATS: [&str; 3] = [DATE_DISPLAY_FORMAT, "%Y-%m-%d", "%d/%m/%Y"];
+const MONTHS: [&str; 12] = [
+    "styczeń",
+    "luty",
+    "marzec",
+    "kwiecień",
+    "maj",
+    "czerwiec",
+    "lipiec",
+    "sierpień",
+    "wrzesień",
+    "październik",
+    "listopad",
+    "grudzień",
+];
+const WEEKDAYS: [&str; 7] = ["pn", "wt", "śr", "cz", "pt", "sb", "nd"];
+const DAY_WIDTH: u16 = 36;
+
+/// `18.10.2026 14:30` and the other accepted formats, a bare date means midnight
+pub fn parse_date_time(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+    DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| parse_date(text).map(|date| date.and_hms(0, 0, 0)))
+}
+
+/// `18.10.2026`, `2026-10-18` or `18/10/2026`
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    let text = text.trim();
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Local::now().naive_local()
+}
+
+/// same day of another month, the last day when the month is shorter
+fn shift_months(date: NaiveDate, months: i32) -> NaiveDate {
+    let month0 = date.year() * 12 + date.month0() as i32 + months;
+    let (year, month) = (month0.div_euclid(12), month0.rem_euclid(12) as u32 + 1);
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+        .unwrap_or(date)
+}
+
+fn days_in_month(date: NaiveDate) -> u32 {
+    let first = date.with_day(1).unwrap_or(date);
+    (shift_months(first, 1) - first).num_days() as u32
+}
+
+/// month navigation and a grid of days, the picked day has no action so it stands out
+fn calendar<'a, Message>(
+    date: NaiveDate,
+    on_pick: impl Fn(NaiveDate) -> Message,
+) -> iced::pure::widget::Column<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    let header = row()
+        .spacing(5)
+        .align_items(Alignment::Center)
+        .push(button("<").on_press(on_pick(shift_months(date, -1))))
+        .push(
+            text(format!(
+                "{} {}",
+                MONTHS[date.month0() as usize],
+                date.year()
+            ))
+            .width(Length::Units(DAY_WIDTH * 5)),
+        )
+        .push(button(">").on_press(on_pick(shift_months(date, 1))));
+    let weekdays = WEEKDAYS.iter().fold(row().spacing(2), |row, weekday| {
+        row.push(text(*weekday).size(14).width(Length::Units(DAY_WIDTH)))
+    });
+    let offset = date
+        .with_day(1)
+        .unwrap_or(date)
+        .weekday()
+        .num_days_from_monday();
+    let cells = (0..offset)
+        .map(|_| None)
+        .chain((1..=days_in_month(date)).map(Some))
+        .collect::<Vec<_>>();
+    cells.chunks(7).fold(
+        column().spacing(2).push(header).push(weekdays),
+        |grid, week| {
+            let week = week
+                .iter()
+                .fold(row().spacing(2), |week: Row<'a, Message>, day| {
+                    let cell = match day.and_then(|day| date.with_day(day)) {
+                        Some(day) => {
+                            let mut cell = button(text(day.day().to_string()).size(14))
+                                .padding(2)
+                                .width(Length::Units(DAY_WIDTH));
+                            if day != date {
+                                cell = cell.on_press(on_pick(day));
+                            }
+                            container(cell)
+                        }
+                        None => container(text("")).width(Length::Units(DAY_WIDTH)),
+                    };
+                    week.push(cell)
+                });
+            grid.push(week)
+        },
+    )
+}
+
+/// text input taking Polish dates, "dziś" and "teraz" shortcuts, a calendar and the time
+pub fn date_time_picker<'a, Message>(
+    value: NaiveDateTime,
+    on_change: Arc<dyn Fn(IcedFormValueResult<NaiveDateTime>) -> Message + 'a>,
+    selector: Selector,
+    options: FieldOptions<'a>,
+) -> iced::pure::widget::Container<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    let input = row()
+        .spacing(5)
+        .align_items(Alignment::Start)
+        .push(parsed_input(
+            value.format(DATE_TIME_DISPLAY_FORMAT).to_string(),
+            |text| {
+                parse_date_time(text).ok_or_else(|| {
+                    IcedFormValueError::deserializing(
+                        "NaiveDateTime",
+                        format!(
+                            "expected a date like {}",
+                            now().format(DATE_TIME_DISPLAY_FORMAT)
+                        ),
+                    )
+                })
+            },
+            on_change.clone(),
+            selector,
+            options,
+        ))
+        .push(button("dziś").on_press(on_change(Ok(now().date().and_time(value.time())))))
+        .push(button("teraz").on_press(on_change(Ok(now()))));
+    let on_hour = on_change.clone();
+    let on_minute = on_change.clone();
+    let time = row()
+        .spacing(5)
+        .align_items(Alignment::Center)
+        .push(text("godzina"))
+        .push(pick_list(
+            (0..24).collect::<Vec<u32>>(),
+            Some(value.hour()),
+            move |hour| on_hour(Ok(value.with_hour(hour).unwrap_or(value))),
+        ))
+        .push(text(":"))
+        .push(pick_list(
+            (0..60).collect::<Vec<u32>>(),
+            Some(value.minute()),
+            move |minute| on_minute(Ok(value.with_minute(minute).unwrap_or(value))),
+        ));
+    let calendar = calendar(value.date(), |date| {
+        on_change(Ok(date.and_time(value.time())))
+    });
+    container(column().spacing(5).push(input).push(calendar).push(time)).width(Length::Fill)
+}
+
+/// [`date_time_picker`] without the time
+pub fn date_picker<'a, Message>(
+    value: NaiveDate,
+    on_change: Arc<dyn Fn(IcedFormValueResult<NaiveDate>) -> Message + 'a>,
+    selector: Selector,
+    options: FieldOptions<'a>,
+) -> iced::pure::widget::Container<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    let input = row()
+        .spacing(5)
+        .align_items(Alignment::Start)
+        .push(parsed_input(
+            value.format(DATE_DISPLAY_FORMAT).to_string(),
+            |text| {
+                parse_date(text).ok_or_else(|| {
+                    IcedFormValueError::deserializing(
+                        "NaiveDate",
+                        format!("expected a date like {}", now().format(DATE_DISPLAY_FORMAT)),
+                    )
+                })
+            },
+            on_change.clone(),
+            selector,
+            options,
+        ))
+        .push(button("dziś").on_press(on_change(Ok(now().date()))));
+    let calendar = calendar(value, |date| on_change(Ok(date)));
+    container(column().spacing(5).push(input).push(calendar)).width(Length::Fill)
+}
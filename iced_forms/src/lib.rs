@@ -1,26 +1,86 @@
-use std::{fmt::Display, str::FromStr, sync::Arc};
+pub mod date_picker;
+pub mod schema;
+pub mod session;
+pub mod validation;
+
+use std::{collections::BTreeMap, fmt::Display, str::FromStr, sync::Arc};
 
-use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
 
+// the derived forms refer to these through `iced_forms::`, so users don't need matching versions
+pub use iced;
+pub use schema::FieldSchema;
+pub use schemars;
+pub use serde_json;
+pub use session::{history_shortcut, EditSession, HistoryAction};
+pub use validation::{with_errors, FieldError, ValidationErrors, Validators, NO_ERRORS};
+
 #[derive(Error, Debug, Clone)]
 pub enum IcedFormValueError {
-    #[error("Deserializing value of type [{type_name}] - {message}")]
+    #[error("{}Deserializing value of type [{type_name}] - {message}", .selector.location())]
     Deserializing {
         type_name: &'static str,
         message: String,
+        /// field the value came from, empty until the error passes a field
+        selector: Selector,
     },
+    /// text typed into a field that doesn't parse, see [`Drafts`]
+    #[error("{0}")]
+    Unparsed(Draft),
+    /// an element control of an array in a typed form, at a path relative to the value the
+    /// callback carries; worked out by [`resolve`] when the message is handled, so the buttons
+    /// don't carry copies of the array
+    #[error("{}array operation {:?}", .0.selector.location(), .0.change)]
+    Pending(Box<UpdatedMessage>),
+}
+
+impl IcedFormValueError {
+    /// error not tied to any field yet, see [`IcedFormValueError::at`]
+    pub fn deserializing(type_name: &'static str, message: impl Display) -> Self {
+        Self::Deserializing {
+            type_name,
+            message: message.to_string(),
+            selector: Selector::empty(),
+        }
+    }
+
+    pub fn selector(&self) -> &Selector {
+        match self {
+            Self::Deserializing { selector, .. } => selector,
+            Self::Unparsed(draft) => &draft.selector,
+            Self::Pending(update) => &update.selector,
+        }
+    }
+
+    /// a pending change passed on to the value holding the one it was made in, `root` being
+    /// the path from there; other errors carry their whole path already
+    pub fn under(self, root: &Selector) -> Self {
+        match self {
+            Self::Pending(update) => Self::Pending(Box::new(update.under(root))),
+            e => e,
+        }
+    }
+
+    /// ties the error to a field unless one deeper down already claimed it
+    pub fn at(mut self, at: &Selector) -> Self {
+        if let Self::Deserializing { selector, .. } = &mut self {
+            if selector.is_empty() {
+                *selector = at.clone();
+            }
+        }
+        self
+    }
 }
 
 pub type IcedFormValueResult<T> = Result<T, IcedFormValueError>;
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum SelectorSegment {
     ArrayIndex(usize),
     ObjectField(String),
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Selector(Vec<SelectorSegment>);
 
 impl Selector {
@@ -37,14 +97,115 @@ impl Selector {
     pub fn empty() -> Self {
         Self(Default::default())
     }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn segments(&self) -> &[SelectorSegment] {
+        &self.0
+    }
+
+    /// the value the selector points at, `None` when a field or an element is missing
+    pub fn resolve<'v>(&self, value: &'v serde_json::Value) -> Option<&'v serde_json::Value> {
+        self.0
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                SelectorSegment::ArrayIndex(index) => value.get(index),
+                SelectorSegment::ObjectField(key) => value.get(key),
+            })
+    }
+
+    pub fn resolve_mut<'v>(
+        &self,
+        value: &'v mut serde_json::Value,
+    ) -> Option<&'v mut serde_json::Value> {
+        self.0
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                SelectorSegment::ArrayIndex(index) => value.get_mut(index),
+                SelectorSegment::ObjectField(key) => value.get_mut(key),
+            })
+    }
+
+    /// replaces the value the selector points at, the path has to exist already
+    pub fn set(
+        &self,
+        document: &mut serde_json::Value,
+        value: serde_json::Value,
+    ) -> IcedFormValueResult<()> {
+        let target = self.resolve_mut(document).ok_or_else(|| {
+            IcedFormValueError::deserializing("serde_json::Value", "nothing at this path").at(self)
+        })?;
+        *target = value;
+        Ok(())
+    }
+
+    pub fn starts_with(&self, prefix: &Selector) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+
+    /// the rest of the path below `prefix`
+    pub fn strip_prefix(&self, prefix: &Selector) -> Option<Selector> {
+        self.0
+            .strip_prefix(prefix.0.as_slice())
+            .map(|rest| Self(rest.to_vec()))
+    }
+
+    /// `path: ` in front of messages, nothing for the root
+    fn location(&self) -> String {
+        match self.is_empty() {
+            true => String::new(),
+            false => format!("{self}: "),
+        }
+    }
 }
 
+/// `info.customer.phone`, `client_contact_events[2].note`; keys holding `.`, `[` or `]`
+/// don't survive [`Selector::from_str`]
 impl Display for Selector {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.0.last() {
-            Some(segment) => segment.fmt(f),
-            None => "".fmt(f),
+        for (position, segment) in self.0.iter().enumerate() {
+            match segment {
+                SelectorSegment::ArrayIndex(index) => write!(f, "[{index}]")?,
+                SelectorSegment::ObjectField(key) if position == 0 => key.fmt(f)?,
+                SelectorSegment::ObjectField(key) => write!(f, ".{key}")?,
+            }
         }
+        Ok(())
+    }
+}
+
+impl FromStr for Selector {
+    type Err = IcedFormValueError;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let error = |message: &str| {
+            IcedFormValueError::deserializing("Selector", format!("{message} in [{path}]"))
+        };
+        let mut segments = vec![];
+        if path.is_empty() {
+            return Ok(Self(segments));
+        }
+        for (position, part) in path.split('.').enumerate() {
+            let (key, indices) = part.split_at(part.find('[').unwrap_or(part.len()));
+            match key.is_empty() {
+                true if position > 0 || indices.is_empty() => return Err(error("empty key")),
+                true => {}
+                false => segments.push(SelectorSegment::ObjectField(key.to_owned())),
+            }
+            let mut indices = indices;
+            while let Some(rest) = indices.strip_prefix('[') {
+                let (index, rest) = rest
+                    .split_once(']')
+                    .ok_or_else(|| error("unclosed bracket"))?;
+                let index = index.parse().map_err(|_| error("index is not a number"))?;
+                segments.push(SelectorSegment::ArrayIndex(index));
+                indices = rest;
+            }
+            if !indices.is_empty() {
+                return Err(error("text after an index"));
+            }
+        }
+        Ok(Self(segments))
     }
 }
 
@@ -57,10 +218,233 @@ impl Display for SelectorSegment {
     }
 }
 
+/// text of a field that doesn't parse yet
+#[derive(Debug, Clone)]
+pub struct Draft {
+    pub selector: Selector,
+    pub text: String,
+    pub message: String,
+}
+
+impl Display for Draft {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+/// text of the fields that don't parse, kept by the app next to the edited value so a typo
+/// doesn't throw the form away; fields show their draft with the error until it parses
+#[derive(Debug, Clone, Default)]
+pub struct Drafts(BTreeMap<Selector, Draft>);
+
+/// for forms that don't keep drafts
+pub static NO_DRAFTS: Drafts = Drafts(BTreeMap::new());
+
+impl Drafts {
+    /// records the outcome of an edit: unparsed text becomes the field's draft (`Ok(None)`),
+    /// a value that parsed clears the drafts (`Ok(Some(value))`) since the edit that fixed one
+    /// can't be told apart from any other, remaining errors are handed back
+    pub fn track<T>(&mut self, result: IcedFormValueResult<T>) -> IcedFormValueResult<Option<T>> {
+        match result {
+            Ok(value) => {
+                self.0.clear();
+                Ok(Some(value))
+            }
+            Err(IcedFormValueError::Unparsed(draft)) => {
+                self.0.insert(draft.selector.clone(), draft);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// [`Drafts::track`] for patches, a value that parsed only clears the drafts at and below
+    /// its own path
+    pub fn track_update(
+        &mut self,
+        update: UpdatedMessage,
+    ) -> IcedFormValueResult<Option<UpdatedMessage>> {
+        match update.change {
+            Change::Set(Err(IcedFormValueError::Unparsed(draft))) => {
+                self.0.insert(draft.selector.clone(), draft);
+                Ok(None)
+            }
+            Change::Set(Err(e)) => Err(e),
+            Change::Set(Ok(_)) | Change::Array(_) => {
+                self.discard(&update.selector);
+                Ok(Some(update))
+            }
+        }
+    }
+
+    /// drops the drafts at and below `selector`
+    pub(crate) fn discard(&mut self, selector: &Selector) {
+        self.0.retain(|draft, _| !draft.starts_with(selector));
+    }
+
+    pub fn get(&self, selector: &Selector) -> Option<&Draft> {
+        self.0.get(selector)
+    }
+
+    /// nothing is waiting to be fixed, the edited value is what the user sees
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// in the order of their paths
+    pub fn iter(&self) -> impl Iterator<Item = &Draft> {
+        self.0.values()
+    }
+}
+
+/// a change to a single value of a document, sent by [`json_form`] so the document is edited
+/// in place instead of being rebuilt from the changed field up
 #[derive(Clone, Debug)]
 pub struct UpdatedMessage {
     pub selector: Selector,
-    pub value: IcedFormValueResult<serde_json::Value>,
+    pub change: Change,
+}
+
+/// what happens to the value at [`UpdatedMessage::selector`]
+#[derive(Clone, Debug)]
+pub enum Change {
+    Set(IcedFormValueResult<serde_json::Value>),
+    /// worked out from the array in the document when applied, so the buttons of every element
+    /// don't carry a copy of the whole array
+    Array(ArrayOp),
+}
+
+/// the buttons of [`json_form`] arrays
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArrayOp {
+    /// appends the element
+    Push(serde_json::Value),
+    MoveUp(usize),
+    MoveDown(usize),
+    Duplicate(usize),
+    Remove(usize),
+}
+
+impl ArrayOp {
+    fn apply(self, values: &mut Vec<serde_json::Value>) -> Result<(), String> {
+        let len = values.len();
+        match self {
+            ArrayOp::Push(value) => values.push(value),
+            ArrayOp::MoveUp(index) if index > 0 && index < len => values.swap(index - 1, index),
+            ArrayOp::MoveDown(index) if index + 1 < len => values.swap(index, index + 1),
+            ArrayOp::Duplicate(index) if index < len => {
+                values.insert(index + 1, values[index].clone())
+            }
+            ArrayOp::Remove(index) if index < len => {
+                values.remove(index);
+            }
+            op => return Err(format!("{op:?} out of range for {len} elements")),
+        }
+        Ok(())
+    }
+}
+
+impl Change {
+    /// the value replacing `current`
+    pub fn applied_to(
+        self,
+        current: Option<&serde_json::Value>,
+    ) -> IcedFormValueResult<serde_json::Value> {
+        match (self, current) {
+            (Change::Set(value), _) => value,
+            (Change::Array(op), Some(serde_json::Value::Array(values))) => {
+                let mut values = values.clone();
+                op.apply(&mut values)
+                    .map_err(|e| IcedFormValueError::deserializing("serde_json::Value", e))?;
+                Ok(serde_json::Value::Array(values))
+            }
+            (Change::Array(_), _) => Err(IcedFormValueError::deserializing(
+                "serde_json::Value",
+                "not an array",
+            )),
+        }
+    }
+}
+
+impl UpdatedMessage {
+    pub fn set(selector: Selector, value: IcedFormValueResult<serde_json::Value>) -> Self {
+        Self {
+            selector,
+            change: Change::Set(value),
+        }
+    }
+
+    pub fn array(selector: Selector, op: ArrayOp) -> Self {
+        Self {
+            selector,
+            change: Change::Array(op),
+        }
+    }
+
+    pub fn apply(self, document: &mut serde_json::Value) -> IcedFormValueResult<()> {
+        let value = self
+            .change
+            .applied_to(self.selector.resolve(document))
+            .map_err(|e| e.at(&self.selector))?;
+        self.selector.set(document, value)
+    }
+
+    /// the same change for the document `root` points into, the inverse of
+    /// [`UpdatedMessage::below`]
+    pub fn under(self, root: &Selector) -> Self {
+        let selector = Selector(root.0.iter().chain(&self.selector.0).cloned().collect());
+        Self { selector, ..self }
+    }
+
+    /// the same change for a document that starts at `root`
+    pub fn below(self, root: &Selector) -> IcedFormValueResult<Self> {
+        let selector = self.selector.strip_prefix(root).ok_or_else(|| {
+            IcedFormValueError::deserializing("Selector", format!("not inside [{root}]"))
+                .at(&self.selector)
+        })?;
+        Ok(Self { selector, ..self })
+    }
+}
+
+/// `on_change` of a single value, changes become patches at `selector`
+pub fn patch_at<'a, Message>(
+    on_change: Arc<dyn Fn(UpdatedMessage) -> Message + 'a>,
+    selector: Selector,
+) -> Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>
+where
+    Message: 'a,
+{
+    Arc::new(move |value| {
+        on_change(match value {
+            Err(IcedFormValueError::Pending(update)) => update.under(&selector),
+            value => UpdatedMessage::set(selector.clone(), value),
+        })
+    })
+}
+
+/// `result` of a typed form showing `current`, with a pending array operation applied to it,
+/// see [`IcedFormValueError::Pending`]
+pub fn resolve<T>(result: IcedFormValueResult<T>, current: &T) -> IcedFormValueResult<T>
+where
+    T: Clone + Serialize + DeserializeOwned,
+{
+    match result {
+        Err(IcedFormValueError::Pending(update)) => {
+            let mut document = to_value(current.clone())?;
+            update.apply(&mut document)?;
+            from_value(&document)
+        }
+        result => result,
+    }
+}
+
+/// the pending array operation in `result`, moved under `root`, see
+/// [`IcedFormValueError::under`]
+pub fn pending<T>(result: &IcedFormValueResult<T>, root: &Selector) -> Option<IcedFormValueError> {
+    match result {
+        Err(e @ IcedFormValueError::Pending(_)) => Some(e.clone().under(root)),
+        _ => None,
+    }
 }
 // pub type Key = vec![];
 // pub struct Message {
@@ -118,8 +502,8 @@ pub struct UpdatedMessage {
 // impl_iced_form!(uuid::Uuid);
 
 use iced::{
-    pure::{checkbox, column, container, text, text_input},
-    Length,
+    pure::{button, checkbox, column, container, pick_list, row, text, text_input},
+    Alignment, Length,
 };
 
 #[derive(Clone, Debug)]
@@ -132,19 +516,13 @@ where
     pub fn to_buffer(&self) -> IcedFormValueResult<serde_json::Value> {
         serde_json::to_string(&self.0)
             .and_then(|v| serde_json::from_str(&v))
-            .map_err(|e| IcedFormValueError::Deserializing {
-                type_name: std::any::type_name::<T>(),
-                message: e.to_string(),
-            })
+            .map_err(|e| IcedFormValueError::deserializing(std::any::type_name::<T>(), e))
     }
 
     pub fn from_buffer(value: &serde_json::Value) -> IcedFormValueResult<Self> {
         serde_json::to_string(value)
             .and_then(|value| serde_json::from_str(&value))
-            .map_err(|e| IcedFormValueError::Deserializing {
-                type_name: std::any::type_name::<T>(),
-                message: e.to_string(),
-            })
+            .map_err(|e| IcedFormValueError::deserializing(std::any::type_name::<T>(), e))
             .map(Self)
     }
 }
@@ -154,10 +532,7 @@ pub fn to_value<T: Clone + Serialize + DeserializeOwned>(
 ) -> IcedFormValueResult<serde_json::Value> {
     serde_json::to_string(&val)
         .and_then(|v| serde_json::from_str(&v))
-        .map_err(|e| IcedFormValueError::Deserializing {
-            type_name: std::any::type_name::<T>(),
-            message: e.to_string(),
-        })
+        .map_err(|e| IcedFormValueError::deserializing(std::any::type_name::<T>(), e))
 }
 
 pub fn from_value<T: Clone + Serialize + DeserializeOwned>(
@@ -165,10 +540,34 @@ pub fn from_value<T: Clone + Serialize + DeserializeOwned>(
 ) -> IcedFormValueResult<T> {
     serde_json::to_string(value)
         .and_then(|value| serde_json::from_str(&value))
-        .map_err(|e| IcedFormValueError::Deserializing {
-            type_name: std::any::type_name::<T>(),
-            message: e.to_string(),
-        })
+        .map_err(|e| IcedFormValueError::deserializing(std::any::type_name::<T>(), e))
+}
+
+/// value for a field of a freshly selected enum variant: whatever of `from` fits into the
+/// field's type, objects are merged key by key over `T::default()`
+pub fn carried_over<T>(from: Option<&serde_json::Value>) -> T
+where
+    T: Default + Clone + Serialize + DeserializeOwned,
+{
+    let from = match from {
+        Some(from) => from,
+        None => return T::default(),
+    };
+    let mut value = match to_value(T::default()) {
+        Ok(serde_json::Value::Object(value)) => value,
+        _ => return from_value(from).unwrap_or_default(),
+    };
+    if let serde_json::Value::Object(from) = from {
+        for (key, field) in from {
+            if let Some(previous) = value.get(key).cloned() {
+                value.insert(key.clone(), field.clone());
+                if from_value::<T>(&serde_json::Value::Object(value.clone())).is_err() {
+                    value.insert(key.clone(), previous);
+                }
+            }
+        }
+    }
+    from_value(&serde_json::Value::Object(value)).unwrap_or_default()
 }
 
 // impl<'a, F, T> IcedForm<'a, F> for IcedFormBuffer<T>
@@ -197,8 +596,170 @@ where
         on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
         selector: Selector,
     ) -> iced::pure::widget::Container<'a, Message>;
+
+    /// `view` with the hints given in `#[iced_form(...)]`, types that have no use for them
+    /// ignore them
+    fn view_with(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        let _ = options;
+        self.view(on_change, selector)
+    }
+
+    /// `view_with` sending the changes as patches at the path of the value that changed, for
+    /// documents kept in an [`EditSession`]; values made of fields hand the patches of their
+    /// fields on, the rest sends their whole value
+    fn view_patches(
+        &'a self,
+        on_patch: Arc<dyn Fn(UpdatedMessage) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        self.view_with(patch_at(on_patch, selector.clone()), selector, options)
+    }
+
+    /// writes a patch of [`IcedForm::view_patches`] into the value, `path` is relative to it;
+    /// values made of fields hand it down, so only the value that changed is deserialized
+    fn set_at(
+        &mut self,
+        path: &[SelectorSegment],
+        value: serde_json::Value,
+    ) -> IcedFormValueResult<()>
+    where
+        Self: Serialize + DeserializeOwned,
+    {
+        set_at(self, path, value)
+    }
+}
+
+/// writes `value` below `path` of `target` by going through its JSON, see [`IcedForm::set_at`]
+pub fn set_at<T>(
+    target: &mut T,
+    path: &[SelectorSegment],
+    value: serde_json::Value,
+) -> IcedFormValueResult<()>
+where
+    T: Clone + Serialize + DeserializeOwned,
+{
+    let document = match path.is_empty() {
+        true => value,
+        false => {
+            let mut document = to_value(target.clone())?;
+            Selector(path.to_vec()).set(&mut document, value)?;
+            document
+        }
+    };
+    *target = from_value(&document)?;
+    Ok(())
+}
+
+/// presentation hints for a single field
+#[derive(Debug, Clone, Copy)]
+pub struct FieldOptions<'a> {
+    /// shown in an empty input, the selector is used when missing
+    pub placeholder: Option<&'static str>,
+    /// text edited line by line
+    pub multiline: bool,
+    /// handed down to nested fields, see [`Drafts`]
+    pub drafts: &'a Drafts,
+    /// handed down to nested fields, shown under the field they point at
+    pub errors: &'a ValidationErrors,
+}
+
+impl Default for FieldOptions<'_> {
+    fn default() -> Self {
+        Self {
+            placeholder: None,
+            multiline: false,
+            drafts: &NO_DRAFTS,
+            errors: &NO_ERRORS,
+        }
+    }
+}
+
+impl FieldOptions<'_> {
+    fn placeholder(&self, selector: &Selector) -> String {
+        self.placeholder
+            .map(str::to_owned)
+            .unwrap_or_else(|| selector.to_string())
+    }
+}
+
+/// text input for a value parsed from text, text that doesn't parse is sent as
+/// [`IcedFormValueError::Unparsed`] and shown from `options.drafts` with the error under it
+pub fn parsed_input<'a, T, Message>(
+    value: String,
+    parse: impl Fn(&str) -> IcedFormValueResult<T> + 'a,
+    on_change: Arc<dyn Fn(IcedFormValueResult<T>) -> Message + 'a>,
+    selector: Selector,
+    options: FieldOptions<'a>,
+) -> iced::pure::widget::Container<'a, Message>
+where
+    T: 'a,
+    Message: Clone + 'a,
+{
+    let draft = options.drafts.get(&selector);
+    let shown = draft.map(|draft| draft.text.clone()).unwrap_or(value);
+    let placeholder = options.placeholder(&selector);
+    let input = text_input(&placeholder, &shown, move |text| {
+        on_change(parse(&text).map_err(|e| {
+            IcedFormValueError::Unparsed(Draft {
+                selector: selector.clone(),
+                text,
+                message: e.to_string(),
+            })
+        }))
+    })
+    .width(Length::Fill);
+    let field = draft
+        .into_iter()
+        .fold(column().spacing(2).push(input), |field, draft| {
+            field.push(text(&draft.message).size(14))
+        });
+    container(field).width(Length::Fill)
 }
 
+/// one line per field that doesn't parse or breaks a rule, `path: error`, empty when there
+/// are none
+pub fn error_summary<'a, Message>(options: FieldOptions) -> iced::pure::widget::Column<'a, Message>
+where
+    Message: 'a,
+{
+    let unparsed = options
+        .drafts
+        .iter()
+        .map(|draft| format!("{}: {}", draft.selector, draft.message));
+    let invalid = options.errors.iter().map(|error| error.to_string());
+    unparsed
+        .chain(invalid)
+        .fold(column().spacing(2), |summary, line| {
+            summary.push(text(line).size(14))
+        })
+}
+
+/// the value as plain text, for fields that can't be edited
+pub fn readonly_view<'a, T, Message>(value: &T) -> iced::pure::widget::Container<'a, Message>
+where
+    T: Serialize,
+    Message: 'a,
+{
+    let value = match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(value)) => value,
+        Ok(value) => value.to_string(),
+        Err(e) => e.to_string(),
+    };
+    container(text(value)).width(Length::Fill)
+}
+
+/// new array elements, keyed by the name of the field holding the array
+pub type Templates = BTreeMap<String, serde_json::Value>;
+
+/// for forms without arrays or with arrays whose elements can start from their schema
+pub static NO_TEMPLATES: Templates = BTreeMap::new();
+
 impl<'a, Message> IcedForm<'a, Message> for serde_json::Value
 where
     Message: Clone + 'a,
@@ -208,88 +769,593 @@ where
         on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
         selector: Selector,
     ) -> iced::pure::widget::Container<'a, Message> {
-        match self {
-            serde_json::Value::Null => container(text(&format!("{selector}"))),
-            serde_json::Value::Bool(value) => container(
-                checkbox(&format!("{selector}"), *value, move |value| {
-                    on_change(to_value(value))
-                })
-                .width(Length::Fill),
-            )
-            .width(Length::Fill)
-            .height(Length::Fill),
-            serde_json::Value::Number(value) => container(
-                text_input(
-                    &format!("{selector}"),
-                    &serde_json::to_string(&serde_json::Value::Number(value.clone()))
-                        .expect("failed to serialize a number?"),
-                    move |value| {
-                        on_change(serde_json::from_str(&value).map_err(|e| {
-                            IcedFormValueError::Deserializing {
-                                type_name: "Number",
-                                message: e.to_string(),
-                            }
-                        }))
-                    },
-                )
-                .width(Length::Fill),
+        self.view_with(on_change, selector, Default::default())
+    }
+
+    /// every change carries the whole document, [`IcedForm::view_patches`] sends only the
+    /// value that changed
+    fn view_with(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        let root = selector.clone();
+        json_form(
+            self,
+            &schema::ANY,
+            Arc::new(move |update: UpdatedMessage| {
+                let update = match update.below(&root) {
+                    Ok(update) => update,
+                    Err(e) => return on_change(Err(e)),
+                };
+                if let Change::Array(_) = update.change {
+                    return on_change(Err(IcedFormValueError::Pending(Box::new(update))));
+                }
+                let mut value = self.clone();
+                on_change(update.apply(&mut value).map(|()| value))
+            }),
+            selector,
+            &NO_TEMPLATES,
+            options,
+        )
+    }
+
+    fn view_patches(
+        &'a self,
+        on_patch: Arc<dyn Fn(UpdatedMessage) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        json_form(
+            self,
+            &schema::ANY,
+            on_patch,
+            selector,
+            &NO_TEMPLATES,
+            options,
+        )
+    }
+
+    fn set_at(
+        &mut self,
+        path: &[SelectorSegment],
+        value: serde_json::Value,
+    ) -> IcedFormValueResult<()> {
+        Selector(path.to_vec()).set(self, value)
+    }
+}
+
+/// same shape as `value` with every leaf reset: empty strings, zeros, `false`, empty arrays
+pub fn empty_like(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Null => serde_json::Value::Null,
+        serde_json::Value::Bool(_) => serde_json::Value::Bool(false),
+        serde_json::Value::Number(_) => serde_json::Value::from(0),
+        serde_json::Value::String(_) => serde_json::Value::String(String::new()),
+        serde_json::Value::Array(_) => serde_json::Value::Array(vec![]),
+        serde_json::Value::Object(values) => serde_json::Value::Object(
+            values
+                .iter()
+                .map(|(key, value)| (key.clone(), empty_like(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// element added by "+": the field's template, otherwise the default of the element schema,
+/// otherwise an emptied copy of the last element, otherwise an empty string
+fn new_element(
+    values: &[serde_json::Value],
+    items: &FieldSchema,
+    selector: &Selector,
+    templates: &Templates,
+) -> serde_json::Value {
+    let template = match selector.0.last() {
+        Some(SelectorSegment::ObjectField(key)) => templates.get(key),
+        _ => None,
+    };
+    match (template, items, values.last()) {
+        (Some(template), _, _) => template.clone(),
+        (None, FieldSchema::Any, Some(last)) => empty_like(last),
+        (None, FieldSchema::Any, None) => serde_json::Value::String(String::new()),
+        (None, items, _) => items.default_value(),
+    }
+}
+
+/// move up, move down, duplicate and remove buttons for the element at `index` of an array
+/// holding `len` elements
+fn element_controls<'a, Message>(
+    element: iced::pure::widget::Row<'a, Message>,
+    len: usize,
+    index: usize,
+    on_op: &dyn Fn(ArrayOp) -> Message,
+) -> iced::pure::widget::Row<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    let mut up = button("^");
+    if index > 0 {
+        up = up.on_press(on_op(ArrayOp::MoveUp(index)));
+    }
+    let mut down = button("v");
+    if index + 1 < len {
+        down = down.on_press(on_op(ArrayOp::MoveDown(index)));
+    }
+    element
+        .push(up)
+        .push(down)
+        .push(button("2×").on_press(on_op(ArrayOp::Duplicate(index))))
+        .push(button("×").on_press(on_op(ArrayOp::Remove(index))))
+}
+
+/// the generic form for any JSON document, `schema` picks editors the bare value can't tell
+/// (options, decimals, dates, identifiers, enums), `templates` decide what new array
+/// elements look like, `options` hand down the drafts of fields that don't parse yet and the
+/// validation errors shown under the values they point at; every change is a patch of the
+/// value at its path, see [`UpdatedMessage::apply`]
+pub fn json_form<'a, Message>(
+    value: &'a serde_json::Value,
+    schema: &'a FieldSchema,
+    on_change: Arc<dyn Fn(UpdatedMessage) -> Message + 'a>,
+    selector: Selector,
+    templates: &'a Templates,
+    options: FieldOptions<'a>,
+) -> iced::pure::widget::Container<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    // hints of the field holding the document don't apply to the values inside
+    let options = FieldOptions {
+        drafts: options.drafts,
+        errors: options.errors,
+        ..Default::default()
+    };
+    let on_value = patch_at(on_change.clone(), selector.clone());
+    let at = selector.clone();
+    let form = match (schema, value) {
+        (FieldSchema::Optional(inner), serde_json::Value::Null) => container(
+            checkbox(format!("{selector}"), false, move |_| {
+                on_value(Ok(inner.default_value()))
+            })
+            .width(Length::Fill),
+        )
+        .width(Length::Fill),
+        (FieldSchema::Optional(inner), value) => container(
+            row()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(checkbox("", true, move |_| {
+                    on_value(Ok(serde_json::Value::Null))
+                }))
+                .push(json_form(
+                    value, inner, on_change, selector, templates, options,
+                )),
+        )
+        .width(Length::Fill),
+        (FieldSchema::Uuid, value) => readonly_view(value),
+        (FieldSchema::DateTime, value) => match from_value::<chrono::NaiveDateTime>(value) {
+            Ok(date) => date_picker::date_time_picker(
+                date,
+                Arc::new(move |date| on_value(date.and_then(to_value))),
+                selector,
+                options,
+            ),
+            Err(e) => container(text(e.to_string())),
+        },
+        (FieldSchema::Date, value) => match from_value::<chrono::NaiveDate>(value) {
+            Ok(date) => date_picker::date_picker(
+                date,
+                Arc::new(move |date| on_value(date.and_then(to_value))),
+                selector,
+                options,
+            ),
+            Err(e) => container(text(e.to_string())),
+        },
+        (FieldSchema::Enum(values), serde_json::Value::String(selected)) => container(pick_list(
+            values.as_slice(),
+            Some(selected.clone()),
+            move |selected| on_value(Ok(serde_json::Value::String(selected))),
+        ))
+        .width(Length::Fill),
+        (schema, value) if schema.is_text() && (value.is_string() || value.is_number()) => {
+            parsed_input(
+                schema.to_text(value),
+                |text| schema.parse(text),
+                on_value,
+                selector,
+                options,
             )
+        }
+        (schema, value) => untyped_form(value, schema, on_change, selector, templates, options),
+    };
+    with_errors(form, options.errors, &at)
+}
+
+/// editors picked by the shape of the value, `schema` is only handed down to the children
+fn untyped_form<'a, Message>(
+    value: &'a serde_json::Value,
+    schema: &'a FieldSchema,
+    on_change: Arc<dyn Fn(UpdatedMessage) -> Message + 'a>,
+    selector: Selector,
+    templates: &'a Templates,
+    options: FieldOptions<'a>,
+) -> iced::pure::widget::Container<'a, Message>
+where
+    Message: Clone + 'a,
+{
+    let on_value = patch_at(on_change.clone(), selector.clone());
+    match value {
+        serde_json::Value::Null => container(text(format!("{selector}"))),
+        serde_json::Value::Bool(value) => container(
+            checkbox(format!("{selector}"), *value, move |value| {
+                on_value(to_value(value))
+            })
+            .width(Length::Fill),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill),
+        serde_json::Value::Number(value) => parsed_input(
+            value.to_string(),
+            |text| FieldSchema::Number.parse(text),
+            on_value,
+            selector,
+            options,
+        ),
+        serde_json::Value::String(value) => container(
+            text_input(&format!("{selector}"), value, move |value| {
+                on_value(Ok(serde_json::Value::String(value)))
+            })
+            .width(Length::Fill),
+        )
+        .width(Length::Fill)
+        .height(Length::Fill),
+        serde_json::Value::Array(values) => {
+            let on_op = |op| on_change(UpdatedMessage::array(selector.clone(), op));
+            let elements =
+                values
+                    .iter()
+                    .enumerate()
+                    .fold(column().spacing(5), |acc, (index, value)| {
+                        let element = json_form(
+                            value,
+                            schema.items(),
+                            on_change.clone(),
+                            selector.push(SelectorSegment::ArrayIndex(index)),
+                            templates,
+                            options,
+                        );
+                        acc.push(element_controls(
+                            row().spacing(5).push(element),
+                            values.len(),
+                            index,
+                            &on_op,
+                        ))
+                    });
+            let added = new_element(values, schema.items(), &selector, templates);
+            container(elements.push(button("+").on_press(on_op(ArrayOp::Push(added)))))
+                .padding(10)
+                .width(Length::Fill)
+        }
+        serde_json::Value::Object(values) => {
+            container(values.iter().fold(column(), move |acc, (key, value)| {
+                acc.push(json_form(
+                    value,
+                    schema.field(key),
+                    on_change.clone(),
+                    selector.push(SelectorSegment::ObjectField(key.to_string())),
+                    templates,
+                    options,
+                ))
+            }))
+            .padding(10)
             .width(Length::Fill)
-            .height(Length::Fill),
-            serde_json::Value::String(value) => container(
-                text_input(&format!("{selector}"), value, move |value| {
+            .height(Length::Fill)
+        }
+    }
+}
+
+impl<'a, Message> IcedForm<'a, Message> for String
+where
+    Message: Clone + 'a,
+{
+    fn view(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        self.view_with(on_change, selector, Default::default())
+    }
+
+    fn view_with(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        let placeholder = options.placeholder(&selector);
+        if !options.multiline {
+            return container(
+                text_input(&placeholder, self, move |value| {
                     on_change(Ok(serde_json::Value::String(value)))
                 })
                 .width(Length::Fill),
             )
-            .width(Length::Fill)
-            .height(Length::Fill),
-            serde_json::Value::Array(values) => container(values.iter().enumerate().fold(
-                column(),
-                move |acc, (index, value)| {
-                    let on_change = on_change.clone();
-                    acc.push(value.view(
-                        Arc::new(move |value| match value {
-                            Ok(value) => {
-                                let mut new = values.clone();
-                                new.insert(index, value);
-                                on_change(Ok(serde_json::Value::Array(new)))
-                            }
-                            Err(e) => on_change(Err(e)),
-                        }),
-                        selector.push(SelectorSegment::ArrayIndex(index)),
+            .width(Length::Fill);
+        }
+        // there is no multiline input, every line gets its own and enter starts a new one
+        let lines = self.split('\n').map(str::to_owned).collect::<Vec<_>>();
+        let joined = |lines: Vec<String>| Ok(serde_json::Value::String(lines.join("\n")));
+        let editors = (0..lines.len()).fold(column().spacing(5), |acc, index| {
+            let mut with_new_line = lines.clone();
+            with_new_line.insert(index + 1, String::new());
+            let mut without_line = lines.clone();
+            without_line.remove(index);
+            let on_edit = on_change.clone();
+            let edited = lines.clone();
+            let mut line = row().spacing(5).push(
+                text_input(&placeholder, &lines[index], move |value| {
+                    let mut edited = edited.clone();
+                    edited[index] = value;
+                    on_edit(joined(edited))
+                })
+                .on_submit(on_change(joined(with_new_line)))
+                .width(Length::Fill),
+            );
+            if lines.len() > 1 {
+                line = line.push(button("×").on_press(on_change(joined(without_line))));
+            }
+            acc.push(line)
+        });
+        container(editors).width(Length::Fill)
+    }
+}
+
+impl<'a, Message> IcedForm<'a, Message> for bool
+where
+    Message: Clone + 'a,
+{
+    fn view(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        container(
+            checkbox(format!("{selector}"), *self, move |value| {
+                on_change(Ok(serde_json::Value::Bool(value)))
+            })
+            .width(Length::Fill),
+        )
+        .width(Length::Fill)
+    }
+}
+
+/// numbers are edited as text and parsed with their own `FromStr`
+macro_rules! impl_iced_form_for_number {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a, Message> IcedForm<'a, Message> for $ty
+            where
+                Message: Clone + 'a,
+            {
+                fn view(
+                    &'a self,
+                    on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+                    selector: Selector,
+                ) -> iced::pure::widget::Container<'a, Message> {
+                    self.view_with(on_change, selector, Default::default())
+                }
+
+                fn view_with(
+                    &'a self,
+                    on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+                    selector: Selector,
+                    options: FieldOptions<'a>,
+                ) -> iced::pure::widget::Container<'a, Message> {
+                    parsed_input(
+                        self.to_string(),
+                        |value| {
+                            <$ty>::from_str(value.trim())
+                                .map_err(|e| IcedFormValueError::deserializing(stringify!($ty), e))
+                                .and_then(to_value)
+                        },
+                        on_change,
+                        selector,
+                        options,
+                    )
+                }
+            }
+        )*
+    };
+}
+
+impl_iced_form_for_number!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+impl<'a, Message> IcedForm<'a, Message> for chrono::NaiveDateTime
+where
+    Message: Clone + 'a,
+{
+    fn view(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        self.view_with(on_change, selector, Default::default())
+    }
+
+    fn view_with(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        date_picker::date_time_picker(
+            *self,
+            Arc::new(move |value| on_change(value.and_then(to_value))),
+            selector,
+            options,
+        )
+    }
+}
+
+impl<'a, Message> IcedForm<'a, Message> for chrono::NaiveDate
+where
+    Message: Clone + 'a,
+{
+    fn view(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        self.view_with(on_change, selector, Default::default())
+    }
+
+    fn view_with(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        date_picker::date_picker(
+            *self,
+            Arc::new(move |value| on_change(value.and_then(to_value))),
+            selector,
+            options,
+        )
+    }
+}
+
+/// shown with a comma, parsed like [`FieldSchema::Decimal`]
+impl<'a, Message> IcedForm<'a, Message> for rust_decimal::Decimal
+where
+    Message: Clone + 'a,
+{
+    fn view(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        self.view_with(on_change, selector, Default::default())
+    }
+
+    fn view_with(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        parsed_input(
+            self.to_string().replace('.', ","),
+            |value| FieldSchema::Decimal.parse(value),
+            on_change,
+            selector,
+            options,
+        )
+    }
+}
+
+/// elements one under another, each removable, new ones start from `Default`
+impl<'a, Message, T> IcedForm<'a, Message> for Vec<T>
+where
+    Message: Clone + 'a,
+    T: IcedForm<'a, Message> + Default + Serialize + DeserializeOwned + 'a,
+{
+    fn view(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        self.view_with(on_change, selector, Default::default())
+    }
+
+    fn view_with(
+        &'a self,
+        on_change: Arc<dyn Fn(IcedFormValueResult<serde_json::Value>) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        // pressing a button doesn't rebuild the array, the operation is applied by `resolve`
+        let on_op = |op| {
+            on_change(Err(IcedFormValueError::Pending(Box::new(
+                UpdatedMessage::array(Selector::empty(), op),
+            ))))
+        };
+        let elements =
+            self.iter()
+                .enumerate()
+                .fold(column().spacing(5), |acc, (index, element)| {
+                    let on_edit = on_change.clone();
+                    let at = Selector::empty().push(SelectorSegment::ArrayIndex(index));
+                    acc.push(element_controls(
+                        row().spacing(5).push(element.view_with(
+                            Arc::new(move |value| {
+                                on_edit(value.map_err(|e| e.under(&at)).and_then(|value| {
+                                    let mut edited = to_value(self.clone())?;
+                                    edited[index] = value;
+                                    Ok(edited)
+                                }))
+                            }),
+                            selector.push(SelectorSegment::ArrayIndex(index)),
+                            options,
+                        )),
+                        self.len(),
+                        index,
+                        &on_op,
                     ))
-                },
-            ))
-            .padding(10)
-            .width(Length::Fill)
-            .height(Length::Fill),
-            serde_json::Value::Object(values) => {
-                container(values.iter().fold(column(), move |acc, (key, value)| {
-                    let on_change = on_change.clone();
-                    acc.push(value.view(
-                        Arc::new(move |value| match value {
-                            Ok(value) => {
-                                let mut new = values.clone();
-                                new.insert(key.to_string(), value);
-                                on_change(Ok(serde_json::Value::Object(new)))
-                            }
-                            Err(e) => on_change(Err(e)),
-                        }),
-                        selector.push(SelectorSegment::ObjectField(key.to_string())),
+                });
+        let added = match to_value(T::default()) {
+            Ok(new) => on_op(ArrayOp::Push(new)),
+            Err(e) => on_change(Err(e)),
+        };
+        container(elements.push(button("+").on_press(added))).width(Length::Fill)
+    }
+
+    fn view_patches(
+        &'a self,
+        on_patch: Arc<dyn Fn(UpdatedMessage) -> Message + 'a>,
+        selector: Selector,
+        options: FieldOptions<'a>,
+    ) -> iced::pure::widget::Container<'a, Message> {
+        let on_op = |op| on_patch(UpdatedMessage::array(selector.clone(), op));
+        let elements =
+            self.iter()
+                .enumerate()
+                .fold(column().spacing(5), |acc, (index, element)| {
+                    acc.push(element_controls(
+                        row().spacing(5).push(element.view_patches(
+                            on_patch.clone(),
+                            selector.push(SelectorSegment::ArrayIndex(index)),
+                            options,
+                        )),
+                        self.len(),
+                        index,
+                        &on_op,
                     ))
-                }))
-                .padding(10)
-                .width(Length::Fill)
-                .height(Length::Fill)
+                });
+        let added = to_value(T::default()).map(ArrayOp::Push);
+        let added = match added {
+            Ok(op) => on_op(op),
+            Err(e) => on_patch(UpdatedMessage::set(selector.clone(), Err(e))),
+        };
+        container(elements.push(button("+").on_press(added))).width(Length::Fill)
+    }
+
+    fn set_at(
+        &mut self,
+        path: &[SelectorSegment],
+        value: serde_json::Value,
+    ) -> IcedFormValueResult<()> {
+        match path.split_first() {
+            Some((SelectorSegment::ArrayIndex(index), rest)) if *index < self.len() => {
+                self[*index].set_at(rest, value)
             }
+            _ => set_at(self, path, value),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::*;
     #[test]
     fn test_serialization() {}
 }
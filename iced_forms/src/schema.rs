@@ -0,0 +1,299 @@
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use schemars::{
+    schema::{InstanceType, Schema, SchemaObject, SingleOrVec},
+    JsonSchema, Map,
+};
+
+use crate::{
+    date_picker::{self, DATE_DISPLAY_FORMAT, DATE_TIME_DISPLAY_FORMAT},
+    IcedFormValueError, IcedFormValueResult,
+};
+
+/// pattern `schemars` gives to `rust_decimal::Decimal`, the only thing telling it apart from text
+const DECIMAL_PATTERN: &str = r"^-?[0-9]+(\.[0-9]+)?$";
+
+pub(crate) static ANY: FieldSchema = FieldSchema::Any;
+
+/// what the JSON form knows about a value, derived from the Rust type with `schemars`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum FieldSchema {
+    Bool,
+    Integer,
+    Number,
+    /// `rust_decimal::Decimal`, kept as a string
+    Decimal,
+    String,
+    /// `chrono::NaiveDateTime`
+    DateTime,
+    /// `chrono::NaiveDate`
+    Date,
+    /// identifiers are shown but never edited
+    Uuid,
+    /// one of the listed strings, e.g. a unit-only enum
+    Enum(Vec<String>),
+    /// `Option<T>`, `null` when missing
+    Optional(Box<FieldSchema>),
+    Array(Box<FieldSchema>),
+    Object(Vec<(String, FieldSchema)>),
+    /// anything else, edited by the shape of the value alone
+    #[default]
+    Any,
+}
+
+impl FieldSchema {
+    pub fn of<T: JsonSchema>() -> Self {
+        let root = schemars::schema_for!(T);
+        Self::from_object(&root.schema, &root.definitions, &mut vec![])
+    }
+
+    fn from_schema(
+        schema: &Schema,
+        definitions: &Map<String, Schema>,
+        seen: &mut Vec<String>,
+    ) -> Self {
+        match schema {
+            Schema::Object(schema) => Self::from_object(schema, definitions, seen),
+            Schema::Bool(_) => Self::Any,
+        }
+    }
+
+    /// `seen` holds the definitions being resolved, recursive types end up as [`FieldSchema::Any`]
+    fn from_object(
+        schema: &SchemaObject,
+        definitions: &Map<String, Schema>,
+        seen: &mut Vec<String>,
+    ) -> Self {
+        if let Some(reference) = &schema.reference {
+            let name = reference.trim_start_matches("#/definitions/");
+            return match definitions.get(name) {
+                Some(definition) if !seen.iter().any(|seen| seen == name) => {
+                    seen.push(name.to_owned());
+                    let resolved = Self::from_schema(definition, definitions, seen);
+                    seen.pop();
+                    resolved
+                }
+                _ => Self::Any,
+            };
+        }
+        if let Some(subschemas) = &schema.subschemas {
+            let alternatives = subschemas
+                .any_of
+                .as_ref()
+                .or(subschemas.one_of.as_ref())
+                .or(subschemas.all_of.as_ref())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            return Self::from_alternatives(alternatives, definitions, seen);
+        }
+        if let Some(values) = &schema.enum_values {
+            return values
+                .iter()
+                .map(|value| value.as_str().map(str::to_owned))
+                .collect::<Option<Vec<_>>>()
+                .map(Self::Enum)
+                .unwrap_or(Self::Any);
+        }
+        let instance_type = match &schema.instance_type {
+            Some(SingleOrVec::Single(instance_type)) => **instance_type,
+            Some(SingleOrVec::Vec(instance_types)) => {
+                let mut not_null = instance_types
+                    .iter()
+                    .filter(|instance_type| **instance_type != InstanceType::Null);
+                return match (not_null.next(), not_null.next()) {
+                    (Some(instance_type), None) => {
+                        let schema = SchemaObject {
+                            instance_type: Some((*instance_type).into()),
+                            ..schema.clone()
+                        };
+                        Self::Optional(Box::new(Self::from_object(&schema, definitions, seen)))
+                    }
+                    _ => Self::Any,
+                };
+            }
+            None => return Self::Any,
+        };
+        match instance_type {
+            InstanceType::Boolean => Self::Bool,
+            InstanceType::Integer => Self::Integer,
+            InstanceType::Number => Self::Number,
+            InstanceType::String => match schema.format.as_deref() {
+                Some("partial-date-time" | "date-time") => Self::DateTime,
+                Some("date") => Self::Date,
+                Some("uuid") => Self::Uuid,
+                _ if schema
+                    .string
+                    .as_ref()
+                    .and_then(|string| string.pattern.as_deref())
+                    == Some(DECIMAL_PATTERN) =>
+                {
+                    Self::Decimal
+                }
+                _ => Self::String,
+            },
+            InstanceType::Array => {
+                match schema.array.as_ref().and_then(|array| array.items.as_ref()) {
+                    Some(SingleOrVec::Single(items)) => {
+                        Self::Array(Box::new(Self::from_schema(items, definitions, seen)))
+                    }
+                    _ => Self::Array(Box::new(Self::Any)),
+                }
+            }
+            InstanceType::Object => Self::Object(
+                schema
+                    .object
+                    .iter()
+                    .flat_map(|object| object.properties.iter())
+                    .map(|(name, property)| {
+                        (name.clone(), Self::from_schema(property, definitions, seen))
+                    })
+                    .collect(),
+            ),
+            InstanceType::Null => Self::Any,
+        }
+    }
+
+    /// `anyOf`/`oneOf`/`allOf`: an option (`T` or `null`), variants of a unit-only enum that
+    /// carry doc comments, or a single wrapped schema
+    fn from_alternatives(
+        alternatives: &[Schema],
+        definitions: &Map<String, Schema>,
+        seen: &mut Vec<String>,
+    ) -> Self {
+        let is_null = |schema: &&Schema| {
+            matches!(
+                schema,
+                Schema::Object(SchemaObject {
+                    instance_type: Some(SingleOrVec::Single(instance_type)),
+                    ..
+                }) if **instance_type == InstanceType::Null
+            )
+        };
+        let nullable = alternatives.iter().any(|schema| is_null(&schema));
+        let mut alternatives = alternatives
+            .iter()
+            .filter(|schema| !is_null(schema))
+            .map(|schema| Self::from_schema(schema, definitions, seen))
+            .collect::<Vec<_>>();
+        match (alternatives.len(), nullable) {
+            (1, true) => Self::Optional(Box::new(alternatives.remove(0))),
+            (1, false) => alternatives.remove(0),
+            (0, _) => Self::Any,
+            _ => alternatives
+                .into_iter()
+                .map(|alternative| match alternative {
+                    Self::Enum(values) => Some(values),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(|values| Self::Enum(values.concat()))
+                .unwrap_or(Self::Any),
+        }
+    }
+
+    /// schema of the elements of an array
+    pub fn items(&self) -> &Self {
+        match self {
+            Self::Array(items) => items,
+            _ => &ANY,
+        }
+    }
+
+    /// schema of an object's field
+    pub fn field(&self, name: &str) -> &Self {
+        match self {
+            Self::Object(fields) => fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, schema)| schema)
+                .unwrap_or(&ANY),
+            _ => &ANY,
+        }
+    }
+
+    /// value a freshly enabled option or a new array element starts with
+    pub fn default_value(&self) -> serde_json::Value {
+        let now = chrono::Local::now().naive_local();
+        match self {
+            Self::Bool => serde_json::Value::Bool(false),
+            Self::Integer | Self::Number => serde_json::Value::from(0),
+            Self::Decimal => serde_json::Value::String("0".to_owned()),
+            Self::String => serde_json::Value::String(String::new()),
+            Self::DateTime => serde_json::to_value(now).unwrap_or_default(),
+            Self::Date => serde_json::to_value(now.date()).unwrap_or_default(),
+            Self::Uuid => serde_json::Value::String(uuid::Uuid::new_v4().to_string()),
+            Self::Enum(values) => values
+                .first()
+                .cloned()
+                .map(serde_json::Value::String)
+                .unwrap_or_default(),
+            Self::Optional(_) | Self::Any => serde_json::Value::Null,
+            Self::Array(_) => serde_json::Value::Array(vec![]),
+            Self::Object(fields) => serde_json::Value::Object(
+                fields
+                    .iter()
+                    .map(|(name, schema)| (name.clone(), schema.default_value()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// values edited in a text input: decimals and numbers, dates get a picker
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Decimal | Self::Integer | Self::Number)
+    }
+
+    /// the text shown for a value edited as text, decimals use a comma and dates the Polish
+    /// order
+    pub fn to_text(&self, value: &serde_json::Value) -> String {
+        match (self, value) {
+            (Self::Decimal, serde_json::Value::String(value)) => value.replace('.', ","),
+            (Self::DateTime, value) => serde_json::from_value::<NaiveDateTime>(value.clone())
+                .map(|value| value.format(DATE_TIME_DISPLAY_FORMAT).to_string())
+                .unwrap_or_default(),
+            (Self::Date, value) => serde_json::from_value::<NaiveDate>(value.clone())
+                .map(|value| value.format(DATE_DISPLAY_FORMAT).to_string())
+                .unwrap_or_default(),
+            (_, serde_json::Value::String(value)) => value.clone(),
+            (_, value) => value.to_string(),
+        }
+    }
+
+    /// reads what was typed into a text input, decimals take both a comma and a dot and
+    /// ignore spaces between thousands
+    pub fn parse(&self, text: &str) -> IcedFormValueResult<serde_json::Value> {
+        let text = text.trim();
+        let error = IcedFormValueError::deserializing;
+        match self {
+            Self::Decimal => {
+                let text = text
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(|c| if c == ',' { '.' } else { c })
+                    .collect::<String>();
+                rust_decimal::Decimal::from_str(&text)
+                    .map(|value| serde_json::Value::String(value.to_string()))
+                    .map_err(|e| error("Decimal", e.to_string()))
+            }
+            Self::DateTime => date_picker::parse_date_time(text)
+                .ok_or_else(|| {
+                    error(
+                        "NaiveDateTime",
+                        format!("expected {DATE_TIME_DISPLAY_FORMAT}"),
+                    )
+                })
+                .and_then(crate::to_value),
+            Self::Date => date_picker::parse_date(text)
+                .ok_or_else(|| error("NaiveDate", format!("expected {DATE_DISPLAY_FORMAT}")))
+                .and_then(crate::to_value),
+            Self::Integer => i64::from_str(text)
+                .map(serde_json::Value::from)
+                .or_else(|_| u64::from_str(text).map(serde_json::Value::from))
+                .map_err(|e| error("Integer", e.to_string())),
+            _ => serde_json::from_str::<serde_json::Number>(text)
+                .map(serde_json::Value::Number)
+                .map_err(|e| error("Number", e.to_string())),
+        }
+    }
+}
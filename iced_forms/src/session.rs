@@ -0,0 +1,242 @@
+use iced::keyboard::{self, KeyCode};
+use serde_json::Value;
+
+use crate::{
+    Drafts, FieldOptions, IcedFormValueResult, Selector, SelectorSegment, UpdatedMessage,
+    ValidationErrors, Validators,
+};
+
+/// what [`history_shortcut`] asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryAction {
+    Undo,
+    Redo,
+}
+
+/// a change recorded at the deepest path that changed
+#[derive(Debug, Clone)]
+struct Edit {
+    selector: Selector,
+    before: Value,
+    after: Value,
+}
+
+/// a document being edited with the patches of [`crate::json_form`]: keeps the loaded value to
+/// tell whether anything changed, the history for undo and redo, the drafts of the fields and
+/// the errors of its validators
+#[derive(Debug, Clone)]
+pub struct EditSession {
+    original: Value,
+    value: Value,
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    drafts: Drafts,
+    validators: Validators,
+    errors: ValidationErrors,
+}
+
+impl EditSession {
+    pub fn new(value: Value) -> Self {
+        Self {
+            original: value.clone(),
+            value,
+            undo: vec![],
+            redo: vec![],
+            drafts: Default::default(),
+            validators: Default::default(),
+            errors: Default::default(),
+        }
+    }
+
+    /// checks the value with `validators` now and after every change
+    pub fn validated_by(mut self, validators: Validators) -> Self {
+        self.validators = validators;
+        self.revalidate();
+        self
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn drafts(&self) -> &Drafts {
+        &self.drafts
+    }
+
+    pub fn errors(&self) -> &ValidationErrors {
+        &self.errors
+    }
+
+    /// drafts and errors for the fields of the form
+    pub fn field_options(&self) -> FieldOptions<'_> {
+        FieldOptions {
+            drafts: &self.drafts,
+            errors: &self.errors,
+            ..Default::default()
+        }
+    }
+
+    /// every field parses and no rule is broken
+    pub fn can_submit(&self) -> bool {
+        self.drafts.is_empty() && self.errors.is_empty()
+    }
+
+    /// applies a patch and records it; typing into the same text field is undone at once, text
+    /// that doesn't parse only becomes a draft; `Ok(false)` when nothing changed
+    pub fn update(&mut self, update: UpdatedMessage) -> IcedFormValueResult<bool> {
+        let update = match self.drafts.track_update(update)? {
+            Some(update) => update,
+            None => return Ok(false),
+        };
+        let selector = update.selector;
+        let before = selector.resolve(&self.value).cloned();
+        let after = update
+            .change
+            .applied_to(before.as_ref())
+            .map_err(|e| e.at(&selector))?;
+        let edit = match &before {
+            Some(before) if *before == after => return Ok(false),
+            Some(before) => narrowed(selector.clone(), before, &after),
+            None => None,
+        };
+        selector.set(&mut self.value, after)?;
+        self.revalidate();
+        let edit = match edit {
+            Some(edit) => edit,
+            None => return Ok(true),
+        };
+        self.redo.clear();
+        match self.undo.last_mut() {
+            Some(last)
+                if last.selector == edit.selector
+                    && last.after.is_string()
+                    && edit.after.is_string() =>
+            {
+                last.after = edit.after
+            }
+            _ => self.undo.push(edit),
+        }
+        Ok(true)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// `false` when there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        match self.undo.pop() {
+            Some(edit) => {
+                self.restore(&edit.selector, edit.before.clone());
+                self.redo.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `false` when there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        match self.redo.pop() {
+            Some(edit) => {
+                self.restore(&edit.selector, edit.after.clone());
+                self.undo.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn perform(&mut self, action: HistoryAction) -> bool {
+        match action {
+            HistoryAction::Undo => self.undo(),
+            HistoryAction::Redo => self.redo(),
+        }
+    }
+
+    /// the value differs from the one the session started with or was last saved as
+    pub fn is_dirty(&self) -> bool {
+        self.value != self.original
+    }
+
+    /// what the value is compared to after it was saved, the history stays
+    pub fn set_original(&mut self, original: Value) {
+        self.original = original;
+    }
+
+    fn restore(&mut self, selector: &Selector, value: Value) {
+        self.drafts.discard(selector);
+        // recorded paths keep existing, edits are only undone in order
+        let _ = selector.set(&mut self.value, value);
+        self.revalidate();
+    }
+
+    fn revalidate(&mut self) {
+        self.errors = self.validators.validate(&self.value);
+    }
+}
+
+/// the edit at the deepest path where `before` and `after` differ, so changing one field of a
+/// big value records only that field
+fn narrowed(selector: Selector, before: &Value, after: &Value) -> Option<Edit> {
+    let child = match (before, after) {
+        (Value::Object(before), Value::Object(after))
+            if before.len() == after.len() && before.keys().all(|key| after.contains_key(key)) =>
+        {
+            let mut changed = after
+                .iter()
+                .filter(|(key, value)| before.get(*key) != Some(value));
+            match (changed.next(), changed.next()) {
+                (Some((key, after)), None) => Some((
+                    SelectorSegment::ObjectField(key.clone()),
+                    &before[key],
+                    after,
+                )),
+                _ => None,
+            }
+        }
+        (Value::Array(before), Value::Array(after)) if before.len() == after.len() => {
+            let mut changed = before
+                .iter()
+                .zip(after)
+                .enumerate()
+                .filter(|(_, (before, after))| before != after);
+            match (changed.next(), changed.next()) {
+                (Some((index, (before, after))), None) => {
+                    Some((SelectorSegment::ArrayIndex(index), before, after))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    match child {
+        Some((segment, before, after)) => narrowed(selector.push(segment), before, after),
+        None if before == after => None,
+        None => Some(Edit {
+            selector,
+            before: before.clone(),
+            after: after.clone(),
+        }),
+    }
+}
+
+/// Ctrl+Z undoes, Ctrl+Y and Ctrl+Shift+Z redo (Cmd on macOS)
+pub fn history_shortcut(event: &keyboard::Event) -> Option<HistoryAction> {
+    match event {
+        keyboard::Event::KeyPressed {
+            key_code,
+            modifiers,
+        } if modifiers.command() => match key_code {
+            KeyCode::Z if modifiers.shift() => Some(HistoryAction::Redo),
+            KeyCode::Z => Some(HistoryAction::Undo),
+            KeyCode::Y => Some(HistoryAction::Redo),
+            _ => None,
+        },
+        _ => None,
+    }
+}
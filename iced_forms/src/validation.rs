@@ -0,0 +1,140 @@
+use std::{collections::BTreeMap, fmt::Display, sync::Arc};
+
+use iced::{
+    pure::{column, container, text, Element},
+    Length,
+};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::Selector;
+
+/// checks the value at one path, `null` when the path is missing
+pub type FieldValidator = Arc<dyn Fn(&Value) -> Result<(), String> + Send + Sync>;
+/// checks the whole document, for rules spanning several fields
+pub type DocumentValidator = Arc<dyn Fn(&Value) -> Vec<FieldError> + Send + Sync>;
+
+/// a rule a value breaks, the empty selector stands for the whole document; usable as an
+/// `anyhow` error so model checks can point at a field
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{}{message}", .selector.location())]
+pub struct FieldError {
+    pub selector: Selector,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(selector: Selector, message: impl Display) -> Self {
+        Self {
+            selector,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// rules checked after every change of an [`crate::EditSession`]
+#[derive(Clone, Default)]
+pub struct Validators {
+    fields: Vec<(Selector, FieldValidator)>,
+    documents: Vec<DocumentValidator>,
+}
+
+impl std::fmt::Debug for Validators {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Validators")
+            .field(
+                "fields",
+                &self
+                    .fields
+                    .iter()
+                    .map(|(selector, _)| selector.to_string())
+                    .collect::<Vec<_>>(),
+            )
+            .field("documents", &self.documents.len())
+            .finish()
+    }
+}
+
+impl Validators {
+    pub fn field(
+        mut self,
+        selector: Selector,
+        validator: impl Fn(&Value) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.fields.push((selector, Arc::new(validator)));
+        self
+    }
+
+    pub fn document(
+        mut self,
+        validator: impl Fn(&Value) -> Vec<FieldError> + Send + Sync + 'static,
+    ) -> Self {
+        self.documents.push(Arc::new(validator));
+        self
+    }
+
+    pub fn validate(&self, document: &Value) -> ValidationErrors {
+        let fields = self.fields.iter().filter_map(|(selector, validator)| {
+            validator(selector.resolve(document).unwrap_or(&Value::Null))
+                .err()
+                .map(|message| FieldError::new(selector.clone(), message))
+        });
+        let documents = self
+            .documents
+            .iter()
+            .flat_map(|validator| validator(document));
+        let mut errors = ValidationErrors::default();
+        for error in fields.chain(documents) {
+            errors
+                .0
+                .entry(error.selector)
+                .or_default()
+                .push(error.message);
+        }
+        errors
+    }
+}
+
+/// messages of the broken rules by the path they point at
+#[derive(Debug, Clone, Default)]
+pub struct ValidationErrors(BTreeMap<Selector, Vec<String>>);
+
+/// for forms that aren't validated
+pub static NO_ERRORS: ValidationErrors = ValidationErrors(BTreeMap::new());
+
+impl ValidationErrors {
+    pub fn get(&self, selector: &Selector) -> &[String] {
+        self.0.get(selector).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// in the order of their paths
+    pub fn iter(&self) -> impl Iterator<Item = FieldError> + '_ {
+        self.0.iter().flat_map(|(selector, messages)| {
+            messages
+                .iter()
+                .map(|message| FieldError::new(selector.clone(), message))
+        })
+    }
+}
+
+/// `form` with the errors found at `selector` under it
+pub fn with_errors<'a, Message>(
+    form: impl Into<Element<'a, Message>>,
+    errors: &ValidationErrors,
+    selector: &Selector,
+) -> iced::pure::widget::Container<'a, Message>
+where
+    Message: 'a,
+{
+    let field = errors
+        .get(selector)
+        .iter()
+        .fold(column().spacing(2).push(form), |field, message| {
+            field.push(text(message).size(14))
+        });
+    container(field).width(Length::Fill)
+}
@@ -1,32 +1,80 @@
 extern crate proc_macro2;
 
 use darling::{
+    ast,
     FromDeriveInput,
-    FromMeta,
+    FromField,
+    FromVariant,
 };
 
-use proc_macro::{
-    Ident,
-    TokenStream,
-};
-use quote::quote;
-use syn::{
-    parse_macro_input,
-    spanned::Spanned,
-    TypePath,
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{
+    format_ident,
+    quote,
 };
+use syn::parse_macro_input;
 
-#[derive(FromDeriveInput, Debug, Default)]
-#[darling(default, attributes(iced_form), forward_attrs(allow, doc, cfg))]
+#[derive(FromDeriveInput, Debug)]
+#[darling(attributes(iced_form), supports(struct_named, enum_any))]
 struct IcedFormDeriveOptions {
-    derive_into: Option<syn::Path>,
+    ident: syn::Ident,
+    vis: syn::Visibility,
+    generics: syn::Generics,
+    data: ast::Data<IcedFormVariantOptions, IcedFormFieldOptions>,
+}
+
+/// `#[iced_form(...)]` on a field
+#[derive(FromField, Debug)]
+#[darling(attributes(iced_form))]
+struct IcedFormFieldOptions {
+    ident: Option<syn::Ident>,
+    ty: syn::Type,
+    /// shown next to the field instead of its name
+    #[darling(default)]
+    label: Option<String>,
+    /// shown in the empty input instead of the field's name
+    #[darling(default)]
+    placeholder: Option<String>,
+    /// not shown and not editable, the value is kept as it is
+    #[darling(default)]
+    skip: bool,
+    /// shown as text
+    #[darling(default)]
+    readonly: bool,
+    /// text edited line by line
+    #[darling(default)]
+    multiline: bool,
+    /// function used instead of `IcedForm::view`, with the same arguments
+    #[darling(default)]
+    widget: Option<syn::Path>,
+    /// fields are shown by ascending order, those without it count as 0
+    #[darling(default)]
+    order: Option<i64>,
+    /// fields with the same group are shown together under its title
+    #[darling(default)]
+    group: Option<String>,
+    /// shown under the field
+    #[darling(default)]
+    help: Option<String>,
+}
+
+/// `#[iced_form(...)]` on an enum variant
+#[derive(FromVariant, Debug)]
+#[darling(attributes(iced_form))]
+struct IcedFormVariantOptions {
+    ident: syn::Ident,
+    fields: ast::Fields<IcedFormFieldOptions>,
+    /// shown in the variant picker instead of the variant's name
+    #[darling(default)]
+    label: Option<String>,
 }
 
 fn pretty_print_tokenstream(code: &'_ TokenStream) {
     fn try_format(input: &'_ str) -> Option<String> {
         Some({
             let mut child = ::std::process::Command::new("rustfmt")
-                .args(&["--edition", "2021"])
+                .args(["--edition", "2021"])
                 .stdin(::std::process::Stdio::piped())
                 .stdout(::std::process::Stdio::piped())
                 .stderr(::std::process::Stdio::piped())
@@ -55,102 +103,764 @@ fn pretty_print_tokenstream(code: &'_ TokenStream) {
     eprintln!("{}", code);
 }
 
-#[proc_macro_derive(IcedFormDerive, attributes(iced_form))]
-pub fn derive(input: TokenStream) -> TokenStream {
-    let ast = parse_macro_input!(input);
-    let options = IcedFormDeriveOptions::from_derive_input(&ast).expect("wrong options");
+/// `serial_number` -> `SerialNumber`
+fn to_variant_name(field: &syn::Ident) -> syn::Ident {
+    let name = field
+        .to_string()
+        .trim_start_matches("r#")
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<String>();
+    syn::Ident::new(&name, field.span())
+}
 
-    let name = &ast.ident;
+/// `serial_number` -> `serial number`
+fn to_label(field: &syn::Ident) -> String {
+    field.to_string().trim_start_matches("r#").replace('_', " ")
+}
 
-    let fields = if let syn::Data::Struct(syn::DataStruct {
-        fields: syn::Fields::Named(syn::FieldsNamed { ref named, .. }),
-        ..
-    }) = ast.data
+/// `PrivateCustomer` -> `private customer`
+fn to_variant_label(variant: &syn::Ident) -> String {
+    let mut label = String::new();
+    for (index, char) in variant
+        .to_string()
+        .trim_start_matches("r#")
+        .chars()
+        .enumerate()
     {
-        named
-    } else {
-        unimplemented!();
+        if char.is_uppercase() && index > 0 {
+            label.push(' ');
+        }
+        label.extend(char.to_lowercase());
+    }
+    label
+}
+
+fn to_key(field: &syn::Ident) -> String {
+    field.to_string().trim_start_matches("r#").to_owned()
+}
+
+/// where the changes of a field's form go
+enum FieldChanges {
+    /// wrapped in the typed message, the path to `on_update`
+    Typed(TokenStream2),
+    /// as patches at their own path to `on_patch`, see `IcedForm::view_patches`
+    Patches,
+}
+
+/// a labelled row with the field's own form
+fn field_row(
+    field: &IcedFormFieldOptions,
+    default_label: Option<String>,
+    value: TokenStream2,
+    changes: &FieldChanges,
+    selector: TokenStream2,
+) -> TokenStream2 {
+    let ty = &field.ty;
+    let on_change = match changes {
+        FieldChanges::Typed(message) => quote! {
+            {
+                let on_update = on_update.clone();
+                let selector = selector.clone();
+                ::std::sync::Arc::new(move |value| {
+                    on_update(#message(
+                        value
+                            .and_then(|value| iced_forms::from_value::<#ty>(&value))
+                            .map_err(|e| e.at(&selector)),
+                    ))
+                })
+            }
+        },
+        FieldChanges::Patches => {
+            quote!(iced_forms::patch_at(on_patch.clone(), selector.clone()))
+        }
     };
-    let fields_with_types = fields
-        .iter()
-        .map(|f| {
-            let ty = f.ty.clone();
-            let ident = f.ident.as_ref().cloned().unwrap();
-            (ident, ty)
+    let (view, handler) = match changes {
+        FieldChanges::Typed(_) => (quote!(iced_forms::IcedForm::view_with), on_change.clone()),
+        FieldChanges::Patches => (
+            quote!(iced_forms::IcedForm::view_patches),
+            quote!(on_patch.clone()),
+        ),
+    };
+    let form = match (&field.widget, field.readonly) {
+        (_, true) => quote!(iced_forms::readonly_view(#value)),
+        (Some(widget), false) => quote! {
+            {
+                let selector = #selector;
+                iced_forms::with_errors(
+                    #widget(#value, #on_change, selector.clone()),
+                    options.errors,
+                    &selector,
+                )
+            }
+        },
+        (None, false) => {
+            let placeholder = match &field.placeholder {
+                Some(placeholder) => quote!(Some(#placeholder)),
+                None => quote!(None),
+            };
+            let multiline = field.multiline;
+            quote! {
+                {
+                    let selector = #selector;
+                    iced_forms::with_errors(
+                        #view(
+                            #value,
+                            #handler,
+                            selector.clone(),
+                            iced_forms::FieldOptions {
+                                placeholder: #placeholder,
+                                multiline: #multiline,
+                                ..options
+                            },
+                        ),
+                        options.errors,
+                        &selector,
+                    )
+                }
+            }
+        }
+    };
+    let row = match field.label.clone().or(default_label) {
+        Some(label) => quote! {
+            iced_forms::iced::pure::row()
+                .spacing(10)
+                .push(
+                    iced_forms::iced::pure::text(#label)
+                        .width(iced_forms::iced::Length::Units(200)),
+                )
+                .push(#form)
+        },
+        None => form,
+    };
+    match &field.help {
+        Some(help) => quote! {
+            iced_forms::iced::pure::column()
+                .spacing(5)
+                .push(#row)
+                .push(iced_forms::iced::pure::text(#help).size(14))
+        },
+        None => row,
+    }
+}
+
+/// `.push(..)` calls for the rows, sorted by `order`; a group is shown where its first
+/// field would be
+fn layout_rows(mut rows: Vec<(&IcedFormFieldOptions, TokenStream2)>) -> Vec<TokenStream2> {
+    rows.sort_by_key(|(field, _)| field.order.unwrap_or_default());
+    let mut groups: Vec<(Option<&str>, Vec<TokenStream2>)> = vec![];
+    for (field, row) in rows {
+        match field.group.as_deref() {
+            Some(group) => match groups.iter_mut().find(|(name, _)| *name == Some(group)) {
+                Some((_, group_rows)) => group_rows.push(row),
+                None => groups.push((Some(group), vec![row])),
+            },
+            None => groups.push((None, vec![row])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(group, rows)| match group {
+            Some(group) => quote! {
+                .push(
+                    iced_forms::iced::pure::container(
+                        iced_forms::iced::pure::column()
+                            .spacing(10)
+                            .push(iced_forms::iced::pure::text(#group).size(24))
+                            #(.push(#rows))*
+                    )
+                    .padding(10)
+                    .width(iced_forms::iced::Length::Fill),
+                )
+            },
+            None => quote!(#(.push(#rows))*),
         })
+        .collect()
+}
+
+/// `view` of the `IcedForm` impl goes through the typed messages and serializes the result,
+/// `methods` are added to the impl
+fn iced_form_impl(
+    name: &syn::Ident,
+    enum_ident: &syn::Ident,
+    methods: TokenStream2,
+) -> TokenStream2 {
+    quote! {
+        impl<'a, Message> iced_forms::IcedForm<'a, Message> for #name
+        where
+            Message: Clone + 'a,
+        {
+            fn view(
+                &'a self,
+                on_change: ::std::sync::Arc<
+                    dyn Fn(iced_forms::IcedFormValueResult<iced_forms::serde_json::Value>) -> Message + 'a,
+                >,
+                selector: iced_forms::Selector,
+            ) -> iced_forms::iced::pure::widget::Container<'a, Message> {
+                self.view_with(on_change, selector, Default::default())
+            }
+
+            fn view_with(
+                &'a self,
+                on_change: ::std::sync::Arc<
+                    dyn Fn(iced_forms::IcedFormValueResult<iced_forms::serde_json::Value>) -> Message + 'a,
+                >,
+                selector: iced_forms::Selector,
+                options: iced_forms::FieldOptions<'a>,
+            ) -> iced_forms::iced::pure::widget::Container<'a, Message> {
+                let at = selector.clone();
+                self.view_fields(
+                    ::std::sync::Arc::new(move |update: #enum_ident| {
+                        if let Some(pending) = update.pending() {
+                            return on_change(Err(pending));
+                        }
+                        let mut form = self.clone();
+                        on_change(
+                            update
+                                .apply(&mut form)
+                                .and_then(|()| iced_forms::to_value(form))
+                                .map_err(|e| e.at(&at)),
+                        )
+                    }),
+                    selector,
+                    options,
+                )
+            }
+
+            #methods
+        }
+    }
+}
+
+fn derive_struct(
+    name: &syn::Ident,
+    vis: &syn::Visibility,
+    fields: &[IcedFormFieldOptions],
+) -> TokenStream2 {
+    let enum_ident = format_ident!("{name}FieldUpdated");
+    let fields = fields
+        .iter()
+        .filter(|field| !field.skip)
         .collect::<Vec<_>>();
 
-    let map_ident_name = |name: &syn::Ident, mapping: fn(&str) -> String| {
-        let mapped = mapping(&format!("{name}"));
-        syn::Ident::new(&mapped, name.span())
-    };
-    let enum_ident = map_ident_name(name, |name| format!("{name}FieldUpdated"));
+    let variants = fields.iter().map(|field| {
+        let variant = to_variant_name(field.ident.as_ref().unwrap());
+        let ty = &field.ty;
+        quote! {
+            #variant(iced_forms::IcedFormValueResult<#ty>)
+        }
+    });
 
-    let to_enum_variant_name_updated =
-        |ident: &syn::Ident| map_ident_name(ident, |name| format!("{name}Updated"));
-    let to_enum_variant_name_error =
-        |ident: &syn::Ident| map_ident_name(ident, |name| format!("{name}Error"));
+    let apply_arms = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let variant = to_variant_name(ident);
+        quote! {
+            Self::#variant(value) => form.#ident = iced_forms::resolve(value, &form.#ident)?
+        }
+    });
 
-    let to_enum_variant = |(ident, ty): &(syn::Ident, syn::Type)| {
-        let variant_name_updated = to_enum_variant_name_updated(ident);
-        let variant_name_error = to_enum_variant_name_error(ident);
+    let pending_arms = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let variant = to_variant_name(ident);
+        let key = to_key(ident);
         quote! {
-            #variant_name_updated(<#ty as iced_forms::IcedForm<'a, #ty>>::Message),
-            #variant_name_error(iced_forms::IcedFormValueError)
+            Self::#variant(value) => iced_forms::pending(
+                value,
+                &iced_forms::Selector::empty()
+                    .push(iced_forms::SelectorSegment::ObjectField(#key.to_owned())),
+            )
         }
+    });
+
+    let rows = |patches: bool| {
+        layout_rows(
+            fields
+                .iter()
+                .map(|field| {
+                    let ident = field.ident.as_ref().unwrap();
+                    let variant = to_variant_name(ident);
+                    let key = to_key(ident);
+                    let changes = match patches {
+                        true => FieldChanges::Patches,
+                        false => FieldChanges::Typed(quote!(#enum_ident::#variant)),
+                    };
+                    let row = field_row(
+                        field,
+                        Some(to_label(ident)),
+                        quote!(&self.#ident),
+                        &changes,
+                        quote!(selector.push(iced_forms::SelectorSegment::ObjectField(#key.to_owned()))),
+                    );
+                    (*field, row)
+                })
+                .collect(),
+        )
     };
-    let enum_variants = fields_with_types
+    let field_rows = rows(false);
+    let patch_rows = rows(true);
+
+    // fields with their own widget or shown as text don't have to implement `IcedForm`
+    let set_arms = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let key = to_key(ident);
+        match field.widget.is_some() || field.readonly {
+            true => quote! {
+                #key => iced_forms::set_at(&mut self.#ident, rest, value)
+            },
+            false => quote! {
+                #key => iced_forms::IcedForm::<'a, Message>::set_at(&mut self.#ident, rest, value)
+            },
+        }
+    });
+
+    let iced_form = iced_form_impl(
+        name,
+        &enum_ident,
+        quote! {
+            fn view_patches(
+                &'a self,
+                on_patch: ::std::sync::Arc<dyn Fn(iced_forms::UpdatedMessage) -> Message + 'a>,
+                selector: iced_forms::Selector,
+                options: iced_forms::FieldOptions<'a>,
+            ) -> iced_forms::iced::pure::widget::Container<'a, Message> {
+                iced_forms::iced::pure::container(
+                    iced_forms::iced::pure::column()
+                        .spacing(10)
+                        #(#patch_rows)*
+                )
+                .width(iced_forms::iced::Length::Fill)
+            }
+
+            fn set_at(
+                &mut self,
+                path: &[iced_forms::SelectorSegment],
+                value: iced_forms::serde_json::Value,
+            ) -> iced_forms::IcedFormValueResult<()> {
+                match path.split_first() {
+                    Some((iced_forms::SelectorSegment::ObjectField(key), rest)) => {
+                        match key.as_str() {
+                            #(#set_arms,)*
+                            _ => iced_forms::set_at(self, path, value),
+                        }
+                    }
+                    _ => iced_forms::set_at(self, path, value),
+                }
+            }
+        },
+    );
+    quote! {
+        #[derive(Debug, Clone)]
+        #vis enum #enum_ident {
+            #(#variants),*
+        }
+
+        impl #enum_ident {
+            /// writes the new value into its field, a value that failed to parse is returned as the error
+            #vis fn apply(self, form: &mut #name) -> iced_forms::IcedFormValueResult<()> {
+                match self {
+                    #(#apply_arms),*
+                }
+                Ok(())
+            }
+
+            /// an array operation made in a field, moved under the field so it can be passed
+            /// on without rebuilding the form, see `iced_forms::IcedFormValueError::Pending`
+            #vis fn pending(&self) -> Option<iced_forms::IcedFormValueError> {
+                match self {
+                    #(#pending_arms,)*
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                }
+            }
+        }
+
+        impl #name {
+            /// the form with one message per edited field
+            #vis fn view_fields<'a, Message>(
+                &'a self,
+                on_update: ::std::sync::Arc<dyn Fn(#enum_ident) -> Message + 'a>,
+                selector: iced_forms::Selector,
+                options: iced_forms::FieldOptions<'a>,
+            ) -> iced_forms::iced::pure::widget::Container<'a, Message>
+            where
+                Message: Clone + 'a,
+            {
+                iced_forms::iced::pure::container(
+                    iced_forms::iced::pure::column()
+                        .spacing(10)
+                        #(#field_rows)*
+                )
+                .width(iced_forms::iced::Length::Fill)
+            }
+        }
+
+        #iced_form
+    }
+}
+
+/// message variant for a field of an enum variant: `CompanyName`, `Point0`, or just the
+/// variant's name for newtype variants
+fn enum_field_message(variant: &IcedFormVariantOptions, index: usize) -> syn::Ident {
+    let field = &variant.fields.fields[index];
+    match (&field.ident, variant.fields.len()) {
+        (Some(ident), _) => format_ident!("{}{}", variant.ident, to_variant_name(ident)),
+        (None, 1) => variant.ident.clone(),
+        (None, _) => format_ident!("{}{index}", variant.ident),
+    }
+}
+
+/// fields of the variant that are not skipped, with their position
+fn edited_fields(
+    variant: &IcedFormVariantOptions,
+) -> impl Iterator<Item = (usize, &IcedFormFieldOptions)> {
+    variant
+        .fields
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| !field.skip)
+}
+
+fn field_binding(field: &IcedFormFieldOptions, index: usize) -> syn::Ident {
+    match &field.ident {
+        Some(ident) => ident.clone(),
+        None => format_ident!("field_{index}"),
+    }
+}
+
+/// pattern binding every field of the variant, `field_<index>` for tuple variants
+fn variant_pattern(name: &syn::Ident, variant: &IcedFormVariantOptions) -> TokenStream2 {
+    let ident = &variant.ident;
+    let bindings = variant
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| field_binding(field, index));
+    match variant.fields.style {
+        ast::Style::Unit => quote!(#name::#ident),
+        ast::Style::Tuple => quote!(#name::#ident(#(#bindings),*)),
+        ast::Style::Struct => quote!(#name::#ident { #(#bindings),* }),
+    }
+}
+
+fn derive_enum(
+    name: &syn::Ident,
+    vis: &syn::Visibility,
+    variants: &[IcedFormVariantOptions],
+) -> TokenStream2 {
+    let enum_ident = format_ident!("{name}FieldUpdated");
+    let variant_ident = format_ident!("{name}Variant");
+    let variant_idents = variants
         .iter()
-        .map(to_enum_variant)
+        .map(|variant| &variant.ident)
         .collect::<Vec<_>>();
+    let variant_labels = variants.iter().map(|variant| {
+        variant
+            .label
+            .clone()
+            .unwrap_or_else(|| to_variant_label(&variant.ident))
+    });
+    let variant_count = variants.len();
 
-    let subform_pushes = fields_with_types.iter().map(|(ident, ty)| {
-        let field_header_text = stringify!(ident);
-        let variant_name_update = to_enum_variant_name_updated(ident);
-        let variant_name_error = to_enum_variant_name_error(ident);
+    let messages = variants.iter().flat_map(|variant| {
+        edited_fields(variant).map(|(index, field)| {
+            let message = enum_field_message(variant, index);
+            let ty = &field.ty;
+            quote!(#message(iced_forms::IcedFormValueResult<#ty>))
+        })
+    });
 
-        let type_name = stringify!(ty);
-        // let enum_variant = to_enum_variant((ident, ty));
-        quote! {
-            .push(
-                row()
-                .push(text(#field_header_text))
-                .push(
-                    self.#ident.view(
-                        #type_name,
-                        move |changed_field| match changed_field {
-                            Ok(value) => {
-                                let mut form = self.clone();
-                                form.#ident = value;
-                                on_change(Ok(form))
-                            },
-                            Err(e) => {
-                                on_change(Err(e))
-                            }
-                        },
-                        )
-                    )
+    let apply_arms = variants.iter().flat_map(|variant| {
+        let ident = &variant.ident;
+        edited_fields(variant).map(move |(index, field)| {
+            let message = enum_field_message(variant, index);
+            let pattern = match &field.ident {
+                Some(field) => quote!(#name::#ident { #field: current, .. }),
+                None => {
+                    let skipped = (0..index).map(|_| quote!(_));
+                    quote!(#name::#ident(#(#skipped,)* current, ..))
+                }
+            };
+            // an update for a variant that is no longer selected is dropped
+            quote! {
+                Self::#message(value) => match form {
+                    #pattern => *current = iced_forms::resolve(value, current)?,
+                    _ => {
+                        value?;
+                    }
+                }
+            }
+        })
+    });
+
+    // fields sit at the same paths as in `view_fields`
+    let pending_arms = variants.iter().flat_map(|variant| {
+        edited_fields(variant).map(move |(index, field)| {
+            let message = enum_field_message(variant, index);
+            let root = match (&field.ident, variant.fields.len()) {
+                (Some(ident), _) => {
+                    let key = to_key(ident);
+                    quote! {
+                        iced_forms::Selector::empty()
+                            .push(iced_forms::SelectorSegment::ObjectField(#key.to_owned()))
+                    }
+                }
+                (None, 1) => quote!(iced_forms::Selector::empty()),
+                (None, _) => quote! {
+                    iced_forms::Selector::empty()
+                        .push(iced_forms::SelectorSegment::ArrayIndex(#index))
+                },
+            };
+            quote!(Self::#message(value) => iced_forms::pending(value, &#root))
+        })
+    });
+
+    let payload_arms = variants.iter().map(|variant| {
+        let pattern = variant_pattern(name, variant);
+        let values = variant.fields.iter().enumerate().map(|(index, field)| {
+            let binding = field_binding(field, index);
+            quote!(iced_forms::to_value(#binding.clone()).unwrap_or_default())
+        });
+        let keys = variant
+            .fields
+            .iter()
+            .filter_map(|field| field.ident.as_ref())
+            .map(to_key);
+        let payload = match (variant.fields.style, variant.fields.len()) {
+            (ast::Style::Unit, _) => quote!(iced_forms::serde_json::Value::Null),
+            (ast::Style::Tuple, 1) => quote!(#(#values)*),
+            (ast::Style::Tuple, _) => {
+                quote!(iced_forms::serde_json::Value::Array(vec![#(#values),*]))
+            }
+            (ast::Style::Struct, _) => quote! {
+                iced_forms::serde_json::Value::Object(
+                    [#((#keys.to_owned(), #values)),*].into_iter().collect(),
                 )
+            },
+        };
+        quote!(#pattern => #payload)
+    });
+
+    let build_arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let fields = variant.fields.iter().enumerate().map(|(index, field)| {
+            let from = match (&field.ident, variant.fields.len()) {
+                (Some(field), _) => {
+                    let key = to_key(field);
+                    quote!(payload.get(#key))
+                }
+                (None, 1) => quote!(Some(&payload)),
+                (None, _) => quote!(payload.get(#index)),
+            };
+            let value = quote!(iced_forms::carried_over(#from));
+            match &field.ident {
+                Some(field) => quote!(#field: #value),
+                None => value,
+            }
+        });
+        let built = match variant.fields.style {
+            ast::Style::Unit => quote!(#name::#ident),
+            ast::Style::Tuple => quote!(#name::#ident(#(#fields),*)),
+            ast::Style::Struct => quote!(#name::#ident { #(#fields),* }),
+        };
+        quote!(#variant_ident::#ident => #built)
+    });
+
+    let view_arms = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let bindings = variant.fields.iter().enumerate().map(|(index, field)| {
+            match (field.skip, variant.fields.style) {
+                (true, ast::Style::Tuple) => quote!(_),
+                _ => {
+                    let binding = field_binding(field, index);
+                    quote!(#binding)
+                }
+            }
+        });
+        let pattern = match variant.fields.style {
+            ast::Style::Unit => quote!(#name::#ident),
+            ast::Style::Tuple => quote!(#name::#ident(#(#bindings),*)),
+            ast::Style::Struct => {
+                let bindings = edited_fields(variant).map(|(index, field)| field_binding(field, index));
+                quote!(#name::#ident { #(#bindings,)* .. })
+            }
+        };
+        let rows = edited_fields(variant).map(|(index, field)| {
+            let binding = field_binding(field, index);
+            let message = enum_field_message(variant, index);
+            let (label, selector) = match (&field.ident, variant.fields.len()) {
+                (Some(ident), _) => {
+                    let key = to_key(ident);
+                    (
+                        Some(to_label(ident)),
+                        quote!(selector.push(iced_forms::SelectorSegment::ObjectField(#key.to_owned()))),
+                    )
+                }
+                (None, 1) => (None, quote!(selector.clone())),
+                (None, _) => (
+                    Some(index.to_string()),
+                    quote!(selector.push(iced_forms::SelectorSegment::ArrayIndex(#index))),
+                ),
+            };
+            let row = field_row(
+                field,
+                label,
+                quote!(#binding),
+                &FieldChanges::Typed(quote!(#enum_ident::#message)),
+                selector,
+            );
+            (field, row)
+        });
+        let rows = layout_rows(rows.collect());
+        quote! {
+            #pattern => iced_forms::iced::pure::column().spacing(10) #(#rows)*
         }
     });
 
-    let output = quote! {
+    let iced_form = iced_form_impl(name, &enum_ident, quote!());
+    quote! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #vis enum #variant_ident {
+            #(#variant_idents),*
+        }
+
+        impl #variant_ident {
+            #vis const ALL: [#variant_ident; #variant_count] = [#(#variant_ident::#variant_idents),*];
+        }
+
+        impl ::std::fmt::Display for #variant_ident {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.write_str(match self {
+                    #(#variant_ident::#variant_idents => #variant_labels),*
+                })
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        #vis enum #enum_ident {
+            /// the variant picker changed
+            SelectVariant(#variant_ident),
+            #(#messages),*
+        }
+
+        impl #enum_ident {
+            /// writes the new value into its field, a value that failed to parse is returned as the error
+            #vis fn apply(self, form: &mut #name) -> iced_forms::IcedFormValueResult<()> {
+                match self {
+                    Self::SelectVariant(variant) => *form = form.clone().with_variant(variant),
+                    #(#apply_arms),*
+                }
+                Ok(())
+            }
+
+            /// an array operation made in a field, moved under the field so it can be passed
+            /// on without rebuilding the form, see `iced_forms::IcedFormValueError::Pending`
+            #vis fn pending(&self) -> Option<iced_forms::IcedFormValueError> {
+                match self {
+                    Self::SelectVariant(_) => None,
+                    #(#pending_arms),*
+                }
+            }
+        }
+
+        impl #name {
+            #vis fn variant(&self) -> #variant_ident {
+                match self {
+                    #(#name::#variant_idents { .. } => #variant_ident::#variant_idents),*
+                }
+            }
+
+            /// switches to another variant, fields with the same name (or the fields of a
+            /// newtype variant's struct) keep their values, the rest starts from `Default`
+            #vis fn with_variant(self, variant: #variant_ident) -> Self {
+                if self.variant() == variant {
+                    return self;
+                }
+                #[allow(unused_variables)]
+                let payload = match &self {
+                    #(#payload_arms),*
+                };
+                match variant {
+                    #(#build_arms),*
+                }
+            }
 
-    impl<'a> iced_forms::IcedForm<'a> for #name {
-        fn view(
-            &self,
-            name: &'static str,
-            on_change: impl Fn(iced_forms::IcedFormValueResult<Self>) -> iced_forms::IcedFormValueResult<Self> + 'a,
-        ) -> iced::pure::widget::Container<'a, iced_forms::IcedFormValueResult<Self>> {
-            use iced::pure::{container, column, row, text};
-            iced::pure::container::<'a>(column() #(#subform_pushes)*)
+            /// the variant picker followed by the fields of the selected variant
+            #vis fn view_fields<'a, Message>(
+                &'a self,
+                on_update: ::std::sync::Arc<dyn Fn(#enum_ident) -> Message + 'a>,
+                selector: iced_forms::Selector,
+                options: iced_forms::FieldOptions<'a>,
+            ) -> iced_forms::iced::pure::widget::Container<'a, Message>
+            where
+                Message: Clone + 'a,
+            {
+                let picker = iced_forms::iced::pure::pick_list(
+                    &#variant_ident::ALL[..],
+                    Some(self.variant()),
+                    {
+                        let on_update = on_update.clone();
+                        move |variant| on_update(#enum_ident::SelectVariant(variant))
+                    },
+                );
+                let fields = match self {
+                    #(#view_arms),*
+                };
+                iced_forms::iced::pure::container(
+                    iced_forms::iced::pure::column()
+                        .spacing(10)
+                        .push(picker)
+                        .push(fields),
+                )
+                .width(iced_forms::iced::Length::Fill)
+            }
         }
+
+        #iced_form
+    }
+}
+
+/// generates, for a struct with named fields or an enum:
+/// - `<Name>FieldUpdated`, one variant per field carrying the field's new typed value
+/// - `<Name>::view_fields`, a labelled form emitting those per-field messages
+/// - `IcedForm` for the type, so it can be nested in other derived forms; structs hand the
+///   patches of `IcedForm::view_patches` and `IcedForm::set_at` on to their fields
+///
+/// enums additionally get `<Name>Variant` for the variant picker and `with_variant`
+///
+/// every field type has to implement `IcedForm` itself, the type has to be `Clone` and
+/// (de)serializable, fields of enum variants also `Default`
+#[proc_macro_derive(IcedFormDerive, attributes(iced_form))]
+pub fn derive(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input);
+    let options = match IcedFormDeriveOptions::from_derive_input(&ast) {
+        Ok(options) => options,
+        Err(e) => return e.write_errors().into(),
+    };
+    if !options.generics.params.is_empty() {
+        return syn::Error::new_spanned(
+            &options.generics,
+            "IcedFormDerive does not support generic types",
+        )
+        .to_compile_error()
+        .into();
     }
-        };
+
+    let output = match &options.data {
+        ast::Data::Struct(fields) => derive_struct(&options.ident, &options.vis, &fields.fields),
+        ast::Data::Enum(variants) => derive_enum(&options.ident, &options.vis, variants),
+    };
 
     let output = TokenStream::from(output);
-    eprintln!("{output}");
-    // pretty_print_tokenstream(&output);
+    if std::env::var_os("ICED_FORMS_DEBUG").is_some() {
+        pretty_print_tokenstream(&output);
+    }
     output
 }
 
@@ -1,56 +1,695 @@
-// use iced_forms::IcedForm;
-// use iced_forms_derive::IcedFormDerive;
-
-// mod simple {
-//     use super::*;
-
-//     // #[derive(Clone, IcedFormDerive)]
-//     pub struct Dog {
-//         pub name: String,
-//     }
-// }
-
-// mod debug {
-//     #[derive(Clone)]
-//     pub struct Dog {
-//         pub name: String,
-//     }
-
-//     impl<'a> iced_forms::IcedForm<'a> for Dog {
-//         fn view(
-//             &self,
-//             name: &'static str,
-//             on_change: impl Fn(iced_forms::IcedFormValueResult<Self>) -> iced_forms::IcedFormValueResult<Self>
-//                 + 'a,
-//         ) -> iced::pure::widget::Container<'a, iced_forms::IcedFormValueResult<Self>> {
-//             use iced::pure::{
-//                 column,
-//                 container,
-//                 row,
-//                 text,
-//             };
-//             let subform = self
-//                 .name
-//                 .view("ty", move |changed_field| match changed_field {
-//                     Ok(value) => {
-//                         let mut form = self.clone();
-//                         form.name = value;
-//                         on_change(iced_forms::IcedFormValueResult::<Self>::Ok(form))
-//                     }
-//                     Err(e) => on_change(iced_forms::IcedFormValueResult::<Self>::Err(e)),
-//                 });
-//             let row = row().push(text("ident")).push(subform);
-//             let column = column().push(row);
-//             iced::pure::container::<'a>(column)
-//         }
-//     }
-// }
-
-// #[cfg(test)]
-// mod tests {
-//     #[test]
-//     fn it_works() {
-//         let result = 2 + 2;
-//         assert_eq!(result, 4);
-//     }
-// }
+use iced_forms_derive::IcedFormDerive;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+pub mod simple {
+    use super::*;
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, IcedFormDerive)]
+    pub struct Dog {
+        pub name: String,
+    }
+}
+
+pub mod nested {
+    use super::*;
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, IcedFormDerive)]
+    pub struct Owner {
+        pub name: String,
+        pub phone_number: String,
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, IcedFormDerive)]
+    pub struct Dog {
+        pub name: String,
+        pub age: u8,
+        pub good_boy: bool,
+        pub owner: Owner,
+    }
+}
+
+/// the shape of `models::Customer`, plus the remaining kinds of variants
+pub mod enums {
+    use super::*;
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, IcedFormDerive)]
+    pub struct PrivateCustomer {
+        pub name: String,
+        pub phone: String,
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, IcedFormDerive)]
+    pub struct Company {
+        pub name: String,
+        pub tax_number: String,
+        pub phone: String,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, IcedFormDerive)]
+    pub enum Customer {
+        PrivateCustomer(PrivateCustomer),
+        Company(Company),
+        Anonymous,
+        Walk { name: String, visits: u32 },
+        Pair(String, u8),
+    }
+}
+
+/// every `#[iced_form(...)]` field attribute
+pub mod attributes {
+    use std::sync::Arc;
+
+    use iced_forms::{
+        iced::pure::{
+            container,
+            text,
+            widget::Container,
+        },
+        IcedFormValueResult,
+        Selector,
+    };
+
+    use super::*;
+
+    /// a custom widget, same arguments as `IcedForm::view`
+    pub fn stars<'a, Message: Clone + 'a>(
+        value: &'a u8,
+        _on_change: Arc<dyn Fn(IcedFormValueResult<iced_forms::serde_json::Value>) -> Message + 'a>,
+        _selector: Selector,
+    ) -> Container<'a, Message> {
+        container(text("*".repeat(*value as usize)))
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, IcedFormDerive)]
+    pub struct Visit {
+        #[iced_form(readonly, order = -1)]
+        pub id: u64,
+        #[iced_form(label = "Imię psa", placeholder = "np. Burek", group = "pies")]
+        pub dog: String,
+        #[iced_form(label = "Wiek", group = "pies", help = "w latach")]
+        pub age: u8,
+        #[iced_form(multiline)]
+        pub notes: String,
+        #[iced_form(widget = "stars")]
+        pub rating: u8,
+        #[iced_form(skip)]
+        pub internal: String,
+        pub symptoms: Vec<String>,
+    }
+}
+
+/// the kinds of values the JSON form needs a schema for, like in `models::RepairContract`
+pub mod typed {
+    use schemars::JsonSchema;
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    pub enum Status {
+        #[default]
+        Received,
+        Done,
+    }
+
+    #[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+    pub struct Loan {
+        pub model: String,
+        pub deposit: rust_decimal::Decimal,
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+    pub struct Repair {
+        pub id: uuid::Uuid,
+        pub date: chrono::NaiveDateTime,
+        pub pickup: Option<chrono::NaiveDate>,
+        pub status: Status,
+        pub price: rust_decimal::Decimal,
+        pub loan: Option<Loan>,
+        pub parts: Vec<Loan>,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use iced_forms::{
+        IcedForm,
+        IcedFormValueResult,
+        Selector,
+    };
+
+    use super::*;
+
+    #[test]
+    fn field_updates_apply_to_the_form() {
+        let mut dog = nested::Dog::default();
+        nested::DogFieldUpdated::Name(Ok("Burek".to_owned()))
+            .apply(&mut dog)
+            .unwrap();
+        nested::DogFieldUpdated::Owner(Ok(nested::Owner {
+            name: "Jan".to_owned(),
+            ..Default::default()
+        }))
+        .apply(&mut dog)
+        .unwrap();
+        assert_eq!(dog.name, "Burek");
+        assert_eq!(dog.owner.name, "Jan");
+        assert!(
+            nested::DogFieldUpdated::Age(iced_forms::from_value(&"dużo".into()))
+                .apply(&mut dog)
+                .is_err()
+        );
+        assert_eq!(dog.age, 0);
+    }
+
+    #[test]
+    fn switching_variants_keeps_common_fields() {
+        use enums::*;
+
+        let mut customer = Customer::PrivateCustomer(PrivateCustomer {
+            name: "Jan".to_owned(),
+            phone: "123".to_owned(),
+        });
+        CustomerFieldUpdated::SelectVariant(CustomerVariant::Company)
+            .apply(&mut customer)
+            .unwrap();
+        match &customer {
+            Customer::Company(company) => {
+                assert_eq!(company.name, "Jan");
+                assert_eq!(company.phone, "123");
+                assert_eq!(company.tax_number, "");
+            }
+            other => panic!("expected a company, got {other:?}"),
+        }
+        let customer = customer.with_variant(CustomerVariant::Walk);
+        assert!(matches!(&customer, Customer::Walk { name, visits: 0 } if name == "Jan"));
+        let customer = customer.with_variant(CustomerVariant::Anonymous);
+        assert_eq!(customer.variant(), CustomerVariant::Anonymous);
+        assert_eq!(
+            CustomerVariant::PrivateCustomer.to_string(),
+            "private customer"
+        );
+
+        let mut customer = Customer::Pair("Jan".to_owned(), 1);
+        CustomerFieldUpdated::Pair1(Ok(2))
+            .apply(&mut customer)
+            .unwrap();
+        CustomerFieldUpdated::WalkVisits(Ok(5))
+            .apply(&mut customer)
+            .unwrap();
+        assert!(matches!(&customer, Customer::Pair(name, 2) if name == "Jan"));
+    }
+
+    #[test]
+    fn attributed_fields() {
+        let mut visit = attributes::Visit {
+            internal: "keep".to_owned(),
+            ..Default::default()
+        };
+        attributes::VisitFieldUpdated::Symptoms(Ok(vec!["kaszel".to_owned()]))
+            .apply(&mut visit)
+            .unwrap();
+        assert_eq!(visit.symptoms, ["kaszel"]);
+        assert_eq!(visit.internal, "keep");
+        let _ = visit.view_fields(
+            Arc::new(|update| update),
+            Selector::empty(),
+            Default::default(),
+        );
+    }
+
+    #[test]
+    fn forms_render() {
+        let customer = enums::Customer::Walk {
+            name: "Jan".to_owned(),
+            visits: 1,
+        };
+        let _ = customer.view_fields(
+            Arc::new(|update| update),
+            Selector::empty(),
+            Default::default(),
+        );
+        let dog = simple::Dog::default();
+        let _ = dog.view_fields(
+            Arc::new(|update| update),
+            Selector::empty(),
+            Default::default(),
+        );
+        let dog = nested::Dog::default();
+        let _ = IcedForm::<IcedFormValueResult<iced_forms::serde_json::Value>>::view(
+            &dog,
+            Arc::new(|value| value),
+            Selector::empty(),
+        );
+    }
+
+    #[test]
+    fn json_array_elements() {
+        use iced_forms::serde_json::json;
+
+        let parts = json!([{"name": "bateria", "count": 2, "tags": ["nowa"], "ok": true}]);
+        assert_eq!(
+            iced_forms::empty_like(&parts[0]),
+            json!({"name": "", "count": 0, "tags": [], "ok": false})
+        );
+        let document = json!({"parts": parts});
+        let templates = [("parts".to_owned(), json!({"name": "ekran"}))]
+            .into_iter()
+            .collect();
+        let _ = iced_forms::json_form(
+            &document,
+            &Default::default(),
+            Arc::new(|value| value),
+            Selector::empty(),
+            &templates,
+            Default::default(),
+        );
+    }
+
+    #[test]
+    fn typed_patches() {
+        use iced_forms::{
+            serde_json::json,
+            IcedForm,
+            UpdatedMessage,
+        };
+
+        let path = |path: &str| path.parse::<Selector>().unwrap();
+        let mut visit = attributes::Visit {
+            internal: "keep".to_owned(),
+            symptoms: vec!["kaszel".to_owned()],
+            ..Default::default()
+        };
+        let mut set = |at: &str, value| {
+            IcedForm::<UpdatedMessage>::set_at(&mut visit, path(at).segments(), value)
+        };
+        set("dog", json!("Burek")).unwrap();
+        set("age", json!(4)).unwrap();
+        set("rating", json!(5)).unwrap();
+        set("symptoms[0]", json!("katar")).unwrap();
+        set("symptoms", json!(["katar", "gorączka"])).unwrap();
+        assert!(set("age", json!("stary")).is_err());
+        assert!(set("symptoms[5]", json!("")).is_err());
+        assert_eq!(
+            (visit.dog.as_str(), visit.age, visit.rating),
+            ("Burek", 4, 5)
+        );
+        assert_eq!(visit.symptoms, ["katar", "gorączka"]);
+        assert_eq!(visit.internal, "keep");
+
+        let mut dog = nested::Dog::default();
+        IcedForm::<UpdatedMessage>::set_at(
+            &mut dog,
+            path("owner.name").segments(),
+            json!("Ala"),
+        )
+        .unwrap();
+        assert_eq!(dog.owner.name, "Ala");
+        let _ = IcedForm::<UpdatedMessage>::view_patches(
+            &dog,
+            Arc::new(|update| update),
+            Selector::empty(),
+            Default::default(),
+        );
+        let _ = visit.view_patches(
+            Arc::new(|update| update),
+            Selector::empty(),
+            Default::default(),
+        );
+
+        let mut document = json!({"owner": {"name": ""}});
+        let owner_name = path("owner.name");
+        IcedForm::<UpdatedMessage>::set_at(&mut document, owner_name.segments(), json!("Ola"))
+            .unwrap();
+        assert_eq!(document, json!({"owner": {"name": "Ola"}}));
+    }
+
+    #[test]
+    fn json_array_operations() {
+        use iced_forms::{
+            serde_json::json,
+            ArrayOp,
+            EditSession,
+            UpdatedMessage,
+        };
+
+        let notes: Selector = "notes".parse().unwrap();
+        let mut session = EditSession::new(json!({"notes": ["a", "b"]}));
+        let mut press = |op| {
+            session
+                .update(UpdatedMessage::array(notes.clone(), op))
+                .map(|_| session.value()["notes"].clone())
+        };
+        assert_eq!(press(ArrayOp::MoveDown(0)).unwrap(), json!(["b", "a"]));
+        assert_eq!(press(ArrayOp::MoveUp(1)).unwrap(), json!(["a", "b"]));
+        assert_eq!(press(ArrayOp::Duplicate(1)).unwrap(), json!(["a", "b", "b"]));
+        assert_eq!(press(ArrayOp::Remove(0)).unwrap(), json!(["b", "b"]));
+        assert_eq!(press(ArrayOp::Push(json!("c"))).unwrap(), json!(["b", "b", "c"]));
+        // buttons of elements that are gone change nothing
+        assert!(press(ArrayOp::Remove(3)).is_err());
+        assert!(press(ArrayOp::MoveUp(0)).is_err());
+        assert_eq!(session.value()["notes"], json!(["b", "b", "c"]));
+        assert!(session.undo());
+        assert_eq!(session.value()["notes"], json!(["b", "b"]));
+
+        let mut document = json!({"notes": "a"});
+        assert!(UpdatedMessage::array(notes, ArrayOp::Remove(0))
+            .apply(&mut document)
+            .is_err());
+    }
+
+    #[test]
+    fn typed_array_operations() {
+        use iced_forms::{
+            serde_json::json,
+            ArrayOp,
+            IcedFormValueError,
+            UpdatedMessage,
+        };
+
+        fn press<T>(at: &str, op: ArrayOp) -> IcedFormValueResult<T> {
+            let at = at.parse().unwrap();
+            Err(IcedFormValueError::Pending(Box::new(UpdatedMessage::array(at, op))))
+        }
+        let path = |path: &str| path.parse::<Selector>().unwrap();
+        let mut visit = attributes::Visit {
+            symptoms: vec!["kaszel".to_owned(), "katar".to_owned()],
+            ..Default::default()
+        };
+        // worked out against the field when the message is applied
+        let duplicated = attributes::VisitFieldUpdated::Symptoms(press("", ArrayOp::Duplicate(1)));
+        assert_eq!(duplicated.pending().unwrap().selector(), &path("symptoms"));
+        duplicated.apply(&mut visit).unwrap();
+        assert_eq!(visit.symptoms, ["kaszel", "katar", "katar"]);
+        let removed = iced_forms::resolve(press("", ArrayOp::Remove(0)), &visit.symptoms);
+        assert_eq!(removed.unwrap(), ["katar", "katar"]);
+        assert!(iced_forms::resolve(press("", ArrayOp::Remove(3)), &visit.symptoms).is_err());
+        assert!(attributes::VisitFieldUpdated::Dog(Ok("Burek".to_owned()))
+            .pending()
+            .is_none());
+
+        // patches of a typed form get the path of the array in the document
+        let on_change = iced_forms::patch_at(Arc::new(|update| update), path("visit"));
+        let update = on_change(press("symptoms", ArrayOp::MoveUp(1)));
+        assert_eq!(update.selector, path("visit.symptoms"));
+        let mut document = json!({"visit": {"symptoms": ["a", "b"]}});
+        update.apply(&mut document).unwrap();
+        assert_eq!(document["visit"]["symptoms"], json!(["b", "a"]));
+        let _ = IcedForm::<IcedFormValueResult<iced_forms::serde_json::Value>>::view(
+            &visit,
+            Arc::new(|value| value),
+            Selector::empty(),
+        );
+    }
+
+    #[test]
+    fn schema_aware_json_form() {
+        use iced_forms::{
+            serde_json::json,
+            FieldSchema,
+        };
+
+        let schema = FieldSchema::of::<typed::Repair>();
+        let loan = FieldSchema::Object(vec![
+            ("model".to_owned(), FieldSchema::String),
+            ("deposit".to_owned(), FieldSchema::Decimal),
+        ]);
+        assert_eq!(schema.field("id"), &FieldSchema::Uuid);
+        assert_eq!(schema.field("date"), &FieldSchema::DateTime);
+        assert_eq!(
+            schema.field("pickup"),
+            &FieldSchema::Optional(Box::new(FieldSchema::Date))
+        );
+        assert_eq!(
+            schema.field("status"),
+            &FieldSchema::Enum(vec!["received".to_owned(), "done".to_owned()])
+        );
+        assert_eq!(
+            schema.field("loan"),
+            &FieldSchema::Optional(Box::new(loan.clone()))
+        );
+        assert_eq!(schema.field("parts").items(), &loan);
+        assert_eq!(loan.default_value(), json!({"model": "", "deposit": "0"}));
+
+        assert_eq!(
+            FieldSchema::Decimal.parse(" 1 234,50 ").unwrap(),
+            json!("1234.50")
+        );
+        assert_eq!(FieldSchema::Decimal.to_text(&json!("12.5")), "12,5");
+        assert!(FieldSchema::Decimal.parse("12,5,0").is_err());
+        assert_eq!(
+            FieldSchema::DateTime.parse("2026-10-18 14:30").unwrap(),
+            json!("2026-10-18T14:30:00")
+        );
+        assert!(FieldSchema::Integer.parse("1.5").is_err());
+        assert_eq!(
+            FieldSchema::DateTime.parse("18.10.2026 14:30").unwrap(),
+            json!("2026-10-18T14:30:00")
+        );
+        assert_eq!(
+            FieldSchema::Date.parse("18.10.2026").unwrap(),
+            json!("2026-10-18")
+        );
+        assert_eq!(
+            FieldSchema::DateTime.to_text(&json!("2026-10-18T14:30:00")),
+            "18.10.2026 14:30"
+        );
+        assert!(FieldSchema::DateTime.parse("18.10.2026 25:30").is_err());
+
+        let repair = json!({
+            "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+            "date": "2026-10-18T14:30:00",
+            "pickup": null,
+            "status": "done",
+            "price": "99.99",
+            "loan": {"model": "zastępczy", "deposit": "50"},
+            "parts": [],
+        });
+        let _ = iced_forms::json_form(
+            &repair,
+            &schema,
+            Arc::new(|value| value),
+            Selector::empty(),
+            &iced_forms::NO_TEMPLATES,
+            Default::default(),
+        );
+    }
+
+    #[test]
+    fn selector_paths() {
+        use iced_forms::{
+            serde_json::json,
+            IcedFormValueError,
+            SelectorSegment,
+        };
+
+        let note = Selector::empty()
+            .push(SelectorSegment::ObjectField(
+                "client_contact_events".to_owned(),
+            ))
+            .push(SelectorSegment::ArrayIndex(2))
+            .push(SelectorSegment::ObjectField("note".to_owned()));
+        assert_eq!(note.to_string(), "client_contact_events[2].note");
+        assert_eq!(
+            "client_contact_events[2].note".parse::<Selector>().unwrap(),
+            note
+        );
+        assert_eq!("".parse::<Selector>().unwrap(), Selector::empty());
+        assert!("info..phone".parse::<Selector>().is_err());
+        assert!("events[x]".parse::<Selector>().is_err());
+
+        let document = json!({
+            "client_contact_events": [{}, {}, {"note": "oddzwonić"}],
+        });
+        assert_eq!(note.resolve(&document), Some(&json!("oddzwonić")));
+        assert_eq!(
+            note.pop()
+                .pop()
+                .push(SelectorSegment::ArrayIndex(3))
+                .resolve(&document),
+            None
+        );
+
+        let phone: Selector = "info.customer.phone".parse().unwrap();
+        let error = IcedFormValueError::deserializing("u8", "invalid digit")
+            .at(&phone)
+            .at(&Selector::empty());
+        assert_eq!(error.selector(), &phone);
+        assert!(error.to_string().starts_with("info.customer.phone: "));
+    }
+
+    #[test]
+    fn path_updates() {
+        use iced_forms::{
+            serde_json::json,
+            Draft,
+            Drafts,
+            IcedFormValueError,
+            UpdatedMessage,
+        };
+
+        let mut document = json!({
+            "info": {"customer": {"phone": "123"}, "price": "10"},
+        });
+        let phone: Selector = "info.customer.phone".parse().unwrap();
+        let price: Selector = "info.price".parse().unwrap();
+        let unparsed = |selector: &Selector| {
+            UpdatedMessage::set(
+                selector.clone(),
+                Err(IcedFormValueError::Unparsed(Draft {
+                    selector: selector.clone(),
+                    text: "1o".to_owned(),
+                    message: "invalid digit".to_owned(),
+                })),
+            )
+        };
+
+        let mut drafts = Drafts::default();
+        assert!(drafts.track_update(unparsed(&phone)).unwrap().is_none());
+        assert!(drafts.track_update(unparsed(&price)).unwrap().is_none());
+        let update = UpdatedMessage::set(phone.clone(), Ok(json!("600 100 200")));
+        drafts
+            .track_update(update)
+            .unwrap()
+            .unwrap()
+            .apply(&mut document)
+            .unwrap();
+        assert_eq!(document["info"]["customer"]["phone"], "600 100 200");
+        assert!(drafts.get(&phone).is_none());
+        assert!(drafts.get(&price).is_some());
+
+        let missing = UpdatedMessage::set("info.customer.email".parse().unwrap(), Ok(json!("")));
+        assert!(missing.apply(&mut document).is_err());
+        let below = UpdatedMessage::set(phone, Ok(json!("1")))
+            .below(&"info.customer".parse().unwrap())
+            .unwrap();
+        assert_eq!(below.selector.to_string(), "phone");
+    }
+
+    #[test]
+    fn edit_session_history() {
+        use iced_forms::{
+            iced::keyboard::{
+                Event,
+                KeyCode,
+                Modifiers,
+            },
+            serde_json::json,
+            EditSession,
+            HistoryAction,
+            UpdatedMessage,
+        };
+
+        let original = json!({
+            "info": {"customer": {"name": "", "phone": ""}, "notes": []},
+        });
+        let mut session = EditSession::new(original.clone());
+        let info: Selector = "info".parse().unwrap();
+        let mut type_name = |name: &str| {
+            let mut value = session.value()["info"].clone();
+            value["customer"]["name"] = json!(name);
+            session
+                .update(UpdatedMessage::set(info.clone(), Ok(value)))
+                .unwrap()
+        };
+        assert!(type_name("J"));
+        assert!(type_name("Ja"));
+        assert!(type_name("Jan"));
+        assert!(!type_name("Jan"));
+        session
+            .update(UpdatedMessage::set(
+                "info.notes".parse().unwrap(),
+                Ok(json!(["oddzwonić"])),
+            ))
+            .unwrap();
+        assert!(session.is_dirty());
+
+        assert!(session.undo());
+        assert_eq!(session.value()["info"]["notes"], json!([]));
+        assert!(session.perform(HistoryAction::Undo));
+        assert_eq!(session.value(), &original);
+        assert!(!session.is_dirty());
+        assert!(!session.can_undo());
+        assert!(session.redo());
+        assert_eq!(session.value()["info"]["customer"]["name"], "Jan");
+        assert!(session.can_redo());
+
+        session.set_original(session.value().clone());
+        assert!(!session.is_dirty());
+
+        let pressed = |key_code, modifiers| Event::KeyPressed {
+            key_code,
+            modifiers,
+        };
+        assert_eq!(
+            iced_forms::history_shortcut(&pressed(KeyCode::Z, Modifiers::COMMAND)),
+            Some(HistoryAction::Undo)
+        );
+        assert_eq!(
+            iced_forms::history_shortcut(&pressed(
+                KeyCode::Z,
+                Modifiers::COMMAND | Modifiers::SHIFT
+            )),
+            Some(HistoryAction::Redo)
+        );
+        assert_eq!(
+            iced_forms::history_shortcut(&pressed(KeyCode::Z, Modifiers::empty())),
+            None
+        );
+    }
+
+    #[test]
+    fn validation() {
+        use iced_forms::{
+            serde_json::json,
+            EditSession,
+            FieldError,
+            UpdatedMessage,
+            Validators,
+        };
+
+        let phone: Selector = "customer.phone".parse().unwrap();
+        let validators = Validators::default()
+            .field(phone.clone(), |value| match value.as_str() {
+                Some("") | None => Err("podaj telefon".to_owned()),
+                Some(_) => Ok(()),
+            })
+            .document(|document| match document["visits"].as_u64() {
+                Some(visits) if visits > 10 => {
+                    vec![FieldError::new(Selector::empty(), "za dużo wizyt")]
+                }
+                _ => vec![],
+            });
+        let mut session = EditSession::new(json!({
+            "customer": {"phone": ""},
+            "visits": 11,
+        }))
+        .validated_by(validators);
+        assert_eq!(session.errors().get(&phone), ["podaj telefon"]);
+        assert_eq!(session.errors().get(&Selector::empty()), ["za dużo wizyt"]);
+        assert!(!session.can_submit());
+
+        session
+            .update(UpdatedMessage::set(phone.clone(), Ok(json!("600 100 200"))))
+            .unwrap();
+        assert!(session.errors().get(&phone).is_empty());
+        session
+            .update(UpdatedMessage::set("visits".parse().unwrap(), Ok(json!(3))))
+            .unwrap();
+        assert!(session.can_submit());
+        session.undo();
+        assert_eq!(
+            session
+                .errors()
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>(),
+            ["za dużo wizyt"]
+        );
+    }
+}
@@ -1,48 +1,298 @@
 mod colors {
     type Color = [f32; 3];
     pub static BLACK: Color = [0., 0., 0.];
-    pub static WHITE: Color = [1., 1., 1.];
 }
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use futures::FutureExt;
 use iced::{
     alignment,
     pure::{
-        widget::{Button, Column, Container, Row, Scrollable, Text, TextInput},
+        widget::{Button, Column, Container, Row, Scrollable, Text},
         Application, Element,
     },
     Alignment, Command, Length,
 };
-use iced_forms::IcedFormValueResult;
+use iced_forms::{
+    EditSession, FieldSchema, HistoryAction, IcedForm, IcedFormValueResult, Selector,
+    UpdatedMessage,
+};
 use tracing::error;
 
-use crate::{db::RepairContractEntry, models::RepairContract};
+use crate::{
+    backup::BackupCheck,
+    db::RepairContractEntry,
+    export::{ContractFilter, ContractSort, ExportFormat, SortColumn},
+    import::{ColumnMapping, ImportField, ImportSource, ImportSummary, ParsedRow},
+    models::{
+        protocols::FinalProtocol, signature::SignatureStroke, ContractStatus, RepairContract,
+        Signature,
+    },
+    reports::Report,
+    settings::{AppSettings, NamedArchive, Settings},
+    storage::{Storage, Unavailable},
+};
 
 use super::*;
 
+mod charts;
+mod signature_pad;
+
 #[derive(Debug, Clone)]
 pub enum Mode {
     Index,
     ViewingEntries,
     CreateNewRepairContract {
-        form: RepairContract,
-        buffer: IcedFormValueResult<serde_json::Value>,
+        /// read back from `session` after every change
+        form: Box<RepairContract>,
+        session: Box<EditSession>,
+        signature: Signature,
+    },
+    Handover {
+        entry: Box<RepairContractEntry>,
+        /// the final protocol, empty if the contract had none; read back from `session`
+        protocol: Box<FinalProtocol>,
+        session: Box<EditSession>,
+        signature: Signature,
     },
+    /// someone else saved the contract while it was being edited here
+    Conflict(Box<crate::db::Conflict>),
+    DeleteContract {
+        entry: Box<RepairContractEntry>,
+        reason: String,
+    },
+    Trash(Vec<RepairContractEntry>),
+    Backups(BackupsPage),
+    Reports(Report),
+    Import(ImportWizard),
+    Settings {
+        buffer: IcedFormValueResult<Box<EditSession>>,
+        schema: FieldSchema,
+        new_archive: NamedArchive,
+        error: Option<String>,
+    },
+}
+
+impl Mode {
+    /// contract leased for editing while this page is open
+    fn leased_path(&self) -> Option<&Path> {
+        match self {
+            Mode::Handover { entry, .. } => Some(&entry.path),
+            Mode::Conflict(conflict) => Some(&conflict.mine.path),
+            Mode::DeleteContract { entry, .. } => Some(&entry.path),
+            _ => None,
+        }
+    }
+}
+
+/// how often the backup scheduler checks whether a backup is due
+const BACKUP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10 * 60);
+
+/// backups of the current archive and the restore wizard: pick a backup -> check it -> restore
+#[derive(Debug, Clone, Default)]
+pub struct BackupsPage {
+    pub backups: Vec<PathBuf>,
+    pub checked: Option<(PathBuf, BackupCheck)>,
+    pub message: Option<String>,
+}
+
+/// state of the legacy spreadsheet import: file -> column mapping -> preview -> import
+#[derive(Debug, Clone, Default)]
+pub struct ImportWizard {
+    pub path: String,
+    pub source: Option<Arc<ImportSource>>,
+    pub mapping: ColumnMapping,
+    pub preview: Vec<ParsedRow>,
+    pub summary: Option<ImportSummary>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnChoice {
+    Skip,
+    Column(usize, String),
+}
+
+impl std::fmt::Display for ColumnChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColumnChoice::Skip => "— pomiń —".fmt(f),
+            ColumnChoice::Column(index, header) => write!(f, "{}: {header}", index + 1),
+        }
+    }
 }
 pub struct ArchiwumZ {
-    pub db: crate::db::Database,
+    pub settings: Settings,
+    pub db: Arc<dyn Storage>,
+    /// the archive `db` points at
+    pub archive: NamedArchive,
+    /// why the archive could not be opened on start, `db` is [`Unavailable`] then
+    pub archive_error: Option<String>,
     pub mode: Mode,
     pub repair_contract_entries_buffer: Vec<RepairContractEntry>,
+    pub contract_filter: ContractFilter,
+    /// positions in `repair_contract_entries_buffer` of the filtered contracts in list order,
+    /// see [`ArchiwumZ::refresh_contracts_list`]
+    pub contracts_list: Vec<usize>,
+    /// zero based page of the filtered contracts list
+    pub contracts_page: usize,
+    /// shown under the navigation until the page changes
+    pub notice: Option<String>,
 }
 
-mod local_messages {
-    use serde_json::Value;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusChoice {
+    All,
+    Only(ContractStatus),
+}
+
+impl StatusChoice {
+    pub const ALL: [StatusChoice; 3] = [
+        StatusChoice::All,
+        StatusChoice::Only(ContractStatus::Open),
+        StatusChoice::Only(ContractStatus::Closed),
+    ];
+}
+
+impl std::fmt::Display for StatusChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StatusChoice::All => "wszystkie".fmt(f),
+            StatusChoice::Only(status) => status.fmt(f),
+        }
+    }
+}
+
+const CONTRACTS_PAGE_SIZE: usize = 50;
+/// shown instead of saving while some field doesn't parse or breaks a rule
+const INVALID_FIELDS: &str = "popraw pola oznaczone błędem przed zapisem";
 
+/// records a patch of a derived form and writes the value that changed into `form`
+fn edit_form<'a, T>(
+    form: &mut T,
+    session: &mut EditSession,
+    update: UpdatedMessage,
+) -> IcedFormValueResult<()>
+where
+    T: IcedForm<'a, Message> + serde::Serialize + serde::de::DeserializeOwned,
+{
+    let selector = update.selector.clone();
+    if !session.update(update)? {
+        return Ok(());
+    }
+    let value = selector
+        .resolve(session.value())
+        .cloned()
+        .unwrap_or_default();
+    form.set_at(selector.segments(), value)
+}
+
+mod local_messages {
     use super::*;
     #[derive(Debug, Clone)]
     pub enum CreateRepairContract {
-        FormUpdated(IcedFormValueResult<Value>),
+        /// a patch of any field of the contract form
+        FieldPatched(UpdatedMessage),
+        SignatureStroke(SignatureStroke),
+        ClearSignature,
+        Save,
+        Saved(Arc<Result<RepairContractEntry>>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Handover {
+        Open(PathBuf),
+        Opened(Arc<Result<RepairContractEntry>>),
+        /// a patch of any field of the final protocol
+        FieldPatched(UpdatedMessage),
+        SignatureStroke(SignatureStroke),
+        ClearSignature,
+        Save,
+        Saved(Arc<Result<RepairContractEntry>>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum ConflictResolution {
+        KeepMine,
+        KeepTheirs,
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum DeleteContract {
+        Open(PathBuf),
+        Opened(Arc<Result<RepairContractEntry>>),
+        ReasonChanged(String),
+        Confirm,
+        Deleted(Arc<Result<()>>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Trash {
+        Show,
+        Loaded(Arc<Result<Vec<RepairContractEntry>>>),
+        Restore(PathBuf),
+        Purge(PathBuf),
+        Changed(Arc<Result<()>>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Backups {
+        Tick,
+        Done(Arc<Result<Option<PathBuf>>>),
+        Show,
+        Listed(Arc<Result<Vec<PathBuf>>>),
+        CreateNow,
+        Check(PathBuf),
+        Checked(Arc<Result<(PathBuf, BackupCheck)>>),
+        Restore,
+        Restored(Arc<Result<PathBuf>>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum ContractsList {
+        QueryChanged(String),
+        StatusChanged(StatusChoice),
+        ShowArchivedChanged(bool),
+        SortBy(SortColumn),
+        PageChanged(usize),
+        SetArchived(PathBuf, bool),
+        Updated(Arc<Result<RepairContractEntry>>),
+        Export(ExportFormat),
+        Exported(Arc<Result<Vec<PathBuf>>>),
+        /// writes the printable document of the contract
+        Print(PathBuf),
+        Printed(Arc<Result<PathBuf>>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Import {
+        PathChanged(String),
+        Load,
+        Loaded(Arc<Result<ImportSource>>),
+        MappingChanged(ImportField, ColumnChoice),
+        Run,
+        Finished(Arc<Result<ImportSummary>>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum SettingsPage {
+        FormUpdated(iced_forms::UpdatedMessage),
+        NewArchiveNameChanged(String),
+        NewArchiveDirChanged(String),
+        AddArchive,
+        RemoveArchive(String),
+        Save,
+        Saved(Arc<Result<Settings>>),
+    }
+
+    #[derive(Debug, Clone)]
+    pub enum Reports {
+        Show,
+        Export,
+        Exported(Arc<Result<PathBuf>>),
     }
 }
 
@@ -51,76 +301,726 @@ use local_messages::*;
 #[derive(Debug, Clone)]
 pub enum Message {
     SwitchMode(Mode),
+    /// opens the form of a new contract, filled in from the settings
+    NewContract,
+    /// opens the settings, filled in from the current ones
+    OpenSettings,
+    SwitchArchive(String),
     RefreshRepairContracts,
     RepairContractsRefreshed(Arc<Result<Vec<RepairContractEntry>>>),
     CreateRepairContract(CreateRepairContract),
+    Handover(Handover),
+    ConflictResolution(ConflictResolution),
+    DeleteContract(DeleteContract),
+    Trash(Trash),
+    Backups(Backups),
+    LeaseReleased(Arc<Result<()>>),
+    /// renews the lease of the contract being edited
+    RefreshLease,
+    LeaseRefreshed(Arc<Result<()>>),
+    ContractsList(ContractsList),
+    Reports(Reports),
+    Import(Import),
+    SettingsPage(SettingsPage),
+    /// undo or redo in the form being edited
+    History(HistoryAction),
 }
 
 mod custom_widgets {
     use super::*;
     pub fn repair_contract_entry_list_item(
         repair_contract_entry: &RepairContractEntry,
-    ) -> Container<Message> {
-        Container::new(Text::new(repair_contract_entry.model.id.to_string()))
+    ) -> Container<'_, Message> {
+        let model = &repair_contract_entry.model;
+        let path = &repair_contract_entry.path;
+        let (archive_label, archived) = match model.archived {
+            true => ("przywróć z archiwum", false),
+            false => ("archiwizuj", true),
+        };
+        let mut row = Row::new()
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(Text::new(model.id.to_string()));
+        if model.archived {
+            row = row.push(Text::new("[archiwum]"));
+        }
+        let handover = Button::new("wydanie urządzenia")
+            .on_press(Message::Handover(Handover::Open(path.clone())));
+        Container::new(
+            row.push(handover)
+                .push(
+                    Button::new("drukuj")
+                        .on_press(Message::ContractsList(ContractsList::Print(path.clone()))),
+                )
+                .push(Button::new(archive_label).on_press(Message::ContractsList(
+                    ContractsList::SetArchived(path.clone(), archived),
+                )))
+                .push(
+                    Button::new("usuń")
+                        .on_press(Message::DeleteContract(DeleteContract::Open(path.clone()))),
+                ),
+        )
+    }
+
+    pub fn signature_field<'a>(
+        title: &'a str,
+        signature: &'a Signature,
+        on_stroke: impl Fn(SignatureStroke) -> Message + 'a,
+        on_clear: Message,
+    ) -> Column<'a, Message> {
+        Column::new()
+            .spacing(10)
+            .push(Text::new(title))
+            .push(signature_pad::signature_pad(signature, on_stroke))
+            .push(Button::new("wyczyść podpis").on_press(on_clear))
     }
 }
 
 mod pages {
 
-    use iced::pure::text;
+    use iced::pure::{checkbox, pick_list, text, text_input};
+    use rust_decimal::prelude::ToPrimitive;
     use iced_forms::{IcedForm, IcedFormValueResult};
 
-    use crate::db::FillForm;
-
     use super::*;
-    pub fn index<'a>() -> Column<'a, Message> {
-        Column::new()
+    pub fn index(archive_error: &Option<String>) -> Column<'_, Message> {
+        let page = Column::new()
             .max_width(800)
             .spacing(20)
-            .align_items(Alignment::Center)
+            .align_items(Alignment::Center);
+        match archive_error {
+            Some(error) => page.push(text(error)).push(text(
+                "wybierz inne archiwum albo popraw jego położenie w ustawieniach",
+            )),
+            None => page,
+        }
     }
 
     pub fn create_new_contract_form<'a>(
-        repair_contract_entries: &'a [RepairContractEntry],
         form: &'a RepairContract,
-        buffer: &'a IcedFormValueResult<serde_json::Value>,
+        session: &'a EditSession,
+        signature: &'a Signature,
+    ) -> Column<'a, Message> {
+        let options = session.field_options();
+        let on_patch = Arc::new(|update| {
+            Message::CreateRepairContract(CreateRepairContract::FieldPatched(update))
+        });
+        let contract = form.view_patches(on_patch, Selector::empty(), options);
+        let signature = custom_widgets::signature_field(
+            "podpis klienta - akceptacja warunków zlecenia",
+            signature,
+            |stroke| Message::CreateRepairContract(CreateRepairContract::SignatureStroke(stroke)),
+            Message::CreateRepairContract(CreateRepairContract::ClearSignature),
+        );
+        let mut save = Button::new("zapisz zlecenie");
+        if session.can_submit() {
+            save = save.on_press(Message::CreateRepairContract(CreateRepairContract::Save));
+        }
+        Column::new()
+            .max_width(800)
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(history_controls(session))
+            .push(contract)
+            .push(signature)
+            .push(iced_forms::error_summary(options))
+            .push(save)
+    }
+
+    /// undo and redo buttons, with a note while the form differs from what was loaded
+    fn history_controls<'a>(session: &EditSession) -> Row<'a, Message> {
+        let mut undo = Button::new("cofnij");
+        if session.can_undo() {
+            undo = undo.on_press(Message::History(HistoryAction::Undo));
+        }
+        let mut redo = Button::new("ponów");
+        if session.can_redo() {
+            redo = redo.on_press(Message::History(HistoryAction::Redo));
+        }
+        let row = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(undo)
+            .push(redo);
+        match session.is_dirty() {
+            true => row.push(text("niezapisane zmiany")),
+            false => row,
+        }
+    }
+
+    pub fn handover<'a>(
+        entry: &'a RepairContractEntry,
+        protocol: &'a FinalProtocol,
+        session: &'a EditSession,
+        signature: &'a Signature,
+    ) -> Column<'a, Message> {
+        let options = session.field_options();
+        let on_patch = Arc::new(|update| Message::Handover(Handover::FieldPatched(update)));
+        let protocol = protocol.view_patches(on_patch, Selector::empty(), options);
+        let signature = custom_widgets::signature_field(
+            "podpis klienta - odbiór urządzenia",
+            signature,
+            |stroke| Message::Handover(Handover::SignatureStroke(stroke)),
+            Message::Handover(Handover::ClearSignature),
+        );
+        let mut save = Button::new("zapisz protokół");
+        if session.can_submit() {
+            save = save.on_press(Message::Handover(Handover::Save));
+        }
+        Column::new()
+            .max_width(800)
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(text(format!(
+                "wydanie urządzenia ze zlecenia {}",
+                entry.model.id
+            )))
+            .push(history_controls(session))
+            .push(protocol)
+            .push(signature)
+            .push(iced_forms::error_summary(options))
+            .push(save)
+    }
+
+    pub fn conflict<'a>(
+        mine: &'a RepairContractEntry,
+        theirs: &'a RepairContractEntry,
+    ) -> Column<'a, Message> {
+        let side = |title: &'a str, entry: &'a RepairContractEntry| {
+            let contents = entry.model.to_toml().unwrap_or_else(|e| format!("{e:#}"));
+            Column::new()
+                .spacing(10)
+                .width(Length::FillPortion(1))
+                .push(text(title))
+                .push(Scrollable::new(text(contents).size(14)).height(Length::Units(400)))
+        };
+        Column::new()
+            .max_width(800)
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(text(format!(
+                "zlecenie {} zostało zapisane na innym stanowisku w trakcie edycji",
+                mine.model.id
+            )))
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .push(side("moje zmiany", mine))
+                    .push(side("zapisane na dysku", theirs)),
+            )
+            .push(
+                Row::new()
+                    .spacing(20)
+                    .push(
+                        Button::new("nadpisz moimi zmianami")
+                            .on_press(Message::ConflictResolution(ConflictResolution::KeepMine)),
+                    )
+                    .push(
+                        Button::new("porzuć moje zmiany")
+                            .on_press(Message::ConflictResolution(ConflictResolution::KeepTheirs)),
+                    ),
+            )
+    }
+
+    pub fn delete_contract<'a>(
+        entry: &'a RepairContractEntry,
+        reason: &'a str,
+    ) -> Column<'a, Message> {
+        Column::new()
+            .max_width(800)
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(text(format!(
+                "przeniesienie zlecenia {} ({}) do kosza",
+                entry.model.id,
+                entry.model.info.customer.name()
+            )))
+            .push(text_input("powód usunięcia", reason, |reason| {
+                Message::DeleteContract(DeleteContract::ReasonChanged(reason))
+            }))
+            .push(
+                Button::new("przenieś do kosza")
+                    .on_press(Message::DeleteContract(DeleteContract::Confirm)),
+            )
+    }
+
+    pub fn trash(entries: &[RepairContractEntry]) -> Column<'_, Message> {
+        let entries = entries.iter().fold(
+            Column::new().spacing(10).align_items(Alignment::Start),
+            |acc, entry| {
+                let deleted = entry
+                    .model
+                    .deleted
+                    .as_ref()
+                    .map(|deleted| format!("usunięto {}: {}", deleted.date, deleted.reason))
+                    .unwrap_or_default();
+                acc.push(
+                    Row::new()
+                        .spacing(20)
+                        .align_items(Alignment::Center)
+                        .push(text(format!(
+                            "{} {} - {deleted}",
+                            entry.model.id,
+                            entry.model.info.customer.name()
+                        )))
+                        .push(
+                            Button::new("przywróć")
+                                .on_press(Message::Trash(Trash::Restore(entry.path.clone()))),
+                        )
+                        .push(
+                            Button::new("usuń na zawsze")
+                                .on_press(Message::Trash(Trash::Purge(entry.path.clone()))),
+                        ),
+                )
+            },
+        );
+        Column::new()
+            .max_width(800)
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(text("kosz"))
+            .push(Scrollable::new(entries))
+    }
+
+    pub fn backups<'a>(page: &'a BackupsPage, target_dir: Option<PathBuf>) -> Column<'a, Message> {
+        let file_name = |path: &'a Path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default()
+        };
+        let backups = page.backups.iter().fold(
+            Column::new().spacing(10).align_items(Alignment::Start),
+            |acc, backup| {
+                acc.push(
+                    Row::new()
+                        .spacing(20)
+                        .align_items(Alignment::Center)
+                        .push(text(file_name(backup)))
+                        .push(
+                            Button::new("sprawdź")
+                                .on_press(Message::Backups(Backups::Check(backup.clone()))),
+                        ),
+                )
+            },
+        );
+        let mut page_column = Column::new()
+            .max_width(800)
+            .spacing(20)
+            .align_items(Alignment::Start)
+            .push(text(format!(
+                "folder kopii: {}",
+                target_dir
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_default()
+            )))
+            .push(Button::new("utwórz kopię teraz").on_press(Message::Backups(Backups::CreateNow)));
+        if let Some(message) = &page.message {
+            page_column = page_column.push(text(message));
+        }
+        if let Some((backup, check)) = &page.checked {
+            page_column = page_column.push(text(format!(
+                "kopia {}: zleceń {}, nieczytelnych {}",
+                file_name(backup),
+                check.contracts,
+                check.problems.len()
+            )));
+            page_column = check.problems.iter().fold(page_column, |acc, (path, e)| {
+                acc.push(text(format!("{}: {e}", file_name(path))).size(16))
+            });
+            page_column = match check.refusal() {
+                Some(refusal) => {
+                    page_column.push(text(format!("nie można przywrócić: {refusal}")))
+                }
+                None => page_column.push(
+                    Button::new(text(format!(
+                        "przywróć tę kopię ({} zleceń, obecny stan zostanie zapisany w nowej kopii)",
+                        check.contracts
+                    )))
+                    .on_press(Message::Backups(Backups::Restore)),
+                ),
+            };
+        }
+        page_column.push(Scrollable::new(backups).height(Length::Units(400)))
+    }
+
+    pub fn reports(report: &Report) -> Column<'_, Message> {
+        let revenue = report
+            .monthly
+            .iter()
+            .map(|(month, stats)| {
+                (
+                    month.to_string(),
+                    stats.revenue.to_f32().unwrap_or_default(),
+                )
+            })
+            .collect();
+        let throughput =
+            report
+                .monthly
+                .iter()
+                .fold(Column::new().spacing(5), |acc, (month, stats)| {
+                    acc.push(text(format!(
+                        "{month}: otwarte {}, zamknięte {}",
+                        stats.opened, stats.closed
+                    )))
+                });
+        let top_device_models = report
+            .top_device_models
+            .iter()
+            .fold(Column::new().spacing(5), |acc, (model_name, count)| {
+                acc.push(text(format!("{model_name}: {count}")))
+            });
+        let turnaround = match report.average_turnaround_days {
+            Some(days) => format!("średni czas naprawy: {days:.1} dni"),
+            None => "średni czas naprawy: brak zamkniętych zleceń".to_string(),
+        };
+        Column::new()
+            .max_width(800)
+            .spacing(20)
+            .align_items(Alignment::Start)
+            .push(text("przychód miesięczny"))
+            .push(charts::bar_chart(revenue, 250))
+            .push(text("zlecenia"))
+            .push(throughput)
+            .push(text(turnaround))
+            .push(text(format!(
+                "części: {}, robocizna: {}",
+                report.parts_total, report.labour_total
+            )))
+            .push(text("najczęściej naprawiane modele"))
+            .push(top_device_models)
+            .push(Button::new("eksportuj CSV").on_press(Message::Reports(Reports::Export)))
+    }
+
+    pub fn import(wizard: &ImportWizard) -> Column<'_, Message> {
+        let file = Row::new()
+            .spacing(10)
+            .push(text_input("ścieżka do pliku CSV", &wizard.path, |path| {
+                Message::Import(Import::PathChanged(path))
+            }))
+            .push(Button::new("wczytaj").on_press(Message::Import(Import::Load)));
+        let mut page = Column::new()
+            .max_width(800)
+            .spacing(20)
+            .align_items(Alignment::Start)
+            .push(file);
+        if let Some(error) = &wizard.error {
+            page = page.push(text(error));
+        }
+        if let Some(source) = &wizard.source {
+            let choices = std::iter::once(ColumnChoice::Skip)
+                .chain(
+                    source
+                        .headers
+                        .iter()
+                        .enumerate()
+                        .map(|(index, header)| ColumnChoice::Column(index, header.clone())),
+                )
+                .collect::<Vec<_>>();
+            let mapping = ImportField::ALL
+                .iter()
+                .fold(Column::new().spacing(5), |acc, field| {
+                    let field = *field;
+                    let selected = match wizard.mapping.get(&field) {
+                        Some(index) => choices.get(index + 1).cloned(),
+                        None => Some(ColumnChoice::Skip),
+                    };
+                    acc.push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(text(field.to_string()).width(Length::Units(200)))
+                            .push(pick_list(choices.clone(), selected, move |choice| {
+                                Message::Import(Import::MappingChanged(field, choice))
+                            })),
+                    )
+                });
+            let importable = wizard.preview.iter().filter(|row| row.importable()).count();
+            let preview = wizard
+                .preview
+                .iter()
+                .fold(Column::new().spacing(5), |acc, row| {
+                    let description = match &row.result {
+                        Err(e) => format!("wiersz {}: błąd - {e}", row.line),
+                        Ok(contract) => format!(
+                            "wiersz {}: {}{} | {} | {} {}",
+                            row.line,
+                            if row.duplicate { "[duplikat] " } else { "" },
+                            contract.date,
+                            contract.info.customer.name(),
+                            contract.info.device.model_name,
+                            contract.info.device.serial_number,
+                        ),
+                    };
+                    acc.push(text(description).size(16))
+                });
+            page = page
+                .push(text("przypisanie kolumn"))
+                .push(mapping)
+                .push(text(format!(
+                    "podgląd: {importable} z {} wierszy zostanie zaimportowanych",
+                    wizard.preview.len()
+                )))
+                .push(Scrollable::new(preview).height(Length::Units(300)))
+                .push(Button::new("importuj").on_press(Message::Import(Import::Run)));
+        }
+        if let Some(summary) = &wizard.summary {
+            page = page.push(text(format!(
+                "zaimportowano {}, pominięto duplikatów {}, błędów {}",
+                summary.imported,
+                summary.skipped_duplicates,
+                summary.failed.len()
+            )));
+            page = summary.failed.iter().fold(page, |page, (line, e)| {
+                page.push(text(format!("wiersz {line}: {e}")).size(16))
+            });
+        }
+        page
+    }
+
+    pub fn settings<'a>(
+        settings: &'a Settings,
+        buffer: &'a IcedFormValueResult<Box<EditSession>>,
+        schema: &'a FieldSchema,
+        new_archive: &'a NamedArchive,
+        error: &'a Option<String>,
     ) -> Column<'a, Message> {
-        // let with_title = |text: &'static str, element| {
-        //     Row::new()
-        //         .spacing(30)
-        //         .align_items(Alignment::Start)
-        //         .push(Text::new(text).width(Length::Units(100)))
-        //         .push(element)
-        // };
         let form: Element<'a, _> = match buffer {
-            Ok(form) => form
-                .view(
-                    Arc::new(move |v| {
-                        Message::CreateRepairContract(CreateRepairContract::FormUpdated(v))
-                    }),
+            Ok(session) => Column::new()
+                .spacing(10)
+                .push(history_controls(session))
+                .push(iced_forms::json_form(
+                    session.value(),
+                    schema,
+                    Arc::new(move |v| Message::SettingsPage(SettingsPage::FormUpdated(v))),
                     Default::default(),
-                )
+                    &iced_forms::NO_TEMPLATES,
+                    session.field_options(),
+                ))
                 .into(),
             Err(e) => text(e.to_string()).into(),
         };
-        Column::new()
+        let (options, can_submit) = match buffer {
+            Ok(session) => (session.field_options(), session.can_submit()),
+            Err(_) => (Default::default(), false),
+        };
+        let mut save = Button::new("zapisz ustawienia");
+        if can_submit {
+            save = save.on_press(Message::SettingsPage(SettingsPage::Save));
+        }
+        let mut page = Column::new()
             .max_width(800)
             .spacing(20)
-            .align_items(Alignment::Center)
-            .push(form)
+            .align_items(Alignment::Start)
+            .push(text(format!("plik ustawień: {}", settings.path.display())))
+            .push(text(
+                "niezaznaczone lub puste ścieżki oznaczają domyślne położenie, zmiana folderu logów działa po ponownym uruchomieniu",
+            ).size(16))
+            .push(form);
+        if let Some(error) = error {
+            page = page.push(text(error));
+        }
+        let archives =
+            settings
+                .values
+                .archives
+                .iter()
+                .fold(Column::new().spacing(5), |acc, archive| {
+                    acc.push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Alignment::Center)
+                            .push(text(format!("{}: {}", archive.name, archive.dir.display())))
+                            .push(Button::new("usuń").on_press(Message::SettingsPage(
+                                SettingsPage::RemoveArchive(archive.name.clone()),
+                            ))),
+                    )
+                });
+        let add_archive = Row::new()
+            .spacing(10)
+            .push(text_input("nazwa", &new_archive.name, |name| {
+                Message::SettingsPage(SettingsPage::NewArchiveNameChanged(name))
+            }))
+            .push(text_input(
+                "folder",
+                &new_archive.dir.to_string_lossy(),
+                |dir| Message::SettingsPage(SettingsPage::NewArchiveDirChanged(dir)),
+            ))
+            .push(
+                Button::new("dodaj archiwum")
+                    .on_press(Message::SettingsPage(SettingsPage::AddArchive)),
+            );
+        page.push(iced_forms::error_summary(options))
+            .push(save)
+            .push(text("dodatkowe archiwa"))
+            .push(archives)
+            .push(add_archive)
     }
 
-    pub fn contracts_list(
-        repair_contract_entries: &'_ [RepairContractEntry],
-    ) -> Container<'_, Message> {
-        let entries = repair_contract_entries.iter().fold(
+    pub fn contracts_list<'a>(
+        repair_contract_entries: &'a [RepairContractEntry],
+        listed: &[usize],
+        filter: &'a ContractFilter,
+        sort: ContractSort,
+        page: usize,
+    ) -> Container<'a, Message> {
+        let status = match filter.status {
+            Some(status) => StatusChoice::Only(status),
+            None => StatusChoice::All,
+        };
+        let controls = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(text_input("szukaj", &filter.query, |query| {
+                Message::ContractsList(ContractsList::QueryChanged(query))
+            }))
+            .push(pick_list(&StatusChoice::ALL[..], Some(status), |status| {
+                Message::ContractsList(ContractsList::StatusChanged(status))
+            }))
+            .push(checkbox(
+                "zarchiwizowane",
+                filter.include_archived,
+                |show| Message::ContractsList(ContractsList::ShowArchivedChanged(show)),
+            ))
+            .push(Button::new("eksport CSV").on_press(Message::ContractsList(
+                ContractsList::Export(ExportFormat::Csv),
+            )))
+            .push(Button::new("eksport XLSX").on_press(Message::ContractsList(
+                ContractsList::Export(ExportFormat::Xlsx),
+            )));
+        let headers = SortColumn::ALL.iter().fold(
+            Row::new()
+                .spacing(10)
+                .align_items(Alignment::Center)
+                .push(text("sortuj:")),
+            |acc, &column| {
+                let label = match (sort.column == column, sort.descending) {
+                    (true, true) => format!("{column} (malejąco)"),
+                    (true, false) => format!("{column} (rosnąco)"),
+                    (false, _) => column.to_string(),
+                };
+                acc.push(
+                    Button::new(text(label))
+                        .on_press(Message::ContractsList(ContractsList::SortBy(column))),
+                )
+            },
+        );
+        let pages = listed.len().div_ceil(CONTRACTS_PAGE_SIZE).max(1);
+        let page = page.min(pages - 1);
+        let count = listed.len();
+        let listed = listed
+            .iter()
+            .skip(page * CONTRACTS_PAGE_SIZE)
+            .take(CONTRACTS_PAGE_SIZE)
+            .map(|&index| &repair_contract_entries[index])
+            .fold(
+                Column::new()
+                    .max_width(800)
+                    .spacing(20)
+                    .align_items(Alignment::Start),
+                |acc, next| acc.push(custom_widgets::repair_contract_entry_list_item(next)),
+            );
+        let mut previous = Button::new("poprzednia");
+        if page > 0 {
+            previous =
+                previous.on_press(Message::ContractsList(ContractsList::PageChanged(page - 1)));
+        }
+        let mut next = Button::new("następna");
+        if page + 1 < pages {
+            next = next.on_press(Message::ContractsList(ContractsList::PageChanged(page + 1)));
+        }
+        let pagination = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(previous)
+            .push(text(format!(
+                "strona {} z {pages} ({count} zleceń)",
+                page + 1,
+            )))
+            .push(next);
+        Container::new(
             Column::new()
-                .max_width(800)
                 .spacing(20)
-                .align_items(Alignment::Start),
-            |acc, next| acc.push(custom_widgets::repair_contract_entry_list_item(next)),
-        );
-        Container::new(Scrollable::new(entries))
+                .push(controls)
+                .push(headers)
+                .push(Scrollable::new(listed))
+                .push(pagination),
+        )
+    }
+}
+
+impl ImportWizard {
+    fn refresh_preview(&mut self, existing: &[RepairContractEntry]) {
+        self.preview = match &self.source {
+            Some(source) => crate::import::preview(source, &self.mapping, existing),
+            None => vec![],
+        };
+    }
+}
+
+impl ArchiwumZ {
+    /// switches the page, giving up the lease of the contract that was being edited
+    fn set_mode(&mut self, mode: Mode) -> Command<Message> {
+        let previous = std::mem::replace(&mut self.mode, mode);
+        self.notice = None;
+        match previous.leased_path() {
+            Some(path) if Some(path) != self.mode.leased_path() => {
+                let db = self.db.clone();
+                let path = path.to_owned();
+                Command::perform(
+                    async move { db.release_lease(&path).map(Arc::new).await },
+                    Message::LeaseReleased,
+                )
+            }
+            _ => Command::none(),
+        }
+    }
+
+    fn entry_saved(&mut self) -> Command<Message> {
+        Command::batch([
+            self.set_mode(Mode::ViewingEntries),
+            self.update(Message::RefreshRepairContracts),
+        ])
+    }
+
+    fn open_archive(&mut self, archive: NamedArchive) -> Command<Message> {
+        let db = match crate::storage::open(&archive.dir) {
+            Ok(db) => db,
+            Err(e) => {
+                self.notice = Some(format!("{e:#}"));
+                return Command::none();
+            }
+        };
+        let mode = match &self.mode {
+            Mode::Settings { .. } => self.mode.clone(),
+            _ => Mode::ViewingEntries,
+        };
+        let released = self.set_mode(mode);
+        self.db = db;
+        self.archive = archive;
+        self.archive_error = None;
+        self.repair_contract_entries_buffer.clear();
+        self.contracts_list.clear();
+        Command::batch([released, self.update(Message::RefreshRepairContracts)])
+    }
+
+    /// filters and sorts the contracts list, only when the entries, the filter or the order change
+    fn refresh_contracts_list(&mut self) {
+        let entries = &self.repair_contract_entries_buffer;
+        let sort = self.settings.values.contracts_sort;
+        self.contracts_list = (0..entries.len())
+            .filter(|&index| self.contract_filter.matches(&entries[index]))
+            .collect();
+        self.contracts_list
+            .sort_by(|&a, &b| sort.compare(&entries[a], &entries[b]));
+    }
+
+    fn save_settings(settings: Settings) -> Command<Message> {
+        Command::perform(
+            async move { tokio::task::block_in_place(|| settings.save()).map(|_| settings) }
+                .map(Arc::new),
+            |res| Message::SettingsPage(SettingsPage::Saved(res)),
+        )
     }
 }
 
@@ -129,31 +1029,139 @@ impl Application for ArchiwumZ {
 
     type Message = Message;
 
-    type Flags = ();
+    type Flags = Settings;
 
-    fn new(_: Self::Flags) -> (Self, iced::Command<Self::Message>) {
-        let base_dir = crate::filesystem::base_directory()
-            .expect("nie udało się stworzyć aplikacji")
-            .join("archiwum");
-        let db = crate::db::Database::new(base_dir);
+    fn new(settings: Self::Flags) -> (Self, iced::Command<Self::Message>) {
+        let (archive, opened) = match settings.current_archive() {
+            Ok(archive) => {
+                let opened = crate::storage::open(&archive.dir).map_err(|e| {
+                    error!("{e:#?}");
+                    format!("nie udało się otworzyć archiwum [{}] :: {e:#}", archive.name)
+                });
+                (archive, opened)
+            }
+            Err(e) => {
+                error!("{e:#?}");
+                let e = format!("nie udało się wybrać archiwum :: {e:#}");
+                (NamedArchive::default(), Err(e))
+            }
+        };
+        let (db, archive_error) = match opened {
+            Ok(db) => (db, None),
+            Err(e) => (Arc::new(Unavailable(e.clone())) as Arc<dyn Storage>, Some(e)),
+        };
         (
             Self {
+                settings,
                 db,
+                archive,
+                archive_error,
                 mode: Mode::Index,
                 repair_contract_entries_buffer: vec![],
+                contract_filter: Default::default(),
+                contracts_list: Default::default(),
+                contracts_page: 0,
+                notice: None,
             },
-            Command::none(),
+            // the contracts are loaded right away, the reports and the import rely on them
+            Command::batch([
+                Command::perform(async {}, |_| Message::RefreshRepairContracts),
+                Command::perform(async {}, |_| Message::Backups(Backups::Tick)),
+            ]),
         )
     }
 
+    fn subscription(&self) -> iced::Subscription<Self::Message> {
+        let backups = match self.settings.values.backup.enabled {
+            true => {
+                iced::time::every(BACKUP_CHECK_INTERVAL).map(|_| Message::Backups(Backups::Tick))
+            }
+            false => iced::Subscription::none(),
+        };
+        let shortcuts = iced_native::subscription::events_with(|event, _| match event {
+            iced_native::Event::Keyboard(event) => {
+                iced_forms::history_shortcut(&event).map(Message::History)
+            }
+            _ => None,
+        });
+        let lease = match self.mode.leased_path() {
+            Some(_) => iced::time::every(crate::db::LEASE_REFRESH_INTERVAL)
+                .map(|_| Message::RefreshLease),
+            None => iced::Subscription::none(),
+        };
+        iced::Subscription::batch([backups, shortcuts, lease])
+    }
+
     fn title(&self) -> String {
-        "Archiwum Z".to_string()
+        format!("Archiwum Z - {}", self.archive.name)
     }
 
     fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
         match message {
-            Message::SwitchMode(mode) => {
-                self.mode = mode;
+            Message::SwitchMode(mode) => return self.set_mode(mode),
+            Message::NewContract => {
+                let form = self.settings.new_contract();
+                let value = iced_forms::to_value(form.clone()).unwrap_or_default();
+                let session = EditSession::new(value)
+                    .validated_by(crate::models::form_validators::<RepairContract>());
+                return self.set_mode(Mode::CreateNewRepairContract {
+                    form: Box::new(form),
+                    session: Box::new(session),
+                    signature: Default::default(),
+                });
+            }
+            Message::OpenSettings => {
+                let buffer = iced_forms::to_value(self.settings.values.clone())
+                    .map(crate::settings::to_form)
+                    .map(|form| {
+                        EditSession::new(form).validated_by(crate::settings::form_validators())
+                    })
+                    .map(Box::new);
+                return self.set_mode(Mode::Settings {
+                    buffer,
+                    schema: FieldSchema::of::<AppSettings>(),
+                    new_archive: Default::default(),
+                    error: None,
+                });
+            }
+            Message::LeaseReleased(res) => {
+                if let Err(e) = res.as_ref() {
+                    error!("{e:#?}");
+                }
+            }
+            Message::RefreshLease => {
+                if let Some(path) = self.mode.leased_path() {
+                    let db = self.db.clone();
+                    let path = path.to_owned();
+                    return Command::perform(
+                        async move { db.refresh_lease(&path).map(Arc::new).await },
+                        Message::LeaseRefreshed,
+                    );
+                }
+            }
+            Message::LeaseRefreshed(res) => {
+                if let Err(e) = res.as_ref() {
+                    error!("{e:#?}");
+                    self.notice = Some(format!("{e:#}"));
+                }
+            }
+            Message::SwitchArchive(name) => {
+                let archive = self.settings.archives().and_then(|archives| {
+                    archives
+                        .into_iter()
+                        .find(|archive| archive.name == name)
+                        .with_context(|| format!("nie ma archiwum o nazwie [{name}]"))
+                });
+                match archive {
+                    Ok(archive) => {
+                        self.settings.open_archive(&name);
+                        return Command::batch([
+                            self.open_archive(archive),
+                            Self::save_settings(self.settings.clone()),
+                        ]);
+                    }
+                    Err(e) => error!("{e:#?}"),
+                }
             }
             Message::RefreshRepairContracts => {
                 let db = self.db.clone();
@@ -163,23 +1171,724 @@ impl Application for ArchiwumZ {
                 );
             }
             Message::RepairContractsRefreshed(res) => match res.as_ref() {
-                Ok(contracts) => self.repair_contract_entries_buffer = contracts.clone(),
+                Ok(contracts) => {
+                    self.repair_contract_entries_buffer = contracts.clone();
+                    self.refresh_contracts_list();
+                    match &mut self.mode {
+                        Mode::Reports(report) => {
+                            *report = Report::from_entries(&self.repair_contract_entries_buffer)
+                        }
+                        Mode::Import(wizard) => {
+                            wizard.refresh_preview(&self.repair_contract_entries_buffer)
+                        }
+                        _ => {}
+                    }
+                }
                 Err(e) => error!("{e:#?}"),
             },
             Message::CreateRepairContract(message) => match message {
-                local_messages::CreateRepairContract::FormUpdated(updated) => {
-                    match &mut self.mode {
-                        Mode::Index => todo!(),
-                        Mode::ViewingEntries => todo!(),
-                        Mode::CreateNewRepairContract { form, buffer } => *buffer = updated,
+                local_messages::CreateRepairContract::FieldPatched(update) => {
+                    if let Mode::CreateNewRepairContract { form, session, .. } = &mut self.mode {
+                        self.notice = edit_form(form.as_mut(), session, update)
+                            .err()
+                            .map(|e| format!("niepoprawna wartość :: {e}"));
+                    }
+                }
+                local_messages::CreateRepairContract::SignatureStroke(stroke) => {
+                    if let Mode::CreateNewRepairContract { signature, .. } = &mut self.mode {
+                        signature.strokes.push(stroke);
+                    }
+                }
+                local_messages::CreateRepairContract::ClearSignature => {
+                    if let Mode::CreateNewRepairContract { signature, .. } = &mut self.mode {
+                        *signature = Default::default();
+                    }
+                }
+                local_messages::CreateRepairContract::Save => {
+                    if let Mode::CreateNewRepairContract {
+                        form,
+                        session,
+                        signature,
+                    } = &self.mode
+                    {
+                        if !session.can_submit() {
+                            self.notice = Some(INVALID_FIELDS.to_owned());
+                            return Command::none();
+                        }
+                        let mut model = RepairContract::clone(form);
+                        model.customer_signature =
+                            Some(signature.clone()).filter(|s| !s.is_empty());
+                        let db = self.db.clone();
+                        return Command::perform(
+                            async move { db.create_entry(model).map(Arc::new).await },
+                            |res| Message::CreateRepairContract(CreateRepairContract::Saved(res)),
+                        );
+                    }
+                }
+                local_messages::CreateRepairContract::Saved(res) => match res.as_ref() {
+                    Ok(_) => return self.entry_saved(),
+                    Err(e) => error!("{e:#?}"),
+                },
+            },
+            Message::Handover(message) => match message {
+                Handover::Open(path) => {
+                    let db = self.db.clone();
+                    return Command::perform(
+                        async move { db.acquire_lease(&path).map(Arc::new).await },
+                        |res| Message::Handover(Handover::Opened(res)),
+                    );
+                }
+                Handover::Opened(res) => match res.as_ref() {
+                    Ok(entry) => {
+                        let protocol = entry.model.final_protocol.clone().unwrap_or_default();
+                        let value = iced_forms::to_value(protocol.clone()).unwrap_or_default();
+                        let session = EditSession::new(value)
+                            .validated_by(crate::models::form_validators::<FinalProtocol>());
+                        return self.set_mode(Mode::Handover {
+                            entry: Box::new(entry.clone()),
+                            signature: protocol.customer_signature.clone().unwrap_or_default(),
+                            protocol: Box::new(protocol),
+                            session: Box::new(session),
+                        });
+                    }
+                    Err(e) => self.notice = Some(format!("{e:#}")),
+                },
+                Handover::FieldPatched(update) => {
+                    if let Mode::Handover {
+                        protocol, session, ..
+                    } = &mut self.mode
+                    {
+                        self.notice = edit_form(protocol.as_mut(), session, update)
+                            .err()
+                            .map(|e| format!("niepoprawna wartość :: {e}"));
+                    }
+                }
+                Handover::SignatureStroke(stroke) => {
+                    if let Mode::Handover { signature, .. } = &mut self.mode {
+                        signature.strokes.push(stroke);
+                    }
+                }
+                Handover::ClearSignature => {
+                    if let Mode::Handover { signature, .. } = &mut self.mode {
+                        *signature = Default::default();
+                    }
+                }
+                Handover::Save => {
+                    if let Mode::Handover {
+                        entry,
+                        protocol,
+                        session,
+                        signature,
+                    } = &self.mode
+                    {
+                        if !session.can_submit() {
+                            self.notice = Some(INVALID_FIELDS.to_owned());
+                            return Command::none();
+                        }
+                        let mut entry = RepairContractEntry::clone(entry);
+                        entry.model.final_protocol = Some(FinalProtocol {
+                            customer_signature: Some(signature.clone()).filter(|s| !s.is_empty()),
+                            ..FinalProtocol::clone(protocol)
+                        });
+                        let db = self.db.clone();
+                        return Command::perform(
+                            async move { db.update_entry(entry).map(Arc::new).await },
+                            |res| Message::Handover(Handover::Saved(res)),
+                        );
+                    }
+                }
+                Handover::Saved(res) => match res.as_ref() {
+                    Ok(_) => return self.entry_saved(),
+                    Err(e) => match e.downcast_ref::<crate::db::Conflict>() {
+                        Some(conflict) => {
+                            return self.set_mode(Mode::Conflict(Box::new(conflict.clone())))
+                        }
+                        None => {
+                            error!("{e:#?}");
+                            self.notice = Some(format!("{e:#}"));
+                        }
+                    },
+                },
+            },
+            Message::DeleteContract(message) => match message {
+                DeleteContract::Open(path) => {
+                    let db = self.db.clone();
+                    return Command::perform(
+                        async move { db.acquire_lease(&path).map(Arc::new).await },
+                        |res| Message::DeleteContract(DeleteContract::Opened(res)),
+                    );
+                }
+                DeleteContract::Opened(res) => match res.as_ref() {
+                    Ok(entry) => {
+                        return self.set_mode(Mode::DeleteContract {
+                            entry: Box::new(entry.clone()),
+                            reason: String::new(),
+                        })
+                    }
+                    Err(e) => self.notice = Some(format!("{e:#}")),
+                },
+                DeleteContract::ReasonChanged(updated) => {
+                    if let Mode::DeleteContract { reason, .. } = &mut self.mode {
+                        *reason = updated;
+                    }
+                }
+                DeleteContract::Confirm => {
+                    if let Mode::DeleteContract { entry, reason } = &self.mode {
+                        let entry = entry.clone();
+                        let reason = reason.trim().to_owned();
+                        let db = self.db.clone();
+                        return Command::perform(
+                            async move { db.delete_entry(&entry, &reason).map(Arc::new).await },
+                            |res| Message::DeleteContract(DeleteContract::Deleted(res)),
+                        );
+                    }
+                }
+                DeleteContract::Deleted(res) => match res.as_ref() {
+                    Ok(_) => return self.entry_saved(),
+                    Err(e) => {
+                        error!("{e:#?}");
+                        self.notice = Some(format!("{e:#}"));
+                    }
+                },
+            },
+            Message::Trash(message) => match message {
+                Trash::Show => {
+                    let db = self.db.clone();
+                    return Command::perform(
+                        async move { db.get_trash().map(Arc::new).await },
+                        |res| Message::Trash(Trash::Loaded(res)),
+                    );
+                }
+                Trash::Loaded(res) => match res.as_ref() {
+                    Ok(entries) => return self.set_mode(Mode::Trash(entries.clone())),
+                    Err(e) => self.notice = Some(format!("{e:#}")),
+                },
+                Trash::Restore(ref path) | Trash::Purge(ref path) => {
+                    let entry = match &self.mode {
+                        Mode::Trash(entries) => entries.iter().find(|entry| entry.path == *path),
+                        _ => None,
+                    };
+                    if let Some(entry) = entry.cloned() {
+                        let db = self.db.clone();
+                        let restore = matches!(message, Trash::Restore(_));
+                        return Command::perform(
+                            async move {
+                                match restore {
+                                    true => db.restore_entry(&entry).await.map(|_| ()),
+                                    false => db.purge_entry(&entry).await,
+                                }
+                            }
+                            .map(Arc::new),
+                            |res| Message::Trash(Trash::Changed(res)),
+                        );
+                    }
+                }
+                Trash::Changed(res) => {
+                    if let Err(e) = res.as_ref() {
+                        error!("{e:#?}");
+                        self.notice = Some(format!("{e:#}"));
+                    }
+                    return Command::batch([
+                        self.update(Message::Trash(Trash::Show)),
+                        self.update(Message::RefreshRepairContracts),
+                    ]);
+                }
+            },
+            Message::Backups(message) => {
+                if let Some(error) = &self.archive_error {
+                    if let Backups::Show | Backups::CreateNow = message {
+                        self.notice = Some(error.clone());
+                    }
+                    return Command::none();
+                }
+                let archive_dir = self.archive.dir.clone();
+                // backups zip the folder of the archive, a database is backed up as a file
+                if crate::storage::is_sqlite(&archive_dir) {
+                    if let Backups::Show | Backups::CreateNow = message {
+                        self.notice = Some(
+                            "kopie zapasowe obejmują tylko archiwa w postaci folderu".to_owned(),
+                        );
+                    }
+                    return Command::none();
+                }
+                let target_dir = match self.settings.backup_dir(&self.archive) {
+                    Ok(target_dir) => target_dir,
+                    Err(e) => {
+                        error!("{e:#?}");
+                        return Command::none();
+                    }
+                };
+                match message {
+                    Backups::Tick => {
+                        let backup = self.settings.values.backup.clone();
+                        return Command::perform(
+                            async move {
+                                tokio::task::block_in_place(|| {
+                                    crate::backup::scheduled_backup(
+                                        &backup,
+                                        &archive_dir,
+                                        &target_dir,
+                                    )
+                                })
+                            }
+                            .map(Arc::new),
+                            |res| Message::Backups(Backups::Done(res)),
+                        );
+                    }
+                    Backups::CreateNow => {
+                        let keep = self.settings.values.backup.keep;
+                        return Command::perform(
+                            async move {
+                                tokio::task::block_in_place(|| {
+                                    let backup =
+                                        crate::backup::create_backup(&archive_dir, &target_dir)?;
+                                    crate::backup::apply_retention(&target_dir, keep)?;
+                                    Ok(Some(backup))
+                                })
+                            }
+                            .map(Arc::new),
+                            |res| Message::Backups(Backups::Done(res)),
+                        );
+                    }
+                    Backups::Done(res) => match res.as_ref() {
+                        Ok(Some(backup)) => {
+                            tracing::info!("utworzono kopię zapasową {backup:?}");
+                            if let Mode::Backups(_) = self.mode {
+                                return self.update(Message::Backups(Backups::Show));
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => error!("nie udało się utworzyć kopii zapasowej :: {e:#?}"),
+                    },
+                    Backups::Show => {
+                        return Command::perform(
+                            async move {
+                                tokio::task::block_in_place(|| {
+                                    crate::backup::list_backups(&target_dir)
+                                })
+                            }
+                            .map(Arc::new),
+                            |res| Message::Backups(Backups::Listed(res)),
+                        );
+                    }
+                    Backups::Listed(res) => match res.as_ref() {
+                        Ok(backups) => match &mut self.mode {
+                            Mode::Backups(page) => page.backups = backups.clone(),
+                            _ => {
+                                return self.set_mode(Mode::Backups(BackupsPage {
+                                    backups: backups.clone(),
+                                    ..Default::default()
+                                }))
+                            }
+                        },
+                        Err(e) => self.notice = Some(format!("{e:#}")),
+                    },
+                    Backups::Check(backup) => {
+                        return Command::perform(
+                            async move {
+                                crate::backup::check_backup(&backup, &archive_dir)
+                                    .await
+                                    .map(|check| (backup, check))
+                            }
+                            .map(Arc::new),
+                            |res| Message::Backups(Backups::Checked(res)),
+                        );
+                    }
+                    Backups::Checked(res) => {
+                        if let Mode::Backups(page) = &mut self.mode {
+                            match res.as_ref() {
+                                Ok(checked) => {
+                                    page.checked = Some(checked.clone());
+                                    page.message = None;
+                                }
+                                Err(e) => page.message = Some(format!("{e:#}")),
+                            }
+                        }
+                    }
+                    Backups::Restore => {
+                        let backup = match &self.mode {
+                            Mode::Backups(BackupsPage {
+                                checked: Some((backup, check)),
+                                ..
+                            }) if check.refusal().is_none() => backup.clone(),
+                            _ => return Command::none(),
+                        };
+                        return Command::perform(
+                            async move {
+                                crate::backup::restore_backup(&backup, &archive_dir, &target_dir)
+                                    .await
+                            }
+                            .map(Arc::new),
+                            |res| Message::Backups(Backups::Restored(res)),
+                        );
+                    }
+                    Backups::Restored(res) => {
+                        if let Mode::Backups(page) = &mut self.mode {
+                            page.checked = None;
+                            page.message = Some(match res.as_ref() {
+                                Ok(safety_backup) => format!(
+                                    "przywrócono archiwum, poprzedni stan zapisano w {}",
+                                    safety_backup.display()
+                                ),
+                                Err(e) => format!("{e:#}"),
+                            });
+                        }
+                        return Command::batch([
+                            self.update(Message::Backups(Backups::Show)),
+                            self.update(Message::RefreshRepairContracts),
+                        ]);
+                    }
+                }
+            }
+            Message::ConflictResolution(message) => {
+                if let Mode::Conflict(conflict) = &self.mode {
+                    match message {
+                        ConflictResolution::KeepMine => {
+                            let mut entry = conflict.mine.clone();
+                            entry.model.revision = conflict.theirs.model.revision;
+                            let db = self.db.clone();
+                            return Command::perform(
+                                async move { db.update_entry(entry).map(Arc::new).await },
+                                |res| Message::Handover(Handover::Saved(res)),
+                            );
+                        }
+                        ConflictResolution::KeepTheirs => return self.entry_saved(),
+                    }
+                }
+            }
+            Message::ContractsList(message) => match message {
+                ContractsList::QueryChanged(query) => {
+                    self.contract_filter.query = query;
+                    self.contracts_page = 0;
+                    self.refresh_contracts_list();
+                }
+                ContractsList::StatusChanged(status) => {
+                    self.contract_filter.status = match status {
+                        StatusChoice::All => None,
+                        StatusChoice::Only(status) => Some(status),
+                    };
+                    self.contracts_page = 0;
+                    self.refresh_contracts_list();
+                }
+                ContractsList::ShowArchivedChanged(show) => {
+                    self.contract_filter.include_archived = show;
+                    self.contracts_page = 0;
+                    self.refresh_contracts_list();
+                }
+                ContractsList::SortBy(column) => {
+                    let sort = &mut self.settings.values.contracts_sort;
+                    *sort = sort.toggled(column);
+                    self.contracts_page = 0;
+                    self.refresh_contracts_list();
+                    return Self::save_settings(self.settings.clone());
+                }
+                ContractsList::PageChanged(page) => self.contracts_page = page,
+                ContractsList::SetArchived(path, archived) => {
+                    let db = self.db.clone();
+                    return Command::perform(
+                        // under the lease, so a contract someone is editing is left alone
+                        async move {
+                            let mut entry = db.acquire_lease(&path).await?;
+                            entry.model.archived = archived;
+                            let updated = db.update_entry(entry).await;
+                            db.release_lease(&path).await?;
+                            updated
+                        }
+                        .map(Arc::new),
+                        |res| Message::ContractsList(ContractsList::Updated(res)),
+                    );
+                }
+                ContractsList::Updated(res) => {
+                    if let Err(e) = res.as_ref() {
+                        error!("{e:#?}");
+                        self.notice = Some(format!("{e:#}"));
+                    }
+                    return self.update(Message::RefreshRepairContracts);
+                }
+                ContractsList::Export(format) => {
+                    let settings = self.settings.clone();
+                    let entries = self
+                        .contract_filter
+                        .apply(&self.repair_contract_entries_buffer)
+                        .into_iter()
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    return Command::perform(
+                        async move {
+                            tokio::task::block_in_place(|| {
+                                let path = crate::export::default_export_path(&settings, format)?;
+                                crate::export::export_contracts(
+                                    &entries.iter().collect::<Vec<_>>(),
+                                    format,
+                                    &path,
+                                )
+                            })
+                        }
+                        .map(Arc::new),
+                        |res| Message::ContractsList(ContractsList::Exported(res)),
+                    );
+                }
+                ContractsList::Exported(res) => match res.as_ref() {
+                    Ok(paths) => tracing::info!("wyeksportowano zlecenia do {paths:?}"),
+                    Err(e) => error!("{e:#?}"),
+                },
+                ContractsList::Print(path) => {
+                    let settings = self.settings.clone();
+                    let model = match self
+                        .repair_contract_entries_buffer
+                        .iter()
+                        .find(|entry| entry.path == path)
+                    {
+                        Some(entry) => entry.model.clone(),
+                        None => return Command::none(),
+                    };
+                    return Command::perform(
+                        async move {
+                            tokio::task::block_in_place(|| {
+                                let path = crate::export::default_print_path(&settings, &model)?;
+                                crate::export::print_contract(&model, &settings.company(), &path)?;
+                                Ok(path)
+                            })
+                        }
+                        .map(Arc::new),
+                        |res| Message::ContractsList(ContractsList::Printed(res)),
+                    );
+                }
+                ContractsList::Printed(res) => match res.as_ref() {
+                    Ok(path) => self.notice = Some(format!("zapisano wydruk {}", path.display())),
+                    Err(e) => {
+                        error!("{e:#?}");
+                        self.notice = Some(format!("{e:#}"));
+                    }
+                },
+            },
+            Message::Import(message) => {
+                let wizard = match &mut self.mode {
+                    Mode::Import(wizard) => wizard,
+                    _ => return Command::none(),
+                };
+                match message {
+                    Import::PathChanged(path) => wizard.path = path,
+                    Import::Load => {
+                        let path = PathBuf::from(wizard.path.trim());
+                        return Command::perform(
+                            async move {
+                                tokio::task::block_in_place(|| ImportSource::from_path(&path))
+                            }
+                            .map(Arc::new),
+                            |res| Message::Import(Import::Loaded(res)),
+                        );
+                    }
+                    Import::Loaded(res) => match res.as_ref() {
+                        Ok(source) => {
+                            wizard.mapping = source.guess_mapping();
+                            wizard.source = Some(Arc::new(source.clone()));
+                            wizard.summary = None;
+                            wizard.error = None;
+                            wizard.refresh_preview(&self.repair_contract_entries_buffer);
+                        }
+                        Err(e) => wizard.error = Some(format!("{e:#}")),
+                    },
+                    Import::MappingChanged(field, choice) => {
+                        match choice {
+                            ColumnChoice::Skip => wizard.mapping.remove(&field),
+                            ColumnChoice::Column(index, _) => wizard.mapping.insert(field, index),
+                        };
+                        wizard.refresh_preview(&self.repair_contract_entries_buffer);
+                    }
+                    Import::Run => {
+                        let db = self.db.clone();
+                        let rows = wizard.preview.clone();
+                        return Command::perform(
+                            async move { crate::import::import(&*db, rows).map(Arc::new).await },
+                            |res| Message::Import(Import::Finished(res)),
+                        );
+                    }
+                    Import::Finished(res) => {
+                        match res.as_ref() {
+                            Ok(summary) => {
+                                wizard.summary = Some(summary.clone());
+                                wizard.source = None;
+                                wizard.preview.clear();
+                            }
+                            Err(e) => wizard.error = Some(format!("{e:#}")),
+                        }
+                        return self.update(Message::RefreshRepairContracts);
                     }
                 }
+            }
+            Message::SettingsPage(message) => match message {
+                SettingsPage::FormUpdated(updated) => {
+                    if let Mode::Settings {
+                        buffer: Ok(session),
+                        error,
+                        ..
+                    } = &mut self.mode
+                    {
+                        let selector = updated.selector.clone();
+                        match session.update(updated) {
+                            Ok(changed) => {
+                                if changed {
+                                    tracing::debug!("zmieniono ustawienie {selector}");
+                                }
+                                *error = None;
+                            }
+                            Err(e) => *error = Some(e.to_string()),
+                        }
+                    }
+                }
+                SettingsPage::NewArchiveNameChanged(name) => {
+                    if let Mode::Settings { new_archive, .. } = &mut self.mode {
+                        new_archive.name = name;
+                    }
+                }
+                SettingsPage::NewArchiveDirChanged(dir) => {
+                    if let Mode::Settings { new_archive, .. } = &mut self.mode {
+                        new_archive.dir = dir.into();
+                    }
+                }
+                SettingsPage::AddArchive => {
+                    if let Mode::Settings {
+                        new_archive, error, ..
+                    } = &mut self.mode
+                    {
+                        let mut settings = self.settings.clone();
+                        match settings.add_archive(new_archive.clone()) {
+                            Ok(()) => {
+                                *new_archive = Default::default();
+                                return Self::save_settings(settings);
+                            }
+                            Err(e) => *error = Some(format!("{e:#}")),
+                        }
+                    }
+                }
+                SettingsPage::RemoveArchive(name) => {
+                    let mut settings = self.settings.clone();
+                    settings.remove_archive(&name);
+                    return Self::save_settings(settings);
+                }
+                SettingsPage::Save => {
+                    if let Mode::Settings { buffer, error, .. } = &mut self.mode {
+                        if buffer.as_ref().is_ok_and(|session| !session.can_submit()) {
+                            *error = Some(INVALID_FIELDS.to_owned());
+                            return Command::none();
+                        }
+                        let values = buffer
+                            .as_ref()
+                            .map_err(Clone::clone)
+                            .map(|session| crate::settings::from_form(session.value().clone()))
+                            .and_then(|buffer| iced_forms::from_value::<AppSettings>(&buffer));
+                        match values {
+                            Ok(values) => {
+                                return Self::save_settings(Settings {
+                                    values: AppSettings {
+                                        archives: self.settings.values.archives.clone(),
+                                        recent_archives: self
+                                            .settings
+                                            .values
+                                            .recent_archives
+                                            .clone(),
+                                        contracts_sort: self.settings.values.contracts_sort,
+                                        ..values
+                                    },
+                                    ..self.settings.clone()
+                                });
+                            }
+                            Err(e) => *error = Some(e.to_string()),
+                        }
+                    }
+                }
+                SettingsPage::Saved(res) => match res.as_ref() {
+                    Ok(settings) => {
+                        self.settings = settings.clone();
+                        if let Mode::Settings { buffer, error, .. } = &mut self.mode {
+                            if let (Ok(session), Ok(saved)) = (
+                                buffer.as_mut(),
+                                iced_forms::to_value(self.settings.values.clone()),
+                            ) {
+                                session.set_original(crate::settings::to_form(saved));
+                            }
+                            *error = None;
+                        }
+                        match self.settings.current_archive() {
+                            Ok(archive) if archive != self.archive => {
+                                return self.open_archive(archive);
+                            }
+                            Ok(_) => {}
+                            Err(e) => error!("{e:#?}"),
+                        }
+                    }
+                    Err(e) => {
+                        error!("{e:#?}");
+                        if let Mode::Settings { error, .. } = &mut self.mode {
+                            *error = Some(format!("{e:#}"));
+                        }
+                    }
+                },
+            },
+            Message::Reports(message) => match message {
+                Reports::Show => {
+                    return self.set_mode(Mode::Reports(Report::from_entries(
+                        &self.repair_contract_entries_buffer,
+                    )));
+                }
+                Reports::Export => {
+                    if let Mode::Reports(report) = &self.mode {
+                        let report = report.clone();
+                        let dir = self.settings.output_dir("raporty");
+                        return Command::perform(
+                            async move {
+                                let path = dir?.join(format!(
+                                    "raport-{}.csv",
+                                    crate::now().format("%Y-%m-%d_%H-%M-%S")
+                                ));
+                                tokio::task::block_in_place(|| report.write_csv(&path))?;
+                                Ok(path)
+                            }
+                            .map(Arc::new),
+                            |res| Message::Reports(Reports::Exported(res)),
+                        );
+                    }
+                }
+                Reports::Exported(res) => match res.as_ref() {
+                    Ok(path) => tracing::info!("wyeksportowano raport do {path:?}"),
+                    Err(e) => error!("{e:#?}"),
+                },
+            },
+            Message::History(action) => match &mut self.mode {
+                Mode::CreateNewRepairContract { form, session, .. } => {
+                    if !session.perform(action) {
+                        return Command::none();
+                    }
+                    match iced_forms::from_value(session.value()) {
+                        Ok(restored) => *form = restored,
+                        Err(e) => self.notice = Some(format!("niepoprawna wartość :: {e}")),
+                    }
+                }
+                Mode::Handover {
+                    protocol, session, ..
+                } => {
+                    if !session.perform(action) {
+                        return Command::none();
+                    }
+                    match iced_forms::from_value(session.value()) {
+                        Ok(restored) => *protocol = restored,
+                        Err(e) => self.notice = Some(format!("niepoprawna wartość :: {e}")),
+                    }
+                }
+                Mode::Settings {
+                    buffer: Ok(session),
+                    ..
+                } => {
+                    session.perform(action);
+                }
+                _ => {}
             },
         }
         Command::none()
     }
 
-    fn view(&self) -> Element<Self::Message> {
+    fn view(&self) -> Element<'_, Self::Message> {
         let title = Text::new("Archiwum Z")
             .width(iced::Length::Fill)
             .size(100)
@@ -189,37 +1898,74 @@ impl Application for ArchiwumZ {
         let navigation = Row::new()
             .push(iced::pure::button("Archiwum Z").on_press(Message::SwitchMode(Mode::Index)))
             .push(
-                iced::pure::button("Utwórz zlecenie").on_press(Message::SwitchMode({
-                    let form = RepairContract::default();
-                    Mode::CreateNewRepairContract {
-                        form: form.clone(),
-                        buffer: iced_forms::to_value(form),
-                    }
-                })),
-            )
+                iced::pure::button("Utwórz zlecenie").on_press(Message::NewContract))
             .push(
                 iced::pure::button("zlecenia").on_press(Message::SwitchMode(Mode::ViewingEntries)),
-            );
+            )
+            .push(iced::pure::button("raporty").on_press(Message::Reports(Reports::Show)))
+            .push(iced::pure::button("kosz").on_press(Message::Trash(Trash::Show)))
+            .push(iced::pure::button("kopie").on_press(Message::Backups(Backups::Show)))
+            .push(
+                iced::pure::button("import")
+                    .on_press(Message::SwitchMode(Mode::Import(Default::default()))),
+            )
+            .push(
+                iced::pure::button("ustawienia").on_press(Message::OpenSettings));
         let navbar = Row::new().push(title).push(navigation);
         let page: Element<'_, _> = match &self.mode {
-            Mode::Index => pages::index().into(),
-            Mode::ViewingEntries => {
-                pages::contracts_list(&self.repair_contract_entries_buffer).into()
-            }
-            Mode::CreateNewRepairContract { form, buffer } => {
-                pages::create_new_contract_form(&self.repair_contract_entries_buffer, form, buffer)
-                    .into()
+            Mode::Index => pages::index(&self.archive_error).into(),
+            Mode::ViewingEntries => pages::contracts_list(
+                &self.repair_contract_entries_buffer,
+                &self.contracts_list,
+                &self.contract_filter,
+                self.settings.values.contracts_sort,
+                self.contracts_page,
+            )
+            .into(),
+            Mode::CreateNewRepairContract {
+                form,
+                session,
+                signature,
+            } => pages::create_new_contract_form(form, session, signature).into(),
+            Mode::Handover {
+                entry,
+                protocol,
+                session,
+                signature,
+            } => pages::handover(entry, protocol, session, signature).into(),
+            Mode::Conflict(conflict) => pages::conflict(&conflict.mine, &conflict.theirs).into(),
+            Mode::DeleteContract { entry, reason } => pages::delete_contract(entry, reason).into(),
+            Mode::Trash(entries) => pages::trash(entries).into(),
+            Mode::Backups(page) => {
+                pages::backups(page, self.settings.backup_dir(&self.archive).ok()).into()
             }
+            Mode::Reports(report) => pages::reports(report).into(),
+            Mode::Import(wizard) => pages::import(wizard).into(),
+            Mode::Settings {
+                buffer,
+                schema,
+                new_archive,
+                error,
+            } => pages::settings(&self.settings, buffer, schema, new_archive, error).into(),
         };
 
-        let global_controls =
-            Row::new().push(Button::new("odśwież").on_press(Message::RefreshRepairContracts));
-        let content = Column::new()
+        let global_controls = Row::new()
+            .spacing(10)
+            .push(iced::pure::pick_list(
+                self.settings.archive_names(),
+                Some(self.archive.name.clone()),
+                Message::SwitchArchive,
+            ))
+            .push(Button::new("odśwież").on_press(Message::RefreshRepairContracts));
+        let mut content = Column::new()
             .max_width(800)
             .spacing(20)
             .align_items(Alignment::Center)
-            .push(Row::new().push(navbar).push(global_controls))
-            .push(page);
+            .push(Row::new().push(navbar).push(global_controls));
+        if let Some(notice) = &self.notice {
+            content = content.push(Text::new(notice));
+        }
+        let content = content.push(page);
         let app = Container::new(content)
             .width(Length::Fill)
             .center_x()
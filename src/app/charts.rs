@@ -0,0 +1,66 @@
+use iced::{
+    alignment,
+    pure::widget::canvas::{Canvas, Cursor, Frame, Geometry, Path, Program, Stroke, Text},
+    Color, Length, Point, Rectangle, Size,
+};
+
+const LABEL_HEIGHT: f32 = 20.;
+const BAR_COLOR: Color = Color::from_rgb(0.2, 0.4, 0.7);
+
+/// simple vertical bar chart, every bar is labeled with its key and value
+pub struct BarChart {
+    bars: Vec<(String, f32)>,
+}
+
+pub fn bar_chart<Message>(bars: Vec<(String, f32)>, height: u16) -> Canvas<Message, BarChart> {
+    Canvas::new(BarChart { bars })
+        .width(Length::Fill)
+        .height(Length::Units(height))
+}
+
+impl<Message> Program<Message> for BarChart {
+    type State = ();
+
+    fn draw(&self, _state: &(), bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(bounds.size());
+        let max = self.bars.iter().map(|(_, value)| *value).fold(0., f32::max);
+        let slot_width = bounds.width / self.bars.len().max(1) as f32;
+        let chart_height = bounds.height - 2. * LABEL_HEIGHT;
+        for (index, (label, value)) in self.bars.iter().enumerate() {
+            let bar_height = if max > 0. {
+                chart_height * value / max
+            } else {
+                0.
+            };
+            let x = index as f32 * slot_width;
+            let top = LABEL_HEIGHT + chart_height - bar_height;
+            frame.fill_rectangle(
+                Point::new(x + slot_width * 0.1, top),
+                Size::new(slot_width * 0.8, bar_height),
+                BAR_COLOR,
+            );
+            frame.fill_text(Text {
+                content: format!("{value:.0}"),
+                position: Point::new(x + slot_width / 2., top),
+                horizontal_alignment: alignment::Horizontal::Center,
+                vertical_alignment: alignment::Vertical::Bottom,
+                ..Default::default()
+            });
+            frame.fill_text(Text {
+                content: label.clone(),
+                position: Point::new(x + slot_width / 2., bounds.height),
+                horizontal_alignment: alignment::Horizontal::Center,
+                vertical_alignment: alignment::Vertical::Bottom,
+                ..Default::default()
+            });
+        }
+        frame.stroke(
+            &Path::line(
+                Point::new(0., LABEL_HEIGHT + chart_height),
+                Point::new(bounds.width, LABEL_HEIGHT + chart_height),
+            ),
+            Stroke::default(),
+        );
+        vec![frame.into_geometry()]
+    }
+}
@@ -0,0 +1,119 @@
+use iced::{
+    mouse,
+    pure::widget::canvas::{
+        event::{self, Event},
+        Canvas, Cursor, Frame, Geometry, Path, Program, Stroke,
+    },
+    Color, Length, Point, Rectangle, Size,
+};
+
+use crate::models::signature::{Signature, SignatureStroke, PAD_SIZE};
+
+pub const WIDTH: u16 = PAD_SIZE.0;
+pub const HEIGHT: u16 = PAD_SIZE.1;
+
+/// canvas on which the customer signs with the mouse or a tablet pen,
+/// every finished stroke is reported through `on_stroke`
+pub struct SignaturePad<'a, F> {
+    signature: &'a Signature,
+    on_stroke: F,
+}
+
+pub fn signature_pad<'a, Message, F>(
+    signature: &'a Signature,
+    on_stroke: F,
+) -> Canvas<Message, SignaturePad<'a, F>>
+where
+    F: Fn(SignatureStroke) -> Message,
+{
+    Canvas::new(SignaturePad {
+        signature,
+        on_stroke,
+    })
+    .width(Length::Units(WIDTH))
+    .height(Length::Units(HEIGHT))
+}
+
+fn stroke_path(stroke: &[(f32, f32)]) -> Option<Path> {
+    let ((x, y), rest) = stroke.split_first()?;
+    Some(Path::new(|builder| {
+        builder.move_to(Point::new(*x, *y));
+        rest.iter()
+            .for_each(|(x, y)| builder.line_to(Point::new(*x, *y)));
+    }))
+}
+
+impl<'a, Message, F> Program<Message> for SignaturePad<'a, F>
+where
+    F: Fn(SignatureStroke) -> Message,
+{
+    /// stroke that is currently being drawn
+    type State = Option<SignatureStroke>;
+
+    fn update(
+        &self,
+        state: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> (event::Status, Option<Message>) {
+        let position = cursor.position_in(&bounds).map(|p| (p.x, p.y));
+        match (event, position) {
+            (Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)), Some(position)) => {
+                *state = Some(vec![position]);
+                (event::Status::Captured, None)
+            }
+            (Event::Mouse(mouse::Event::CursorMoved { .. }), Some(position)) => match state {
+                Some(stroke) => {
+                    stroke.push(position);
+                    (event::Status::Captured, None)
+                }
+                None => (event::Status::Ignored, None),
+            },
+            (Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)), _) => {
+                match state.take() {
+                    Some(stroke) => (event::Status::Captured, Some((self.on_stroke)(stroke))),
+                    None => (event::Status::Ignored, None),
+                }
+            }
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(&self, state: &Self::State, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(bounds.size());
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), Color::WHITE);
+        frame.stroke(
+            &Path::rectangle(
+                Point::ORIGIN,
+                Size::new(bounds.width - 1., bounds.height - 1.),
+            ),
+            Stroke::default().with_color(Color::from_rgb(0.6, 0.6, 0.6)),
+        );
+        self.signature
+            .strokes
+            .iter()
+            .chain(state.iter())
+            .filter_map(|stroke| stroke_path(stroke))
+            .for_each(|path| {
+                frame.stroke(
+                    &path,
+                    Stroke::default().with_color(Color::BLACK).with_width(2.),
+                )
+            });
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        _state: &Self::State,
+        bounds: Rectangle,
+        cursor: Cursor,
+    ) -> mouse::Interaction {
+        if cursor.is_over(&bounds) {
+            mouse::Interaction::Crosshair
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    fs::File,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+use tracing::{
+    info,
+    instrument,
+};
+use zip::{
+    write::SimpleFileOptions,
+    CompressionMethod,
+    ZipArchive,
+    ZipWriter,
+};
+
+use crate::{
+    db::RepairContractEntry,
+    settings::BackupSettings,
+};
+
+const BACKUP_PREFIX: &str = "archiwum-";
+const BACKUP_EXTENSION: &str = "zip";
+
+/// every file of the archive, including the trash, without the lease files
+fn archive_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir).with_context(|| format!("odczytywanie folderu {dir:?}"))? {
+        let path = entry
+            .with_context(|| format!("odczytywanie folderu {dir:?}"))?
+            .path();
+        if path.is_dir() {
+            files.extend(archive_files(&path)?);
+        } else if path.extension().and_then(|extension| extension.to_str()) != Some("lock") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// backups kept inside the archive would be zipped into every later backup and removed by
+/// a restore, which replaces the whole archive folder
+fn refuse_target_inside(archive_dir: &Path, target_dir: &Path) -> Result<()> {
+    // the backup folder may not exist yet, its nearest existing parent is resolved instead
+    let resolved = |path: &Path| -> Result<PathBuf> {
+        let absolute = std::path::absolute(path)
+            .with_context(|| format!("odczytywanie położenia {path:?}"))?;
+        let resolved = absolute
+            .ancestors()
+            .find_map(|ancestor| {
+                let canonical = std::fs::canonicalize(ancestor).ok()?;
+                Some(canonical.join(absolute.strip_prefix(ancestor).ok()?))
+            })
+            .unwrap_or(absolute);
+        Ok(resolved)
+    };
+    if resolved(target_dir)?.starts_with(resolved(archive_dir)?) {
+        bail!("folder kopii {target_dir:?} nie może leżeć w folderze archiwum {archive_dir:?}");
+    }
+    Ok(())
+}
+
+/// a new backup file named after the current time; a name already taken (e.g. by the backup
+/// made just before a restore) moves the time a millisecond on, keeping the names in order
+fn new_backup_file(target_dir: &Path) -> Result<(PathBuf, File)> {
+    let mut time = crate::now();
+    loop {
+        let path = target_dir.join(format!(
+            "{BACKUP_PREFIX}{}.{BACKUP_EXTENSION}",
+            time.format("%Y-%m-%d_%H-%M-%S-%3f")
+        ));
+        match File::options().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                time += chrono::Duration::milliseconds(1)
+            }
+            Err(e) => return Err(e).with_context(|| format!("tworzenie kopii {path:?}")),
+        }
+    }
+}
+
+/// zips the whole archive into `target_dir`, the file name carries the timestamp
+#[instrument]
+pub fn create_backup(archive_dir: &Path, target_dir: &Path) -> Result<PathBuf> {
+    refuse_target_inside(archive_dir, target_dir)?;
+    std::fs::create_dir_all(target_dir)
+        .with_context(|| format!("tworzenie folderu kopii {target_dir:?}"))?;
+    let (path, file) = new_backup_file(target_dir)?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for file in archive_files(archive_dir)? {
+        let name = file
+            .strip_prefix(archive_dir)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        zip.start_file(name, options)?;
+        std::io::copy(
+            &mut File::open(&file).with_context(|| format!("odczytywanie {file:?}"))?,
+            &mut zip,
+        )
+        .with_context(|| format!("kopiowanie {file:?}"))?;
+    }
+    zip.finish()
+        .with_context(|| format!("zapisywanie kopii {path:?}"))?;
+    info!("utworzono kopię zapasową {path:?}");
+    Ok(path)
+}
+
+/// backups in `target_dir`, newest first
+pub fn list_backups(target_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !target_dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut backups = std::fs::read_dir(target_dir)
+        .with_context(|| format!("odczytywanie folderu kopii {target_dir:?}"))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("odczytywanie folderu kopii {target_dir:?}"))?;
+    backups.retain(|path| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| {
+                name.starts_with(BACKUP_PREFIX) && name.ends_with(&format!(".{BACKUP_EXTENSION}"))
+            })
+            .unwrap_or_default()
+    });
+    // the timestamp in the name sorts chronologically
+    backups.sort_by(|a, b| b.cmp(a));
+    Ok(backups)
+}
+
+/// removes all but the `keep` newest backups, returns the removed ones
+pub fn apply_retention(target_dir: &Path, keep: usize) -> Result<Vec<PathBuf>> {
+    let removed = list_backups(target_dir)?
+        .into_iter()
+        .skip(keep.max(1))
+        .collect::<Vec<_>>();
+    for path in &removed {
+        std::fs::remove_file(path).with_context(|| format!("usuwanie starej kopii {path:?}"))?;
+    }
+    Ok(removed)
+}
+
+/// true when there is no backup younger than `interval_hours`
+pub fn is_due(target_dir: &Path, interval_hours: u64) -> Result<bool> {
+    let newest = match list_backups(target_dir)?.into_iter().next() {
+        Some(newest) => newest,
+        None => return Ok(true),
+    };
+    let modified = std::fs::metadata(&newest)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("odczytywanie daty kopii {newest:?}"))?;
+    let age = SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or_default();
+    Ok(age >= Duration::from_secs(interval_hours * 60 * 60))
+}
+
+/// backup made by the scheduler: only when due, old backups are pruned afterwards
+pub fn scheduled_backup(
+    settings: &BackupSettings,
+    archive_dir: &Path,
+    target_dir: &Path,
+) -> Result<Option<PathBuf>> {
+    if !settings.enabled || !is_due(target_dir, settings.interval_hours)? {
+        return Ok(None);
+    }
+    let backup = create_backup(archive_dir, target_dir)?;
+    apply_retention(target_dir, settings.keep)?;
+    Ok(Some(backup))
+}
+
+/// result of reading every contract of a backup
+#[derive(Debug, Clone, Default)]
+pub struct BackupCheck {
+    pub contracts: usize,
+    pub problems: Vec<(PathBuf, String)>,
+}
+
+impl BackupCheck {
+    /// why the backup must not replace the archive, `None` when it can
+    pub fn refusal(&self) -> Option<String> {
+        match (self.contracts, self.problems.len()) {
+            (_, problems) if problems > 0 => {
+                Some(format!("kopia zawiera {problems} nieczytelnych zleceń"))
+            }
+            (0, _) => Some("kopia nie zawiera żadnych zleceń".to_owned()),
+            _ => None,
+        }
+    }
+}
+
+/// the backup is unpacked next to the archive, so that the final swap is a rename
+fn staging_dir(archive_dir: &Path) -> PathBuf {
+    let mut name = archive_dir.file_name().unwrap_or_default().to_owned();
+    name.push(".przywracanie");
+    archive_dir.with_file_name(name)
+}
+
+async fn unpack_and_check(backup: &Path, staging: &Path) -> Result<BackupCheck> {
+    tokio::task::block_in_place(|| {
+        if staging.exists() {
+            std::fs::remove_dir_all(staging)
+                .with_context(|| format!("usuwanie folderu {staging:?}"))?;
+        }
+        File::open(backup)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| Ok(ZipArchive::new(file)?))
+            .and_then(|mut zip| Ok(zip.extract(staging)?))
+            .with_context(|| format!("rozpakowywanie kopii {backup:?}"))
+    })?;
+    let mut check = BackupCheck::default();
+    let files = tokio::task::block_in_place(|| archive_files(staging))?;
+    for file in files
+        .into_iter()
+        .filter(|file| file.extension().and_then(|extension| extension.to_str()) == Some("toml"))
+    {
+        match RepairContractEntry::from_path(&file).await {
+            Ok(_) => check.contracts += 1,
+            Err(e) => check.problems.push((file, format!("{e:#}"))),
+        }
+    }
+    Ok(check)
+}
+
+/// unpacks the backup to a temporary folder and reads every contract in it
+#[instrument]
+pub async fn check_backup(backup: &Path, archive_dir: &Path) -> Result<BackupCheck> {
+    let staging = staging_dir(archive_dir);
+    let check = unpack_and_check(backup, &staging).await;
+    if staging.exists() {
+        tokio::fs::remove_dir_all(&staging)
+            .await
+            .with_context(|| format!("usuwanie folderu {staging:?}"))?;
+    }
+    check
+}
+
+/// replaces the archive with the backup, but only when it has contracts and every one of them
+/// can be read; the current archive is backed up to `target_dir` first, that backup's path
+/// is returned
+#[instrument]
+pub async fn restore_backup(
+    backup: &Path,
+    archive_dir: &Path,
+    target_dir: &Path,
+) -> Result<PathBuf> {
+    refuse_target_inside(archive_dir, target_dir)?;
+    let staging = staging_dir(archive_dir);
+    let check = unpack_and_check(backup, &staging).await?;
+    if let Some(refusal) = check.refusal() {
+        tokio::fs::remove_dir_all(&staging)
+            .await
+            .with_context(|| format!("usuwanie folderu {staging:?}"))?;
+        bail!("{refusal} ({backup:?}), archiwum nie zostało zmienione");
+    }
+    tokio::task::block_in_place(|| {
+        let safety_backup = create_backup(archive_dir, target_dir)?;
+        let mut name = archive_dir.file_name().unwrap_or_default().to_owned();
+        name.push(".zastąpione");
+        let replaced = archive_dir.with_file_name(name);
+        std::fs::rename(archive_dir, &replaced)
+            .with_context(|| format!("przenoszenie {archive_dir:?} do {replaced:?}"))?;
+        std::fs::rename(&staging, archive_dir)
+            .with_context(|| format!("przenoszenie {staging:?} do {archive_dir:?}"))?;
+        std::fs::remove_dir_all(&replaced).with_context(|| format!("usuwanie {replaced:?}"))?;
+        info!("przywrócono archiwum {archive_dir:?} z kopii {backup:?}");
+        Ok(safety_backup)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::Database,
+        testing::TempDir,
+    };
+
+    fn contract_files(dir: &Path) -> usize {
+        archive_files(dir)
+            .unwrap()
+            .iter()
+            .filter(|file| {
+                file.extension().and_then(|extension| extension.to_str()) == Some("toml")
+            })
+            .count()
+    }
+
+    #[test]
+    fn names_and_retention() {
+        let root = TempDir::default();
+        let (archive_dir, target_dir) = (root.path().join("archiwum"), root.path().join("kopie"));
+        std::fs::create_dir_all(&archive_dir).unwrap();
+        // made within the same second, e.g. a scheduled backup and the one taken before a restore
+        let created = (0..4)
+            .map(|_| create_backup(&archive_dir, &target_dir).unwrap())
+            .collect::<Vec<_>>();
+        let mut newest_first = created.clone();
+        newest_first.reverse();
+        assert_eq!(list_backups(&target_dir).unwrap(), newest_first);
+
+        assert_eq!(apply_retention(&target_dir, 2).unwrap(), newest_first[2..]);
+        assert_eq!(list_backups(&target_dir).unwrap(), newest_first[..2]);
+        // the newest backup is always kept
+        assert_eq!(apply_retention(&target_dir, 0).unwrap(), newest_first[1..2]);
+        assert_eq!(list_backups(&target_dir).unwrap(), newest_first[..1]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn backup_dir_inside_archive_is_refused() {
+        let root = TempDir::default();
+        let archive_dir = root.path().join("archiwum");
+        let db = Database::new(archive_dir.clone());
+        db.create_entry(Default::default()).await.unwrap();
+        let backup = create_backup(&archive_dir, &root.path().join("kopie")).unwrap();
+        // next to the archive is fine, even when written through it
+        create_backup(&archive_dir, &archive_dir.join("../kopie")).unwrap();
+
+        for target_dir in [archive_dir.join("kopie"), archive_dir.join(".././archiwum/kopie")] {
+            assert!(create_backup(&archive_dir, &target_dir).is_err());
+            assert!(restore_backup(&backup, &archive_dir, &target_dir).await.is_err());
+        }
+        assert!(!archive_dir.join("kopie").exists());
+        assert_eq!(contract_files(&archive_dir), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn restore() {
+        let root = TempDir::default();
+        let (archive_dir, target_dir) = (root.path().join("archiwum"), root.path().join("kopie"));
+        let db = Database::new(archive_dir.clone());
+        let empty = create_backup(&archive_dir, &target_dir).unwrap();
+        db.create_entry(Default::default()).await.unwrap();
+        let trashed = db.create_entry(Default::default()).await.unwrap();
+        db.delete_entry(&trashed, "pomyłka").await.unwrap();
+        let backup = create_backup(&archive_dir, &target_dir).unwrap();
+        db.create_entry(Default::default()).await.unwrap();
+
+        let check = check_backup(&empty, &archive_dir).await.unwrap();
+        assert_eq!(check.refusal().as_deref(), Some("kopia nie zawiera żadnych zleceń"));
+        assert!(restore_backup(&empty, &archive_dir, &target_dir).await.is_err());
+        assert_eq!(contract_files(&archive_dir), 3);
+
+        let check = check_backup(&backup, &archive_dir).await.unwrap();
+        assert_eq!((check.contracts, check.refusal()), (2, None));
+        let safety_backup = restore_backup(&backup, &archive_dir, &target_dir)
+            .await
+            .unwrap();
+        assert_eq!(db.get_entries().await.unwrap().len(), 1);
+        assert_eq!(db.get_trash().await.unwrap().len(), 1);
+        assert_eq!(list_backups(&target_dir).unwrap()[0], safety_backup);
+        let check = check_backup(&safety_backup, &archive_dir).await.unwrap();
+        assert_eq!(check.contracts, 3);
+        assert!(!staging_dir(&archive_dir).exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unreadable_backup_is_refused() {
+        let root = TempDir::default();
+        let (archive_dir, target_dir) = (root.path().join("archiwum"), root.path().join("kopie"));
+        let db = Database::new(archive_dir.clone());
+        db.create_entry(Default::default()).await.unwrap();
+        std::fs::write(archive_dir.join("zepsute.repair-contract.toml"), "id = [").unwrap();
+        let backup = create_backup(&archive_dir, &target_dir).unwrap();
+        std::fs::remove_file(archive_dir.join("zepsute.repair-contract.toml")).unwrap();
+
+        let check = check_backup(&backup, &archive_dir).await.unwrap();
+        assert_eq!((check.contracts, check.problems.len()), (1, 1));
+        assert!(restore_backup(&backup, &archive_dir, &target_dir).await.is_err());
+        assert_eq!(db.get_entries().await.unwrap().len(), 1);
+        // refused before the safety backup
+        assert_eq!(list_backups(&target_dir).unwrap(), [backup]);
+    }
+}
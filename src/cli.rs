@@ -0,0 +1,483 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use chrono::NaiveDate;
+use clap::{
+    Parser,
+    Subcommand,
+    ValueEnum,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    db::{
+        Database,
+        RepairContractEntry,
+    },
+    export::{
+        ContractFilter,
+        ExportFormat,
+    },
+    models::{
+        ContractStatus,
+        RepairContract,
+    },
+    settings::{
+        Settings,
+        SettingsOverrides,
+    },
+};
+
+/// Archiwum Z - archiwum zleceń serwisowych; bez polecenia uruchamia aplikację okienkową
+#[derive(Parser, Debug)]
+#[clap(name = "archiwumz", version)]
+pub struct Cli {
+    /// plik ustawień, domyślnie `settings.toml` w folderze konfiguracji użytkownika
+    #[clap(long, global = true, env = "ARCHIWUMZ_SETTINGS")]
+    pub settings: Option<PathBuf>,
+    /// nazwa jednego z archiwów z ustawień
+    #[clap(
+        long,
+        global = true,
+        env = "ARCHIWUMZ_ARCHIVE",
+        conflicts_with = "archive-dir"
+    )]
+    pub archive: Option<String>,
+    /// folder archiwum, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_ARCHIVE_DIR")]
+    pub archive_dir: Option<PathBuf>,
+    /// folder logów, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_LOGS_DIR")]
+    pub logs_dir: Option<PathBuf>,
+    /// nazwa firmy na wydrukach, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_COMPANY_NAME")]
+    pub company_name: Option<String>,
+    /// adres firmy na wydrukach, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_COMPANY_ADDRESS")]
+    pub company_address: Option<String>,
+    /// NIP firmy na wydrukach, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_COMPANY_TAX_NUMBER")]
+    pub company_tax_number: Option<String>,
+    /// telefon firmy na wydrukach, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_COMPANY_PHONE")]
+    pub company_phone: Option<String>,
+    /// e-mail firmy na wydrukach, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_COMPANY_EMAIL")]
+    pub company_email: Option<String>,
+    /// cena wstępna nowych zleceń, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_PROGNOSIS_PRICE")]
+    pub prognosis_price: Option<Decimal>,
+    /// przewidywany czas naprawy nowych zleceń w dniach roboczych, nadpisuje ustawienia
+    #[clap(long, global = true, env = "ARCHIWUMZ_EXPECTED_REPAIR_DAYS")]
+    pub expected_repair_days: Option<i64>,
+    #[clap(subcommand)]
+    pub command: Option<CliCommand>,
+}
+
+impl Cli {
+    pub fn settings(&self) -> Result<Settings> {
+        Settings::load(
+            self.settings.clone(),
+            SettingsOverrides {
+                archive: self.archive.clone(),
+                archive_dir: self.archive_dir.clone(),
+                logs_dir: self.logs_dir.clone(),
+                company_name: self.company_name.clone(),
+                company_address: self.company_address.clone(),
+                company_tax_number: self.company_tax_number.clone(),
+                company_phone: self.company_phone.clone(),
+                company_email: self.company_email.clone(),
+                prognosis_price: self.prognosis_price,
+                expected_repair_time_work_days: self.expected_repair_days,
+            },
+        )
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum FormatArg {
+    Csv,
+    Xlsx,
+}
+
+impl From<FormatArg> for ExportFormat {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Csv => ExportFormat::Csv,
+            FormatArg::Xlsx => ExportFormat::Xlsx,
+        }
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum StatusArg {
+    Open,
+    Closed,
+}
+
+impl From<StatusArg> for ContractStatus {
+    fn from(status: StatusArg) -> Self {
+        match status {
+            StatusArg::Open => ContractStatus::Open,
+            StatusArg::Closed => ContractStatus::Closed,
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum MigrationDirection {
+    /// z aktualnego archiwum do bazy SQLite
+    ToSqlite,
+    /// z bazy SQLite do aktualnego archiwum
+    FromSqlite,
+}
+
+/// filters shared by the commands working on a list of contracts
+#[derive(clap::Args, Debug, Clone)]
+pub struct FilterArgs {
+    /// fragment nazwy klienta, telefonu, NIP, modelu, numeru seryjnego albo id
+    #[clap(long)]
+    pub query: Option<String>,
+    #[clap(long, value_enum)]
+    pub status: Option<StatusArg>,
+    /// data przyjęcia od (RRRR-MM-DD)
+    #[clap(long)]
+    pub from: Option<NaiveDate>,
+    /// data przyjęcia do (RRRR-MM-DD)
+    #[clap(long)]
+    pub to: Option<NaiveDate>,
+    /// pokazuje także zarchiwizowane zlecenia
+    #[clap(long)]
+    pub archived: bool,
+}
+
+impl From<FilterArgs> for ContractFilter {
+    fn from(args: FilterArgs) -> Self {
+        Self {
+            query: args.query.unwrap_or_default(),
+            status: args.status.map(Into::into),
+            from: args.from,
+            to: args.to,
+            include_archived: args.archived,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// lista zleceń, jedno na linię: id, data, status, klient, urządzenie
+    List {
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// wypisuje zlecenie w formacie TOML
+    Show {
+        /// id zlecenia albo jego początek
+        id: String,
+    },
+    /// zapisuje zlecenie z podpisami klienta jako dokument HTML do wydruku
+    Print {
+        /// id zlecenia albo jego początek
+        id: String,
+        /// plik docelowy, domyślnie folder `wydruki` obok aplikacji
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// dodaje zlecenie z pliku TOML do archiwum
+    Create {
+        #[clap(long)]
+        from: PathBuf,
+    },
+    /// sprawdza czy wszystkie pliki archiwum dają się odczytać
+    Validate,
+    /// zapisuje wszystkie zlecenia ponownie w aktualnym formacie i pod kanonicznymi nazwami plików
+    Reindex {
+        /// tylko wypisuje co zostałoby zmienione
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// archiwizuje zlecenia zamknięte dawniej niż podana liczba lat temu
+    ArchiveOld {
+        #[clap(long, default_value = "2")]
+        years: u32,
+    },
+    /// tworzy kopię zapasową archiwum i usuwa najstarsze ponad limit z ustawień
+    Backup,
+    /// zastępuje archiwum kopią zapasową, o ile wszystkie zlecenia w niej dają się odczytać
+    Restore {
+        /// plik kopii, `list` wypisuje dostępne kopie
+        backup: Option<PathBuf>,
+        /// tylko sprawdza kopię
+        #[clap(long)]
+        check: bool,
+    },
+    /// lista archiwów z ustawień, aktualne oznaczone gwiazdką
+    Archives,
+    /// kopiuje zlecenia między archiwum w folderze a bazą SQLite, pomija już istniejące
+    #[cfg(feature = "sqlite")]
+    Migrate {
+        #[clap(value_enum)]
+        direction: MigrationDirection,
+        /// plik bazy SQLite
+        database: PathBuf,
+    },
+    /// przepisuje zlecenia zapisane w starszej wersji formatu, wcześniej tworzy kopię zapasową
+    Upgrade {
+        /// tylko wypisuje pliki do aktualizacji
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// eksport zleceń do arkusza kalkulacyjnego
+    Export {
+        #[clap(long, value_enum, default_value = "csv")]
+        format: FormatArg,
+        /// plik docelowy, domyślnie folder `eksport` obok aplikacji
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+}
+
+/// the one contract whose id starts with `id`
+fn find_by_id<'a>(
+    entries: &'a [RepairContractEntry],
+    id: &str,
+) -> Result<&'a RepairContractEntry> {
+    let mut matching = entries
+        .iter()
+        .filter(|entry| entry.model.id.to_string().starts_with(id));
+    match (matching.next(), matching.next()) {
+        (Some(entry), None) => Ok(entry),
+        (None, _) => anyhow::bail!("nie znaleziono zlecenia [{id}]"),
+        (Some(_), Some(_)) => anyhow::bail!("[{id}] pasuje do wielu zleceń"),
+    }
+}
+
+pub fn run(settings: Settings, command: CliCommand) -> Result<()> {
+    if let CliCommand::Archives = command {
+        let current = settings.current_archive()?;
+        for archive in settings.archives()? {
+            let marker = if archive == current { "*" } else { " " };
+            println!("{marker} {}\t{}", archive.name, archive.dir.display());
+        }
+        return Ok(());
+    }
+    let runtime = tokio::runtime::Runtime::new().context("uruchamianie środowiska tokio")?;
+    let archive = settings.current_archive()?;
+    let archive_dir = archive.dir.clone();
+    runtime.block_on(async move {
+        let folder = || crate::storage::open_folder(&archive_dir);
+        let db = crate::storage::open(&archive_dir)?;
+        match command {
+            CliCommand::List { filter } => {
+                let filter = ContractFilter::from(filter);
+                let mut entries = db.get_entries().await?;
+                entries.sort_by_key(|entry| entry.model.date);
+                for entry in filter.apply(&entries) {
+                    let model = &entry.model;
+                    println!(
+                        "{}\t{}\t{}\t{}\t{} {}",
+                        model.id,
+                        model.date,
+                        model.status(),
+                        model.info.customer.name(),
+                        model.info.device.model_name,
+                        model.info.device.serial_number,
+                    );
+                }
+                Ok(())
+            }
+            CliCommand::Show { id } => {
+                let entries = db.get_entries().await?;
+                let entry = find_by_id(&entries, &id)?;
+                println!("# {}", entry.path.display());
+                print!("{}", entry.model.to_toml()?);
+                Ok(())
+            }
+            CliCommand::Print { id, output } => {
+                let entries = db.get_entries().await?;
+                let model = &find_by_id(&entries, &id)?.model;
+                let output = match output {
+                    Some(output) => output,
+                    None => crate::export::default_print_path(&settings, model)?,
+                };
+                let company = settings.company();
+                tokio::task::block_in_place(|| {
+                    crate::export::print_contract(model, &company, &output)
+                })?;
+                println!("{}", output.display());
+                Ok(())
+            }
+            CliCommand::Create { from } => {
+                let model = tokio::task::block_in_place(|| RepairContract::from_file(&from))?;
+                let entry = db.create_entry(model).await?;
+                println!("{}", entry.path.display());
+                Ok(())
+            }
+            CliCommand::Validate => {
+                let scanned = folder()?.scan_entries().await?;
+                let mut ids = HashMap::new();
+                let mut problems = 0;
+                for (path, result) in &scanned {
+                    match result {
+                        Ok(entry) => {
+                            if let Some(other) = ids.insert(entry.model.id, path) {
+                                problems += 1;
+                                println!("{}: to samo id co w {}", path.display(), other.display());
+                            }
+                        }
+                        Err(e) => {
+                            problems += 1;
+                            println!("{}: {e:#}", path.display());
+                        }
+                    }
+                }
+                match problems {
+                    0 => {
+                        println!("sprawdzono {} zleceń, wszystkie poprawne", scanned.len());
+                        Ok(())
+                    }
+                    problems => anyhow::bail!(
+                        "znaleziono {problems} problemów w {} plikach",
+                        scanned.len()
+                    ),
+                }
+            }
+            CliCommand::Reindex { dry_run } => {
+                let db = folder()?;
+                for entry in db.get_entries().await? {
+                    let canonical = entry.path.with_file_name(Database::file_name(&entry.model));
+                    if dry_run {
+                        println!("{} -> {}", entry.path.display(), canonical.display());
+                        continue;
+                    }
+                    let from = entry.path.clone();
+                    let to = db.reindex_entry(entry).await?;
+                    println!("{} -> {}", from.display(), to.display());
+                }
+                Ok(())
+            }
+            CliCommand::Export {
+                format,
+                output,
+                filter,
+            } => {
+                let format = ExportFormat::from(format);
+                let filter = ContractFilter::from(filter);
+                let output = match output {
+                    Some(output) => output,
+                    None => crate::export::default_export_path(&settings, format)?,
+                };
+                let entries = db.get_entries().await?;
+                let written = tokio::task::block_in_place(|| {
+                    crate::export::export_contracts(&filter.apply(&entries), format, &output)
+                })?;
+                written
+                    .iter()
+                    .for_each(|path| println!("{}", path.display()));
+                Ok(())
+            }
+            CliCommand::ArchiveOld { years } => {
+                let before = crate::now() - chrono::Duration::days(365 * years as i64);
+                let archived = folder()?.archive_closed_before(before).await?;
+                println!("zarchiwizowano {archived} zleceń zamkniętych przed {before}");
+                Ok(())
+            }
+            CliCommand::Backup => {
+                let archive_dir = folder()?.base_dir().await;
+                let target_dir = settings.backup_dir(&archive)?;
+                let backup = tokio::task::block_in_place(|| {
+                    crate::backup::create_backup(&archive_dir, &target_dir)
+                })?;
+                println!("{}", backup.display());
+                for removed in tokio::task::block_in_place(|| {
+                    crate::backup::apply_retention(&target_dir, settings.values.backup.keep)
+                })? {
+                    println!("usunięto {}", removed.display());
+                }
+                Ok(())
+            }
+            CliCommand::Restore { backup, check } => {
+                let archive_dir = folder()?.base_dir().await;
+                let target_dir = settings.backup_dir(&archive)?;
+                let backup = match backup {
+                    Some(backup) => backup,
+                    None => {
+                        for backup in crate::backup::list_backups(&target_dir)? {
+                            println!("{}", backup.display());
+                        }
+                        return Ok(());
+                    }
+                };
+                match check {
+                    true => {
+                        let check = crate::backup::check_backup(&backup, &archive_dir).await?;
+                        for (path, e) in &check.problems {
+                            println!("{}: {e}", path.display());
+                        }
+                        println!(
+                            "zleceń: {}, nieczytelnych: {}",
+                            check.contracts,
+                            check.problems.len()
+                        );
+                    }
+                    false => {
+                        let safety_backup =
+                            crate::backup::restore_backup(&backup, &archive_dir, &target_dir)
+                                .await?;
+                        println!(
+                            "przywrócono, poprzedni stan zapisano w {}",
+                            safety_backup.display()
+                        );
+                    }
+                }
+                Ok(())
+            }
+            CliCommand::Upgrade { dry_run } => {
+                let target_dir = settings.backup_dir(&archive)?;
+                let (outdated, backup) = folder()?.upgrade_with_backup(&target_dir, dry_run).await?;
+                if let Some(backup) = backup {
+                    println!("kopia zapasowa: {}", backup.display());
+                }
+                for path in &outdated {
+                    println!("{}", path.display());
+                }
+                println!(
+                    "zleceń w starszej wersji formatu: {} (aktualna wersja {})",
+                    outdated.len(),
+                    crate::migrations::SCHEMA_VERSION
+                );
+                Ok(())
+            }
+            CliCommand::Archives => unreachable!("obsłużone przed otwarciem archiwum"),
+            #[cfg(feature = "sqlite")]
+            CliCommand::Migrate {
+                direction,
+                database,
+            } => {
+                let database = crate::storage::sqlite::SqliteStorage::open(&database)?;
+                let summary = match direction {
+                    MigrationDirection::ToSqlite => {
+                        crate::storage::migrate(db.as_ref(), &database).await?
+                    }
+                    MigrationDirection::FromSqlite => {
+                        crate::storage::migrate(&database, db.as_ref()).await?
+                    }
+                };
+                println!(
+                    "skopiowano {} zleceń, pominięto {} już istniejących",
+                    summary.copied, summary.skipped
+                );
+                Ok(())
+            }
+        }
+    })
+}
@@ -13,7 +13,9 @@ use std::path::{
     PathBuf,
 };
 use std::sync::Arc;
+use thiserror::Error;
 use tokio::fs::DirEntry;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::RwLock;
 
 use anyhow::Result;
@@ -33,8 +35,9 @@ impl FillForm for crate::AppTime {
         self.to_string()
     }
 
+    /// also takes the Polish `18.10.2026 14:30`
     fn deserialize(val: &str) -> Result<Self> {
-        val.parse().context("zła data")
+        iced_forms::date_picker::parse_date_time(val).context("zła data")
     }
 }
 
@@ -48,6 +51,66 @@ impl FillForm for String {
     }
 }
 
+const TRASH_DIR_NAME: &str = "trash";
+
+/// leases older than that are considered abandoned (e.g. the app crashed)
+const LEASE_TIMEOUT_MINUTES: i64 = 30;
+/// how often a held lease is renewed, well within [`LEASE_TIMEOUT_MINUTES`]
+pub const LEASE_REFRESH_INTERVAL: std::time::Duration =
+    std::time::Duration::from_secs(LEASE_TIMEOUT_MINUTES as u64 * 60 / 3);
+/// a takeover marker this old was left behind by a crash, taking over takes milliseconds
+const TAKEOVER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// contents of a `*.lock` file placed next to a contract that is being edited
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Lease {
+    pub holder: String,
+    pub acquired: crate::AppTime,
+}
+
+impl Lease {
+    pub fn ours() -> Self {
+        Self {
+            holder: lease_holder(),
+            acquired: crate::now(),
+        }
+    }
+
+    pub fn is_stale(&self) -> bool {
+        (crate::now() - self.acquired).num_minutes() >= LEASE_TIMEOUT_MINUTES
+    }
+}
+
+/// `user@computer (proces 1234)`, tells apart the machines sharing an archive and the app
+/// instances running on one of them
+fn lease_holder() -> String {
+    let var = |names: [&str; 2]| {
+        names
+            .iter()
+            .find_map(|name| std::env::var(name).ok())
+            .unwrap_or_default()
+    };
+    format!(
+        "{}@{} (proces {})",
+        var(["USERNAME", "USER"]),
+        var(["COMPUTERNAME", "HOSTNAME"]),
+        std::process::id()
+    )
+}
+
+/// the contract on disk has a different revision than the one being saved
+#[derive(Error, Debug, Clone)]
+#[error(
+    "zlecenie {:?} zostało zmienione przez kogoś innego (wersja {}, edytowana {})",
+    theirs.path,
+    theirs.model.revision,
+    mine.model.revision
+)]
+pub struct Conflict {
+    pub mine: RepairContractEntry,
+    pub theirs: RepairContractEntry,
+}
+
 #[derive(Debug, Clone)]
 struct Db {
     pub base_dir: PathBuf,
@@ -89,7 +152,8 @@ impl RepairContractEntry {
             .await
             .with_context(|| format!("odczytywanie zawartości zlecenia z {path:?}"))?;
         let entry = tokio::task::block_in_place(|| {
-            toml::from_str(&content)
+            content
+                .parse::<RepairContract>()
                 .with_context(|| format!("parsowanie zawartości pliku {path:?} :: {content}"))
                 .map(|model| Self {
                     path: path.into(),
@@ -112,17 +176,48 @@ impl Database {
         }
     }
 
-    #[instrument(skip(self))]
-    pub async fn get_entries(&self) -> Result<Vec<RepairContractEntry>> {
-        info!("getting entries");
-        let db = self.db.write().await;
-        let base_dir = db.base_dir.clone();
-        let dir_entries: Vec<_> = tokio::fs::read_dir(&base_dir)
+    pub async fn base_dir(&self) -> PathBuf {
+        self.db.read().await.base_dir.clone()
+    }
+
+    async fn dir_entries(base_dir: &Path) -> Result<Vec<DirEntry>> {
+        tokio::fs::read_dir(base_dir)
             .await
             .with_context(|| format!("odczytywanie plików z {:?}", base_dir))
             .map(tokio_stream::wrappers::ReadDirStream::new)?
             .try_collect()
-            .await?;
+            .await
+            .with_context(|| format!("odczytywanie plików z {:?}", base_dir))
+    }
+
+    /// reads every contract file separately, so that one broken file does not hide the others
+    #[instrument(skip(self))]
+    pub async fn scan_entries(&self) -> Result<Vec<(PathBuf, Result<RepairContractEntry>)>> {
+        let db = self.db.write().await;
+        Self::scan_dir(&db.base_dir).await
+    }
+
+    async fn scan_dir(dir: &Path) -> Result<Vec<(PathBuf, Result<RepairContractEntry>)>> {
+        let dir_entries = Self::dir_entries(dir).await?;
+        let entries = futures::stream::iter(dir_entries)
+            .map(|entry| async move {
+                let path = entry.path();
+                RepairContractEntry::from_dir_entry(entry)
+                    .await
+                    .map(|result| (path, result))
+            })
+            .buffer_unordered(FS_CONCURRENCY_LIMIT)
+            .filter_map(|v| async { v })
+            .collect()
+            .await;
+        Ok(entries)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn get_entries(&self) -> Result<Vec<RepairContractEntry>> {
+        info!("getting entries");
+        let db = self.db.write().await;
+        let dir_entries = Self::dir_entries(&db.base_dir).await?;
 
         let entries: Vec<_> = futures::stream::iter(dir_entries)
             .map(RepairContractEntry::from_dir_entry)
@@ -133,23 +228,662 @@ impl Database {
 
         Ok(entries)
     }
+    /// contracts already marked as deleted (e.g. migrated ones) are written to the trash
     #[instrument(skip(self))]
     pub async fn create_entry(&self, model: RepairContract) -> Result<RepairContractEntry> {
         let db = self.db.write().await;
-        let base_dir = db.base_dir.clone();
-        let contract_time = model.date;
-        let filename = format!("{contract_time}.repair-contract.toml");
-
-        let path = base_dir.join(&filename);
+        let dir = match model.deleted {
+            Some(_) => {
+                let trash_dir = Self::trash_dir(&db.base_dir);
+                tokio::fs::create_dir_all(&trash_dir)
+                    .await
+                    .with_context(|| format!("tworzenie kosza {trash_dir:?}"))?;
+                trash_dir
+            }
+            None => db.base_dir.clone(),
+        };
+        // two contracts accepted at the same moment (e.g. imported with a date only) get the id appended
+        let path = Self::free_path(&dir, &model);
+        let filename = path.file_name().unwrap_or_default().to_owned();
         let entry = {
             let path = path.clone();
             RepairContractEntry { path, model }
         };
-        let contents = tokio::task::block_in_place(|| toml::to_string_pretty(&entry))
-            .context("serializacja modelu {model:#?}")?;
-        tokio::fs::write(path, &contents)
+        let contents = tokio::task::block_in_place(|| entry.model.to_toml())?;
+        // `create_new` so that another machine creating the same file at the same time fails loudly
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+            .with_context(|| format!("tworzenie pliku {filename:?}"))?;
+        file.write_all(contents.as_bytes())
+            .await
+            .with_context(|| format!("pisanie do pliku {filename:?}"))?;
+        // a tokio file finishes writing in the background unless flushed
+        file.flush()
             .await
             .with_context(|| format!("pisanie do pliku {filename:?}"))?;
         Ok(entry)
     }
+
+    /// saves the entry unless someone else saved it since it was read, in which case
+    /// a [`Conflict`] is returned; to overwrite their changes set the revision to theirs
+    #[instrument(skip(self))]
+    pub async fn update_entry(&self, entry: RepairContractEntry) -> Result<RepairContractEntry> {
+        let _db = self.db.write().await;
+        let theirs = RepairContractEntry::from_path(&entry.path).await?;
+        if theirs.model.revision != entry.model.revision {
+            return Err(Conflict {
+                mine: entry,
+                theirs,
+            }
+            .into());
+        }
+        let mut entry = entry;
+        entry.model.revision += 1;
+        let contents = tokio::task::block_in_place(|| entry.model.to_toml())?;
+        tokio::fs::write(&entry.path, &contents)
+            .await
+            .with_context(|| format!("pisanie do pliku {:?}", entry.path))?;
+        Ok(entry)
+    }
+
+    fn trash_dir(base_dir: &Path) -> PathBuf {
+        base_dir.join(TRASH_DIR_NAME)
+    }
+
+    /// `file_name` inside `dir`, suffixed with the contract id when already taken
+    fn free_path(dir: &Path, model: &RepairContract) -> PathBuf {
+        let path = dir.join(Self::file_name(model));
+        match path.exists() {
+            true => dir.join(format!("{}.{}.repair-contract.toml", model.date, model.id)),
+            false => path,
+        }
+    }
+
+    async fn move_entry(entry: &RepairContractEntry, target: &Path) -> Result<()> {
+        let contents = tokio::task::block_in_place(|| entry.model.to_toml())?;
+        tokio::fs::write(target, &contents)
+            .await
+            .with_context(|| format!("pisanie do pliku {target:?}"))?;
+        tokio::fs::remove_file(&entry.path)
+            .await
+            .with_context(|| format!("usuwanie pliku {:?}", entry.path))
+    }
+
+    /// moves the contract to the trash, returns its new path
+    #[instrument(skip(self))]
+    pub async fn delete_entry(&self, entry: &RepairContractEntry, reason: &str) -> Result<PathBuf> {
+        let db = self.db.write().await;
+        let trash_dir = Self::trash_dir(&db.base_dir);
+        Self::leased(&entry.path, async {
+            tokio::fs::create_dir_all(&trash_dir)
+                .await
+                .with_context(|| format!("tworzenie kosza {trash_dir:?}"))?;
+            let mut entry = entry.clone();
+            entry.model.deleted = Some(Deletion {
+                date: crate::now(),
+                reason: reason.to_owned(),
+            });
+            let target = Self::free_path(&trash_dir, &entry.model);
+            Self::move_entry(&entry, &target).await?;
+            Ok(target)
+        })
+        .await
+    }
+
+    /// contracts in the trash, most recently deleted first
+    #[instrument(skip(self))]
+    pub async fn get_trash(&self) -> Result<Vec<RepairContractEntry>> {
+        let db = self.db.write().await;
+        let trash_dir = Self::trash_dir(&db.base_dir);
+        if !trash_dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut entries: Vec<_> = futures::stream::iter(Self::dir_entries(&trash_dir).await?)
+            .map(RepairContractEntry::from_dir_entry)
+            .buffer_unordered(FS_CONCURRENCY_LIMIT)
+            .filter_map(|v| async { v })
+            .try_collect()
+            .await?;
+        entries.sort_by_key(|entry: &RepairContractEntry| {
+            std::cmp::Reverse(entry.model.deleted.as_ref().map(|deleted| deleted.date))
+        });
+        Ok(entries)
+    }
+
+    /// moves the contract from the trash back into the archive
+    #[instrument(skip(self))]
+    pub async fn restore_entry(&self, entry: &RepairContractEntry) -> Result<RepairContractEntry> {
+        let db = self.db.write().await;
+        Self::leased(&entry.path, async {
+            let mut entry = entry.clone();
+            entry.model.deleted = None;
+            let target = Self::free_path(&db.base_dir, &entry.model);
+            Self::move_entry(&entry, &target).await?;
+            entry.path = target;
+            Ok(entry)
+        })
+        .await
+    }
+
+    /// removes a contract from the trash for good
+    #[instrument(skip(self))]
+    pub async fn purge_entry(&self, entry: &RepairContractEntry) -> Result<()> {
+        let db = self.db.write().await;
+        if entry.path.parent() != Some(Self::trash_dir(&db.base_dir).as_path()) {
+            anyhow::bail!("tylko zlecenia z kosza mogą zostać usunięte na zawsze");
+        }
+        Self::leased(&entry.path, async {
+            tokio::fs::remove_file(&entry.path)
+                .await
+                .with_context(|| format!("usuwanie pliku {:?}", entry.path))
+        })
+        .await
+    }
+
+    /// marks contracts closed before `before` as archived, returns how many were changed;
+    /// contracts someone is editing are left for the next run
+    #[instrument(skip(self))]
+    pub async fn archive_closed_before(&self, before: crate::AppTime) -> Result<usize> {
+        let closed_before = |entry: &RepairContractEntry| {
+            !entry.model.archived
+                && entry
+                    .model
+                    .final_protocol
+                    .as_ref()
+                    .map(|protocol| protocol.date < before)
+                    .unwrap_or_default()
+        };
+        let mut archived = 0;
+        for entry in self.get_entries().await? {
+            if !closed_before(&entry) {
+                continue;
+            }
+            let mut entry = match self.acquire_lease(&entry.path).await {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("pomijam {:?} :: {e:#}", entry.path);
+                    continue;
+                }
+            };
+            let updated = match closed_before(&entry) {
+                true => {
+                    entry.model.archived = true;
+                    self.update_entry(entry.clone()).await.map(|_| 1)
+                }
+                false => Ok(0),
+            };
+            self.release_lease(&entry.path).await?;
+            archived += updated?;
+        }
+        Ok(archived)
+    }
+
+    /// rewrites every contract stored in an older schema version in the current one, the trash
+    /// included, returns the affected files (only lists them with `dry_run`)
+    #[instrument(skip(self))]
+    pub async fn upgrade_entries(&self, dry_run: bool) -> Result<Vec<PathBuf>> {
+        let mut scanned = self.scan_entries().await?;
+        let trash_dir = Self::trash_dir(&self.db.read().await.base_dir);
+        if trash_dir.exists() {
+            scanned.extend(Self::scan_dir(&trash_dir).await?);
+        }
+        let mut upgraded = vec![];
+        for (path, entry) in scanned {
+            let content = tokio::fs::read_to_string(&path)
+                .await
+                .with_context(|| format!("odczytywanie zawartości zlecenia z {path:?}"))?;
+            let version = match toml::from_str::<toml::Value>(&content) {
+                Ok(document) => crate::migrations::document_version(&document),
+                Err(e) => {
+                    warn!("pomijam nieczytelny plik {path:?} :: {e}");
+                    continue;
+                }
+            };
+            if version >= crate::migrations::SCHEMA_VERSION {
+                continue;
+            }
+            if !dry_run {
+                let entry = entry?;
+                let _db = self.db.write().await;
+                let contents = tokio::task::block_in_place(|| entry.model.to_toml())?;
+                tokio::fs::write(&path, &contents)
+                    .await
+                    .with_context(|| format!("pisanie do pliku {path:?}"))?;
+            }
+            upgraded.push(path);
+        }
+        Ok(upgraded)
+    }
+
+    /// like [`Self::upgrade_entries`], but unless `dry_run` the archive is backed up to
+    /// `target_dir` before anything is rewritten; the backup is only made when some contract
+    /// is outdated and is returned with them
+    #[instrument(skip(self))]
+    pub async fn upgrade_with_backup(
+        &self,
+        target_dir: &Path,
+        dry_run: bool,
+    ) -> Result<(Vec<PathBuf>, Option<PathBuf>)> {
+        let outdated = self.upgrade_entries(true).await?;
+        if dry_run || outdated.is_empty() {
+            return Ok((outdated, None));
+        }
+        let archive_dir = self.base_dir().await;
+        let backup =
+            tokio::task::block_in_place(|| crate::backup::create_backup(&archive_dir, target_dir))?;
+        self.upgrade_entries(false).await?;
+        Ok((outdated, Some(backup)))
+    }
+
+    pub fn lease_path(path: &Path) -> PathBuf {
+        path.with_extension("toml.lock")
+    }
+
+    async fn read_lease(path: &Path) -> Result<Option<Lease>> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => toml::from_str(&content)
+                .map(Some)
+                .with_context(|| format!("parsowanie blokady {path:?}")),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("odczytywanie blokady {path:?}")),
+        }
+    }
+
+    /// marks the contract as being edited on this machine and returns its current content;
+    /// fails when another machine holds a fresh lease
+    #[instrument(skip(self))]
+    pub async fn acquire_lease(&self, path: &Path) -> Result<RepairContractEntry> {
+        let _db = self.db.write().await;
+        Self::take_lease(path).await?;
+        RepairContractEntry::from_path(path).await
+    }
+
+    /// runs `operation` on a contract nobody else is editing, holding its lease meanwhile
+    async fn leased<T>(
+        path: &Path,
+        operation: impl std::future::Future<Output = Result<T>>,
+    ) -> Result<T> {
+        Self::take_lease(path).await?;
+        let result = operation.await;
+        Self::drop_lease(path).await?;
+        result
+    }
+
+    async fn take_lease(path: &Path) -> Result<()> {
+        let lease_path = Self::lease_path(path);
+        let ours = Lease::ours();
+        let contents = toml::to_string(&ours).context("serializacja blokady")?;
+        if Self::create_lease(&lease_path, &contents).await? {
+            return Ok(());
+        }
+        match Self::read_lease(&lease_path).await? {
+            Some(lease) if lease.holder == ours.holder => {
+                Self::replace_lease(&lease_path, &contents).await
+            }
+            Some(lease) if !lease.is_stale() => Self::ensure_free(&lease, &ours),
+            abandoned => Self::take_over(&lease_path, abandoned, &ours, &contents).await,
+        }
+    }
+
+    /// `create_new` so that only one of the machines sharing the folder gets the lease;
+    /// `false` when there already is one
+    async fn create_lease(lease_path: &Path, contents: &str) -> Result<bool> {
+        match tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(lease_path)
+            .await
+        {
+            Ok(mut file) => {
+                file.write_all(contents.as_bytes())
+                    .await
+                    .with_context(|| format!("zapisywanie blokady {lease_path:?}"))?;
+                file.flush()
+                    .await
+                    .with_context(|| format!("zapisywanie blokady {lease_path:?}"))?;
+                Ok(true)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e).with_context(|| format!("zapisywanie blokady {lease_path:?}")),
+        }
+    }
+
+    /// replaces a stale lease; every machine that found it stale races to create a marker with
+    /// `create_new`, only the winner removes the lease and creates its own, again with
+    /// `create_new`
+    async fn take_over(
+        lease_path: &Path,
+        abandoned: Option<Lease>,
+        ours: &Lease,
+        contents: &str,
+    ) -> Result<()> {
+        let marker = lease_path.with_extension("lock.takeover");
+        if !Self::create_lease(&marker, contents).await? {
+            let age = tokio::fs::metadata(&marker)
+                .await
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok());
+            if age.is_some_and(|age| age >= TAKEOVER_TIMEOUT) {
+                warn!("usuwam porzucony znacznik przejmowania {marker:?}");
+                let _ = tokio::fs::remove_file(&marker).await;
+            }
+            anyhow::bail!("ktoś inny właśnie przejmuje blokadę zlecenia, spróbuj ponownie");
+        }
+        let taken = async {
+            // someone may have taken over and saved a fresh lease before we made the marker
+            if let Some(current) = Self::read_lease(lease_path).await? {
+                if Some(&current) != abandoned.as_ref() {
+                    Self::ensure_free(&current, ours)?;
+                }
+                tokio::fs::remove_file(lease_path)
+                    .await
+                    .with_context(|| format!("usuwanie blokady {lease_path:?}"))?;
+            }
+            match Self::create_lease(lease_path, contents).await? {
+                true => Ok(()),
+                false => match Self::read_lease(lease_path).await? {
+                    Some(lease) => Self::ensure_free(&lease, ours),
+                    None => anyhow::bail!("blokada {lease_path:?} zniknęła w trakcie przejmowania"),
+                },
+            }
+        }
+        .await;
+        tokio::fs::remove_file(&marker)
+            .await
+            .with_context(|| format!("usuwanie znacznika przejmowania {marker:?}"))?;
+        taken
+    }
+
+    fn ensure_free(lease: &Lease, ours: &Lease) -> Result<()> {
+        match lease.holder != ours.holder && !lease.is_stale() {
+            true => anyhow::bail!(
+                "zlecenie jest edytowane przez {} od {}",
+                lease.holder,
+                lease.acquired
+            ),
+            false => Ok(()),
+        }
+    }
+
+    /// swaps the whole lease file at once, so nobody reads a half written one; only for
+    /// renewing our own lease
+    async fn replace_lease(lease_path: &Path, contents: &str) -> Result<()> {
+        let temporary = lease_path.with_extension(format!("lock.{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&temporary, contents)
+            .await
+            .with_context(|| format!("zapisywanie blokady {temporary:?}"))?;
+        tokio::fs::rename(&temporary, lease_path)
+            .await
+            .with_context(|| format!("zapisywanie blokady {lease_path:?}"))
+    }
+
+    /// renews our lease so it doesn't go stale during a long edit; fails when it was taken over
+    #[instrument(skip(self))]
+    pub async fn refresh_lease(&self, path: &Path) -> Result<()> {
+        let _db = self.db.write().await;
+        let lease_path = Self::lease_path(path);
+        let ours = Lease::ours();
+        match Self::read_lease(&lease_path).await? {
+            Some(lease) if lease.holder != ours.holder => anyhow::bail!(
+                "blokadę zlecenia przejął {} o {}, zapis może się nie udać",
+                lease.holder,
+                lease.acquired
+            ),
+            _ => {
+                let contents = toml::to_string(&ours).context("serializacja blokady")?;
+                Self::replace_lease(&lease_path, &contents).await
+            }
+        }
+    }
+
+    /// removes the lease, unless it has been taken over by someone else in the meantime
+    #[instrument(skip(self))]
+    pub async fn release_lease(&self, path: &Path) -> Result<()> {
+        let _db = self.db.write().await;
+        Self::drop_lease(path).await
+    }
+
+    async fn drop_lease(path: &Path) -> Result<()> {
+        let lease_path = Self::lease_path(path);
+        match Self::read_lease(&lease_path).await? {
+            Some(lease) if lease.holder == lease_holder() => tokio::fs::remove_file(&lease_path)
+                .await
+                .with_context(|| format!("usuwanie blokady {lease_path:?}")),
+            _ => Ok(()),
+        }
+    }
+
+    /// canonical file name of a contract inside the archive
+    pub fn file_name(model: &RepairContract) -> String {
+        format!("{}.repair-contract.toml", model.date)
+    }
+
+    /// rewrites the entry in the canonical format and moves it under its canonical file name
+    /// (unless another file already took it); returns the new path
+    #[instrument(skip(self))]
+    pub async fn reindex_entry(&self, entry: RepairContractEntry) -> Result<PathBuf> {
+        let db = self.db.write().await;
+        let canonical = db.base_dir.join(Self::file_name(&entry.model));
+        let target = if canonical == entry.path || !canonical.exists() {
+            canonical
+        } else {
+            entry.path.clone()
+        };
+        let contents = tokio::task::block_in_place(|| entry.model.to_toml())?;
+        tokio::fs::write(&target, &contents)
+            .await
+            .with_context(|| format!("pisanie do pliku {target:?}"))?;
+        if target != entry.path {
+            tokio::fs::remove_file(&entry.path)
+                .await
+                .with_context(|| format!("usuwanie pliku {:?}", entry.path))?;
+        }
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    async fn write_lease(path: &Path, lease: &Lease) {
+        tokio::fs::write(Database::lease_path(path), toml::to_string(lease).unwrap())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn leases() {
+        let dir = TempDir::default();
+        let db = Database::new(dir.path().to_owned());
+        let path = db.create_entry(Default::default()).await.unwrap().path;
+
+        db.acquire_lease(&path).await.unwrap();
+        // our own lease can be taken again, e.g. after reopening the page
+        db.acquire_lease(&path).await.unwrap();
+        db.refresh_lease(&path).await.unwrap();
+
+        let theirs = Lease {
+            holder: "ktoś@inny".to_owned(),
+            acquired: crate::now(),
+        };
+        write_lease(&path, &theirs).await;
+        let error = db.acquire_lease(&path).await.unwrap_err();
+        assert!(error.to_string().contains("ktoś@inny"), "{error:#}");
+        assert!(db.refresh_lease(&path).await.is_err());
+        // someone else's lease stays in place
+        db.release_lease(&path).await.unwrap();
+        assert!(Database::lease_path(&path).exists());
+
+        write_lease(
+            &path,
+            &Lease {
+                acquired: crate::now() - chrono::Duration::minutes(LEASE_TIMEOUT_MINUTES),
+                ..theirs
+            },
+        )
+        .await;
+        db.acquire_lease(&path).await.unwrap();
+        let lease = Database::read_lease(&Database::lease_path(&path))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.holder, lease_holder());
+        db.release_lease(&path).await.unwrap();
+        assert!(!Database::lease_path(&path).exists());
+        // no temporary files are left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    /// rewrites the contract file as a version 2 document, with an untagged customer
+    fn downgrade(entry: &RepairContractEntry) -> String {
+        let mut document: toml::Value = toml::from_str(&entry.model.to_toml().unwrap()).unwrap();
+        document["info"]["customer"]
+            .as_table_mut()
+            .unwrap()
+            .remove("type");
+        document["schema_version"] = toml::Value::Integer(2);
+        let content = toml::to_string_pretty(&document).unwrap();
+        std::fs::write(&entry.path, &content).unwrap();
+        content
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn upgrade_dry_run_and_real_run() {
+        let root = TempDir::default();
+        let (archive_dir, target_dir) = (root.path().join("archiwum"), root.path().join("kopie"));
+        let db = Database::new(archive_dir);
+        let current = db.create_entry(Default::default()).await.unwrap();
+        let current_content = std::fs::read_to_string(&current.path).unwrap();
+        let outdated = db.create_entry(Default::default()).await.unwrap();
+        let trashed = db
+            .create_entry(RepairContract {
+                deleted: Some(Deletion {
+                    date: crate::now(),
+                    reason: "pomyłka".to_owned(),
+                }),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let outdated_content = [downgrade(&outdated), downgrade(&trashed)];
+        let mut expected = vec![outdated.path.clone(), trashed.path.clone()];
+        expected.sort();
+
+        let (mut listed, backup) = db.upgrade_with_backup(&target_dir, true).await.unwrap();
+        listed.sort();
+        assert_eq!((&listed, backup), (&expected, None));
+        assert_eq!(std::fs::read_to_string(&outdated.path).unwrap(), outdated_content[0]);
+        assert_eq!(std::fs::read_to_string(&trashed.path).unwrap(), outdated_content[1]);
+        assert!(!target_dir.exists());
+
+        let (mut upgraded, backup) = db.upgrade_with_backup(&target_dir, false).await.unwrap();
+        upgraded.sort();
+        assert_eq!(upgraded, expected);
+        assert_eq!(
+            crate::backup::list_backups(&target_dir).unwrap(),
+            [backup.unwrap()]
+        );
+        for path in &expected {
+            let document: toml::Value =
+                toml::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+            assert_eq!(
+                crate::migrations::document_version(&document),
+                crate::migrations::SCHEMA_VERSION
+            );
+        }
+        assert_eq!(std::fs::read_to_string(&current.path).unwrap(), current_content);
+        assert_eq!(db.get_trash().await.unwrap().len(), 1);
+
+        // nothing left to upgrade, so no new backup either
+        let (upgraded, backup) = db.upgrade_with_backup(&target_dir, false).await.unwrap();
+        assert!(upgraded.is_empty() && backup.is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stale_lease_takeover() {
+        let dir = TempDir::default();
+        let db = Database::new(dir.path().to_owned());
+        let path = db.create_entry(Default::default()).await.unwrap().path;
+        write_lease(
+            &path,
+            &Lease {
+                holder: "ktoś@inny".to_owned(),
+                acquired: crate::now() - chrono::Duration::minutes(LEASE_TIMEOUT_MINUTES),
+            },
+        )
+        .await;
+        // another machine is in the middle of taking the stale lease over
+        let marker = Database::lease_path(&path).with_extension("lock.takeover");
+        std::fs::write(&marker, "").unwrap();
+        let error = db.acquire_lease(&path).await.unwrap_err();
+        assert!(error.to_string().contains("przejmuje"), "{error:#}");
+        assert!(marker.exists());
+
+        // a marker left by a crash is cleared, the next attempt succeeds
+        std::fs::File::options()
+            .write(true)
+            .open(&marker)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() - 2 * TAKEOVER_TIMEOUT)
+            .unwrap();
+        assert!(db.acquire_lease(&path).await.is_err());
+        db.acquire_lease(&path).await.unwrap();
+        let lease = Database::read_lease(&Database::lease_path(&path))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(lease.holder, lease_holder());
+        assert!(lease.holder.ends_with(&format!("(proces {})", std::process::id())));
+        db.release_lease(&path).await.unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn leased_contracts_are_left_alone() {
+        let dir = TempDir::default();
+        let db = Database::new(dir.path().to_owned());
+        let entry = db
+            .create_entry(RepairContract {
+                final_protocol: Some(Default::default()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        // the same user on the same computer, but another instance of the app
+        let ours = format!("(proces {})", std::process::id());
+        let theirs = Lease {
+            holder: lease_holder().replace(&ours, "(proces 0)"),
+            acquired: crate::now(),
+        };
+        write_lease(&entry.path, &theirs).await;
+        assert!(db.delete_entry(&entry, "pomyłka").await.is_err());
+        assert_eq!(db.archive_closed_before(crate::now()).await.unwrap(), 0);
+        assert!(!db.get_entries().await.unwrap()[0].model.archived);
+
+        tokio::fs::remove_file(Database::lease_path(&entry.path))
+            .await
+            .unwrap();
+        assert_eq!(db.archive_closed_before(crate::now()).await.unwrap(), 1);
+        let entry = db.get_entries().await.unwrap().remove(0);
+        assert!(entry.model.archived);
+        let trashed_path = db.delete_entry(&entry, "pomyłka").await.unwrap();
+        let trashed = db.get_trash().await.unwrap().remove(0);
+        assert_eq!(trashed.path, trashed_path);
+        write_lease(&trashed.path, &theirs).await;
+        assert!(db.restore_entry(&trashed).await.is_err());
+        assert!(db.purge_entry(&trashed).await.is_err());
+        tokio::fs::remove_file(Database::lease_path(&trashed.path))
+            .await
+            .unwrap();
+        db.purge_entry(&trashed).await.unwrap();
+        // no leases are left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert_eq!(std::fs::read_dir(Database::trash_dir(dir.path())).unwrap().count(), 0);
+    }
 }
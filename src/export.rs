@@ -0,0 +1,634 @@
+use std::{
+    cmp::Ordering,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use chrono::NaiveDate;
+use rust_decimal::{
+    prelude::ToPrimitive,
+    Decimal,
+};
+use rust_xlsxwriter::{
+    Format,
+    Workbook,
+    Worksheet,
+};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    db::RepairContractEntry,
+    models::{
+        ContractStatus,
+        signature::PAD_SIZE,
+        RepairContract,
+        Signature,
+    },
+    settings::{
+        CompanyDetails,
+        Settings,
+    },
+};
+
+/// narrows down the list of contracts, shared by the list page and the exports
+#[derive(Debug, Clone, Default)]
+pub struct ContractFilter {
+    /// matched against customer, device and id, case insensitive
+    pub query: String,
+    pub status: Option<ContractStatus>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// archived contracts are hidden unless asked for
+    pub include_archived: bool,
+}
+
+impl ContractFilter {
+    pub fn matches(&self, entry: &RepairContractEntry) -> bool {
+        let model = &entry.model;
+        let query = self.query.trim().to_lowercase();
+        let date = model.date.date();
+        (query.is_empty()
+            || [
+                model.id.to_string().as_str(),
+                model.info.customer.name(),
+                model.info.customer.phone(),
+                model.info.customer.tax_number().unwrap_or_default(),
+                model.info.device.model_name.as_str(),
+                model.info.device.serial_number.as_str(),
+            ]
+            .iter()
+            .any(|field| field.to_lowercase().contains(&query)))
+            && self.status.is_none_or(|status| model.status() == status)
+            && self.from.is_none_or(|from| date >= from)
+            && self.to.is_none_or(|to| date <= to)
+            && (self.include_archived || !model.archived)
+    }
+
+    pub fn apply<'a>(&self, entries: &'a [RepairContractEntry]) -> Vec<&'a RepairContractEntry> {
+        entries.iter().filter(|entry| self.matches(entry)).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortColumn {
+    #[default]
+    Date,
+    Customer,
+    Device,
+    Status,
+    Price,
+}
+
+impl SortColumn {
+    pub const ALL: [SortColumn; 5] = [
+        SortColumn::Date,
+        SortColumn::Customer,
+        SortColumn::Device,
+        SortColumn::Status,
+        SortColumn::Price,
+    ];
+}
+
+impl std::fmt::Display for SortColumn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SortColumn::Date => "data",
+            SortColumn::Customer => "klient",
+            SortColumn::Device => "urządzenie",
+            SortColumn::Status => "status",
+            SortColumn::Price => "cena",
+        }
+        .fmt(f)
+    }
+}
+
+/// order of the contracts list, remembered in the settings
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct ContractSort {
+    pub column: SortColumn,
+    pub descending: bool,
+}
+
+impl Default for ContractSort {
+    fn default() -> Self {
+        Self {
+            column: SortColumn::Date,
+            descending: true,
+        }
+    }
+}
+
+impl ContractSort {
+    /// clicking the current column flips the direction, another column starts ascending
+    pub fn toggled(self, column: SortColumn) -> Self {
+        match self.column == column {
+            true => Self {
+                descending: !self.descending,
+                ..self
+            },
+            false => Self {
+                column,
+                descending: false,
+            },
+        }
+    }
+
+    pub fn compare(&self, a: &RepairContractEntry, b: &RepairContractEntry) -> Ordering {
+        let (a, b) = (&a.model, &b.model);
+        let ordering = match self.column {
+            SortColumn::Date => a.date.cmp(&b.date),
+            SortColumn::Customer => compare_text(a.info.customer.name(), b.info.customer.name()),
+            SortColumn::Device => {
+                compare_text(&a.info.device.model_name, &b.info.device.model_name)
+            }
+            SortColumn::Status => a.status().cmp(&b.status()),
+            SortColumn::Price => price(a).cmp(&price(b)),
+        }
+        .then_with(|| a.date.cmp(&b.date));
+        match self.descending {
+            true => ordering.reverse(),
+            false => ordering,
+        }
+    }
+}
+
+fn compare_text(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+/// the final price once the contract is closed, the prognosis before that
+fn price(model: &RepairContract) -> Decimal {
+    match &model.final_protocol {
+        Some(protocol) => protocol.final_price,
+        None => model.info.prognosis_price,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+        .fmt(f)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Cell {
+    Text(String),
+    Number(Decimal),
+}
+
+impl Cell {
+    fn text(value: impl ToString) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl std::fmt::Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cell::Text(text) => text.fmt(f),
+            Cell::Number(number) => number.fmt(f),
+        }
+    }
+}
+
+const CONTRACT_HEADERS: &[&str] = &[
+    "id",
+    "data przyjęcia",
+    "status",
+    "klient",
+    "telefon",
+    "NIP",
+    "model urządzenia",
+    "numer seryjny",
+    "przewidywany czas naprawy (dni robocze)",
+    "cena wstępna",
+    "data wydania",
+    "cena końcowa",
+];
+
+const LINE_ITEM_HEADERS: &[&str] = &["id zlecenia", "rodzaj", "id", "nazwa", "cena"];
+
+fn contract_row(model: &RepairContract) -> Vec<Cell> {
+    let customer = &model.info.customer;
+    let protocol = model.final_protocol.as_ref();
+    vec![
+        Cell::text(model.id),
+        Cell::text(model.date),
+        Cell::text(model.status()),
+        Cell::text(customer.name()),
+        Cell::text(customer.phone()),
+        Cell::text(customer.tax_number().unwrap_or_default()),
+        Cell::text(&model.info.device.model_name),
+        Cell::text(&model.info.device.serial_number),
+        Cell::text(model.info.expected_repair_time_work_days),
+        Cell::Number(model.info.prognosis_price),
+        Cell::text(
+            protocol
+                .map(|protocol| protocol.date.to_string())
+                .unwrap_or_default(),
+        ),
+        protocol
+            .map(|protocol| Cell::Number(protocol.final_price))
+            .unwrap_or_else(|| Cell::text("")),
+    ]
+}
+
+fn line_item_rows(model: &RepairContract) -> Vec<Vec<Cell>> {
+    let protocol = match &model.final_protocol {
+        Some(protocol) => protocol,
+        None => return vec![],
+    };
+    let repairs = protocol.performed_repairs.iter().map(|repair| {
+        vec![
+            Cell::text(model.id),
+            Cell::text("usługa"),
+            Cell::text(&repair.id),
+            Cell::text(&repair.name),
+            Cell::Number(repair.price),
+        ]
+    });
+    let parts = protocol.parts_replaced.iter().map(|part| {
+        vec![
+            Cell::text(model.id),
+            Cell::text("część"),
+            Cell::text(&part.id),
+            Cell::text(&part.name),
+            Cell::Number(part.price),
+        ]
+    });
+    repairs.chain(parts).collect()
+}
+
+fn write_csv_table(path: &Path, headers: &[&str], rows: &[Vec<Cell>]) -> Result<()> {
+    let mut writer =
+        csv::Writer::from_path(path).with_context(|| format!("tworzenie pliku {path:?}"))?;
+    writer.write_record(headers)?;
+    for row in rows {
+        writer.write_record(row.iter().map(ToString::to_string))?;
+    }
+    writer
+        .flush()
+        .with_context(|| format!("zapisywanie pliku {path:?}"))
+}
+
+fn write_sheet(sheet: &mut Worksheet, headers: &[&str], rows: &[Vec<Cell>]) -> Result<()> {
+    let bold = Format::new().set_bold();
+    for (col, header) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *header, &bold)?;
+        sheet.set_column_width(col as u16, header.chars().count().max(12) as f64)?;
+    }
+    for (row, cells) in rows.iter().enumerate() {
+        let row = row as u32 + 1;
+        for (col, cell) in cells.iter().enumerate() {
+            let col = col as u16;
+            match cell {
+                Cell::Text(text) => sheet.write_string(row, col, text)?,
+                Cell::Number(number) => {
+                    sheet.write_number(row, col, number.to_f64().unwrap_or_default())?
+                }
+            };
+        }
+    }
+    Ok(())
+}
+
+/// writes the contracts to `path`, line items from the final protocols go to a separate sheet
+/// (XLSX) or to a sibling `*-pozycje.csv` file (CSV); returns every written file
+pub fn export_contracts(
+    entries: &[&RepairContractEntry],
+    format: ExportFormat,
+    path: &Path,
+) -> Result<Vec<PathBuf>> {
+    let contracts = entries
+        .iter()
+        .map(|entry| contract_row(&entry.model))
+        .collect::<Vec<_>>();
+    let line_items = entries
+        .iter()
+        .flat_map(|entry| line_item_rows(&entry.model))
+        .collect::<Vec<_>>();
+    match format {
+        ExportFormat::Csv => {
+            let line_items_path = path.with_file_name(format!(
+                "{}-pozycje.csv",
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or("eksport")
+            ));
+            write_csv_table(path, CONTRACT_HEADERS, &contracts)?;
+            write_csv_table(&line_items_path, LINE_ITEM_HEADERS, &line_items)?;
+            Ok(vec![path.to_owned(), line_items_path])
+        }
+        ExportFormat::Xlsx => {
+            let mut workbook = Workbook::new();
+            write_sheet(
+                workbook.add_worksheet().set_name("zlecenia")?,
+                CONTRACT_HEADERS,
+                &contracts,
+            )?;
+            write_sheet(
+                workbook.add_worksheet().set_name("pozycje")?,
+                LINE_ITEM_HEADERS,
+                &line_items,
+            )?;
+            workbook
+                .save(path)
+                .with_context(|| format!("zapisywanie pliku {path:?}"))?;
+            Ok(vec![path.to_owned()])
+        }
+    }
+}
+
+/// default location of exports made from the app
+pub fn default_export_path(settings: &Settings, format: ExportFormat) -> Result<PathBuf> {
+    Ok(settings.output_dir("eksport")?.join(format!(
+        "zlecenia-{}.{format}",
+        crate::now().format("%Y-%m-%d_%H-%M-%S")
+    )))
+}
+
+fn escape_html(text: impl ToString) -> String {
+    text.to_string()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn signature_block(title: &str, signature: Option<&Signature>) -> String {
+    let signature = match signature.filter(|signature| !signature.is_empty()) {
+        Some(signature) => signature.to_svg(PAD_SIZE.0.into(), PAD_SIZE.1.into()),
+        None => "<p>brak podpisu</p>".to_owned(),
+    };
+    format!("<h3>{}</h3>\n{signature}\n", escape_html(title))
+}
+
+fn table(headers: &[&str], rows: &[Vec<Cell>]) -> String {
+    let row = |cells: Vec<String>, tag: &str| {
+        let cells = cells
+            .iter()
+            .map(|cell| format!("<{tag}>{cell}</{tag}>"))
+            .collect::<String>();
+        format!("<tr>{cells}</tr>\n")
+    };
+    let mut table = String::from("<table>\n");
+    table.push_str(&row(headers.iter().map(escape_html).collect(), "th"));
+    for cells in rows {
+        table.push_str(&row(cells.iter().map(escape_html).collect(), "td"));
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+/// the service's own details above the contract, the ones left empty are skipped
+fn company_header(company: &CompanyDetails) -> String {
+    let lines = [
+        company.name.clone(),
+        company.address.clone(),
+        match company.tax_number.is_empty() {
+            true => String::new(),
+            false => format!("NIP: {}", company.tax_number),
+        },
+        company.phone.clone(),
+        company.email.clone(),
+    ]
+    .into_iter()
+    .filter(|line| !line.trim().is_empty())
+    .map(escape_html)
+    .collect::<Vec<_>>();
+    match lines.is_empty() {
+        true => String::new(),
+        false => format!("<header>\n<p>{}</p>\n</header>\n", lines.join("<br>\n")),
+    }
+}
+
+/// printable HTML of the contract with the customer signatures embedded as SVG
+pub fn contract_document(model: &RepairContract, company: &CompanyDetails) -> String {
+    let customer = &model.info.customer;
+    let mut details = vec![
+        ("data przyjęcia", model.date.to_string()),
+        ("klient", customer.name().to_owned()),
+        ("telefon", customer.phone().to_owned()),
+    ];
+    if let Some(tax_number) = customer.tax_number() {
+        details.push(("NIP", tax_number.to_owned()));
+    }
+    details.extend([
+        ("model urządzenia", model.info.device.model_name.clone()),
+        ("numer seryjny", model.info.device.serial_number.clone()),
+        (
+            "przewidywany czas naprawy (dni robocze)",
+            model.info.expected_repair_time_work_days.to_string(),
+        ),
+        ("cena wstępna", model.info.prognosis_price.to_string()),
+        ("opis usterki", model.info.description.join(", ")),
+        ("widoczne uszkodzenia", model.info.visible_damages.join(", ")),
+        ("uwagi", model.info.notes.clone()),
+    ]);
+    let mut body = company_header(company);
+    body.push_str(&format!("<h1>Zlecenie serwisowe {}</h1>\n<dl>\n", model.id));
+    for (label, value) in details {
+        body.push_str(&format!(
+            "<dt>{}</dt><dd>{}</dd>\n",
+            escape_html(label),
+            escape_html(value)
+        ));
+    }
+    body.push_str("</dl>\n");
+    body.push_str(&signature_block(
+        "podpis klienta przy przyjęciu",
+        model.customer_signature.as_ref(),
+    ));
+    if let Some(protocol) = &model.final_protocol {
+        body.push_str(&format!(
+            "<h2>Protokół wydania z {}</h2>\n",
+            escape_html(protocol.date)
+        ));
+        // the contract id is already in the title
+        let line_items = line_item_rows(model)
+            .into_iter()
+            .map(|row| row[1..].to_vec())
+            .collect::<Vec<_>>();
+        body.push_str(&table(&LINE_ITEM_HEADERS[1..], &line_items));
+        body.push_str(&format!(
+            "<p>cena końcowa: {}</p>\n",
+            escape_html(protocol.final_price)
+        ));
+        body.push_str(&signature_block(
+            "podpis klienta przy odbiorze",
+            protocol.customer_signature.as_ref(),
+        ));
+    }
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"pl\">\n<head><meta charset=\"utf-8\"><title>Zlecenie {}</title></head>\n<body>\n{body}</body>\n</html>\n",
+        model.id
+    )
+}
+
+pub fn print_contract(model: &RepairContract, company: &CompanyDetails, path: &Path) -> Result<()> {
+    std::fs::write(path, contract_document(model, company))
+        .with_context(|| format!("zapisywanie pliku {path:?}"))
+}
+
+/// default location of printable documents made from the app
+pub fn default_print_path(settings: &Settings, model: &RepairContract) -> Result<PathBuf> {
+    Ok(settings
+        .output_dir("wydruki")?
+        .join(format!("zlecenie-{}.html", model.id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{
+            protocols::FinalProtocol,
+            Customer,
+            PerformedRepair,
+            PrivateCustomer,
+            ReplacementPart,
+        },
+        testing::TempDir,
+    };
+
+    #[test]
+    fn company_details_head_the_document() {
+        let model = entry("Jan Kowalski", "X1", false).model;
+        let company = CompanyDetails {
+            name: "Serwis & Spółka".to_owned(),
+            tax_number: "1234567890".to_owned(),
+            ..Default::default()
+        };
+        let document = contract_document(&model, &company);
+        let header = "<header>\n<p>Serwis &amp; Spółka<br>\nNIP: 1234567890</p>\n</header>";
+        assert!(document.contains(header), "{document}");
+        assert!(document.find("<header>") < document.find("<h1>"));
+        assert!(!contract_document(&model, &Default::default()).contains("<header>"));
+    }
+
+    fn entry(customer: &str, device: &str, archived: bool) -> RepairContractEntry {
+        let mut model = RepairContract {
+            archived,
+            ..Default::default()
+        };
+        model.info.customer = Customer::PrivateCustomer(PrivateCustomer {
+            name: customer.to_owned(),
+            phone: "600100200".to_owned(),
+        });
+        model.info.device.model_name = device.to_owned();
+        model.info.prognosis_price = Decimal::new(1999, 2);
+        RepairContractEntry {
+            path: Default::default(),
+            model,
+        }
+    }
+
+    fn read_csv(path: &Path) -> (Vec<String>, Vec<Vec<String>>) {
+        let mut reader = csv::Reader::from_path(path).unwrap();
+        let headers = reader.headers().unwrap().iter().map(str::to_owned).collect();
+        let rows = reader
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_owned).collect())
+            .collect();
+        (headers, rows)
+    }
+
+    #[test]
+    fn csv_export_of_filtered_contracts() {
+        let dir = TempDir::default();
+        let mut closed = entry("Jan Kowalski", "X1", false);
+        closed.model.final_protocol = Some(FinalProtocol {
+            final_price: Decimal::from(150),
+            performed_repairs: vec![PerformedRepair {
+                id: "r1".to_owned(),
+                name: "wymiana ekranu".to_owned(),
+                price: Decimal::from(100),
+            }],
+            parts_replaced: vec![ReplacementPart {
+                id: "c1".to_owned(),
+                name: "ekran".to_owned(),
+                price: Decimal::from(50),
+            }],
+            ..Default::default()
+        });
+        let entries = [
+            closed,
+            entry("Anna Nowak", "Y2", false),
+            entry("Jan Kowalski", "Z3", true),
+            entry("jan kowalski", "Z4", false),
+        ];
+        let filter = ContractFilter {
+            query: "KOWALSKI".to_owned(),
+            ..Default::default()
+        };
+        let path = dir.path().join("zlecenia.csv");
+        let written = export_contracts(&filter.apply(&entries), ExportFormat::Csv, &path).unwrap();
+        let line_items_path = dir.path().join("zlecenia-pozycje.csv");
+        assert_eq!(written, [path.clone(), line_items_path.clone()]);
+
+        let (headers, rows) = read_csv(&path);
+        assert_eq!(headers, CONTRACT_HEADERS);
+        // the archived and the not matching contracts are left out, the order is kept
+        assert_eq!(rows.len(), 2);
+        let expected = |entry: &RepairContractEntry, closed: Option<(&str, &str)>| {
+            let model = &entry.model;
+            let (closed_on, final_price) = closed.unwrap_or_default();
+            [
+                model.id.to_string(),
+                model.date.to_string(),
+                model.status().to_string(),
+                model.info.customer.name().to_owned(),
+                "600100200".to_owned(),
+                String::new(),
+                model.info.device.model_name.clone(),
+                String::new(),
+                model.info.expected_repair_time_work_days.to_string(),
+                "19.99".to_owned(),
+                closed_on.to_owned(),
+                final_price.to_owned(),
+            ]
+        };
+        let closed_on = entries[0]
+            .model
+            .final_protocol
+            .as_ref()
+            .unwrap()
+            .date
+            .to_string();
+        assert_eq!(rows[0], expected(&entries[0], Some((&closed_on, "150"))));
+        assert_eq!(rows[1], expected(&entries[3], None));
+
+        let (headers, rows) = read_csv(&line_items_path);
+        assert_eq!(headers, LINE_ITEM_HEADERS);
+        let id = entries[0].model.id.to_string();
+        assert_eq!(
+            rows,
+            [
+                [id.as_str(), "usługa", "r1", "wymiana ekranu", "100"],
+                [id.as_str(), "część", "c1", "ekran", "50"],
+            ]
+        );
+    }
+}
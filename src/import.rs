@@ -0,0 +1,512 @@
+use std::{
+    collections::{
+        BTreeMap,
+        HashSet,
+    },
+    fmt::Display,
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use chrono::{
+    NaiveDate,
+    NaiveDateTime,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    db::RepairContractEntry,
+    models::{
+        protocols::FinalProtocol,
+        Company,
+        Customer,
+        PrivateCustomer,
+        RepairContract,
+    },
+    storage::Storage,
+    AppTime,
+};
+
+/// fields of [`RepairContract`] a legacy spreadsheet column can be mapped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImportField {
+    Date,
+    CustomerName,
+    CustomerPhone,
+    CustomerTaxNumber,
+    DeviceModel,
+    DeviceSerialNumber,
+    Description,
+    Notes,
+    PrognosisPrice,
+    ClosedDate,
+    FinalPrice,
+}
+
+impl ImportField {
+    pub const ALL: [ImportField; 11] = [
+        ImportField::Date,
+        ImportField::CustomerName,
+        ImportField::CustomerPhone,
+        ImportField::CustomerTaxNumber,
+        ImportField::DeviceModel,
+        ImportField::DeviceSerialNumber,
+        ImportField::Description,
+        ImportField::Notes,
+        ImportField::PrognosisPrice,
+        ImportField::ClosedDate,
+        ImportField::FinalPrice,
+    ];
+
+    /// lowercase header fragments used to guess the mapping
+    fn header_hints(&self) -> &'static [&'static str] {
+        match self {
+            ImportField::Date => &["data przyjęcia", "data", "date"],
+            ImportField::CustomerName => &["klient", "nazwa", "customer", "imię"],
+            ImportField::CustomerPhone => &["telefon", "tel", "phone"],
+            ImportField::CustomerTaxNumber => &["nip", "tax"],
+            ImportField::DeviceModel => &["model", "urządzenie", "device"],
+            ImportField::DeviceSerialNumber => &["numer seryjny", "serial", "s/n", "sn"],
+            ImportField::Description => &["opis", "usterka", "description"],
+            ImportField::Notes => &["uwagi", "notatki", "notes"],
+            ImportField::PrognosisPrice => &["cena wstępna", "wycena", "prognosis"],
+            ImportField::ClosedDate => &["data wydania", "data odbioru", "closed"],
+            ImportField::FinalPrice => &["cena końcowa", "cena", "price"],
+        }
+    }
+}
+
+impl Display for ImportField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportField::Date => "data przyjęcia",
+            ImportField::CustomerName => "klient",
+            ImportField::CustomerPhone => "telefon",
+            ImportField::CustomerTaxNumber => "NIP",
+            ImportField::DeviceModel => "model urządzenia",
+            ImportField::DeviceSerialNumber => "numer seryjny",
+            ImportField::Description => "opis usterki",
+            ImportField::Notes => "uwagi",
+            ImportField::PrognosisPrice => "cena wstępna",
+            ImportField::ClosedDate => "data wydania",
+            ImportField::FinalPrice => "cena końcowa",
+        }
+        .fmt(f)
+    }
+}
+
+/// which column (by index) holds which field
+pub type ColumnMapping = BTreeMap<ImportField, usize>;
+
+#[derive(Debug, Clone)]
+pub struct SourceRecord {
+    /// line of the file the record starts on, quoted cells may span several
+    pub line: usize,
+    pub cells: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportSource {
+    pub headers: Vec<String>,
+    pub records: Vec<SourceRecord>,
+}
+
+impl ImportSource {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("odczytywanie pliku importu {path:?}"))?;
+        Self::from_csv(&decode(&bytes))
+    }
+
+    /// guesses between `,` and `;` (the latter is what Excel writes in Polish locale)
+    pub fn from_csv(content: &str) -> Result<Self> {
+        let first_line = content.lines().next().unwrap_or_default();
+        let delimiter = match first_line.matches(';').count() > first_line.matches(',').count() {
+            true => b';',
+            false => b',',
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(content.as_bytes());
+        let headers = reader
+            .headers()
+            .context("odczytywanie nagłówków")?
+            .iter()
+            .map(|header| header.trim().to_owned())
+            .collect();
+        let records = reader
+            .records()
+            .map(|record| {
+                let record = record.context("odczytywanie wiersza")?;
+                Ok(SourceRecord {
+                    line: record
+                        .position()
+                        .map(|position| position.line() as usize)
+                        .unwrap_or_default(),
+                    cells: record.iter().map(|cell| cell.trim().to_owned()).collect(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { headers, records })
+    }
+
+    pub fn guess_mapping(&self) -> ColumnMapping {
+        let headers = self
+            .headers
+            .iter()
+            .map(|header| header.to_lowercase())
+            .collect::<Vec<_>>();
+        let mut taken = HashSet::new();
+        let mut mapping = ColumnMapping::new();
+        // exact matches first so that e.g. "cena końcowa" is not stolen by a generic "cena"
+        for exact in [true, false] {
+            for field in ImportField::ALL {
+                if mapping.contains_key(&field) {
+                    continue;
+                }
+                let found = field.header_hints().iter().find_map(|hint| {
+                    (0..headers.len()).find(|index| {
+                        !taken.contains(index)
+                            && match exact {
+                                true => headers[*index] == *hint,
+                                false => headers[*index].contains(hint),
+                            }
+                    })
+                });
+                if let Some(index) = found {
+                    taken.insert(index);
+                    mapping.insert(field, index);
+                }
+            }
+        }
+        mapping
+    }
+}
+
+/// UTF-8, with or without the BOM Excel adds, or Windows-1250 which Excel uses for plain CSV
+/// in the Polish locale; every byte means something in Windows-1250, so this never fails
+pub fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(content) => content.to_owned(),
+        Err(_) => encoding_rs::WINDOWS_1250
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedRow {
+    /// line in the source file the record starts on, counting the header as line 1
+    pub line: usize,
+    pub result: Result<RepairContract, String>,
+    pub duplicate: bool,
+}
+
+impl ParsedRow {
+    pub fn importable(&self) -> bool {
+        self.result.is_ok() && !self.duplicate
+    }
+}
+
+pub fn parse_date(value: &str) -> Result<AppTime> {
+    const DATE_TIME_FORMATS: &[&str] = &[
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%d.%m.%Y %H:%M:%S",
+        "%d.%m.%Y %H:%M",
+    ];
+    const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%d.%m.%Y", "%d/%m/%Y"];
+    DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            DATE_FORMATS.iter().find_map(|format| {
+                NaiveDate::parse_from_str(value, format)
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })
+        })
+        .with_context(|| format!("zła data [{value}]"))
+}
+
+/// accepts both `1234.50` and the Polish `1 234,50` or `1.234,50`; the separator that comes last
+/// and only once is the decimal one, the other one separates thousands
+pub fn parse_decimal(value: &str) -> Result<Decimal> {
+    let normalized = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+    let normalized = normalized.trim_end_matches("zł");
+    let decimal_comma = normalized.matches(',').count() == 1
+        && normalized.rfind(',') > normalized.rfind('.');
+    let normalized = match decimal_comma {
+        true => normalized.replace('.', "").replace(',', "."),
+        false if normalized.matches('.').count() > 1 => normalized.replace(['.', ','], ""),
+        false => normalized.replace(',', ""),
+    };
+    Decimal::from_str(&normalized).with_context(|| format!("zła kwota [{value}]"))
+}
+
+fn parse_record(record: &[String], mapping: &ColumnMapping) -> Result<RepairContract> {
+    let cell = |field: ImportField| {
+        mapping
+            .get(&field)
+            .and_then(|index| record.get(*index))
+            .map(|value| value.as_str())
+            .filter(|value| !value.is_empty())
+    };
+    let mut contract = RepairContract {
+        date: parse_date(
+            cell(ImportField::Date)
+                .with_context(|| format!("brak kolumny [{}]", ImportField::Date))?,
+        )?,
+        ..Default::default()
+    };
+    let name = cell(ImportField::CustomerName)
+        .unwrap_or_default()
+        .to_owned();
+    let phone = cell(ImportField::CustomerPhone)
+        .unwrap_or_default()
+        .to_owned();
+    contract.info.customer = match cell(ImportField::CustomerTaxNumber) {
+        Some(tax_number) => Customer::Company(Company {
+            name,
+            tax_number: tax_number.to_owned(),
+            phone,
+        }),
+        None => Customer::PrivateCustomer(PrivateCustomer { name, phone }),
+    };
+    contract.info.device.model_name = cell(ImportField::DeviceModel)
+        .unwrap_or_default()
+        .to_owned();
+    contract.info.device.serial_number = cell(ImportField::DeviceSerialNumber)
+        .unwrap_or_default()
+        .to_owned();
+    contract.info.description = cell(ImportField::Description)
+        .map(|description| vec![description.to_owned()])
+        .unwrap_or_default();
+    contract.info.notes = cell(ImportField::Notes).unwrap_or_default().to_owned();
+    if let Some(price) = cell(ImportField::PrognosisPrice) {
+        contract.info.prognosis_price = parse_decimal(price)?;
+    }
+    let closed_date = cell(ImportField::ClosedDate).map(parse_date).transpose()?;
+    let final_price = cell(ImportField::FinalPrice)
+        .map(parse_decimal)
+        .transpose()?;
+    if closed_date.is_some() || final_price.is_some() {
+        contract.final_protocol = Some(FinalProtocol {
+            date: closed_date.unwrap_or(contract.date),
+            final_price: final_price.unwrap_or_default(),
+            ..Default::default()
+        });
+    }
+    Ok(contract)
+}
+
+fn duplicate_key(contract: &RepairContract) -> Option<(String, NaiveDate)> {
+    let serial_number = contract.info.device.serial_number.trim().to_lowercase();
+    (!serial_number.is_empty()).then(|| (serial_number, contract.date.date()))
+}
+
+/// parses every record, marking rows whose serial number and date already exist
+/// in the archive (or earlier in the same file) as duplicates
+pub fn preview(
+    source: &ImportSource,
+    mapping: &ColumnMapping,
+    existing: &[RepairContractEntry],
+) -> Vec<ParsedRow> {
+    let mut seen = existing
+        .iter()
+        .filter_map(|entry| duplicate_key(&entry.model))
+        .collect::<HashSet<_>>();
+    source
+        .records
+        .iter()
+        .map(|record| {
+            let result = parse_record(&record.cells, mapping).map_err(|e| format!("{e:#}"));
+            let duplicate = result
+                .as_ref()
+                .ok()
+                .and_then(duplicate_key)
+                .map(|key| !seen.insert(key))
+                .unwrap_or_default();
+            ParsedRow {
+                line: record.line,
+                result,
+                duplicate,
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub imported: usize,
+    pub skipped_duplicates: usize,
+    pub failed: Vec<(usize, String)>,
+}
+
+/// creates the contracts of the importable rows; duplicates are checked again against the
+/// archive itself, the preview may have been made before it was loaded or someone else may
+/// have added contracts since
+pub async fn import(db: &dyn Storage, rows: Vec<ParsedRow>) -> Result<ImportSummary> {
+    let mut seen = db
+        .get_entries()
+        .await?
+        .iter()
+        .filter_map(|entry| duplicate_key(&entry.model))
+        .collect::<HashSet<_>>();
+    let mut summary = ImportSummary::default();
+    for row in rows {
+        let contract = match row.result {
+            Ok(contract) => contract,
+            Err(e) => {
+                summary.failed.push((row.line, e));
+                continue;
+            }
+        };
+        let duplicate = row.duplicate
+            || duplicate_key(&contract)
+                .map(|key| !seen.insert(key))
+                .unwrap_or_default();
+        if duplicate {
+            summary.skipped_duplicates += 1;
+            continue;
+        }
+        match db.create_entry(contract).await {
+            Ok(_) => summary.imported += 1,
+            Err(e) => summary.failed.push((row.line, format!("{e:#}"))),
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimals() {
+        let parsed = |value| parse_decimal(value).unwrap().to_string();
+        assert_eq!(parsed("1234.50"), "1234.50");
+        assert_eq!(parsed("1234,50"), "1234.50");
+        assert_eq!(parsed("1 234,50 zł"), "1234.50");
+        assert_eq!(parsed("1\u{a0}234,50"), "1234.50");
+        assert_eq!(parsed("1.234,50"), "1234.50");
+        assert_eq!(parsed("1.234.567"), "1234567");
+        assert_eq!(parsed("1,234.50"), "1234.50");
+        assert_eq!(parsed("-12,5"), "-12.5");
+        assert_eq!(parsed("1,234,567"), "1234567");
+        assert!(parse_decimal("dwa").is_err());
+    }
+
+    #[test]
+    fn dates() {
+        let parsed = |value| parse_date(value).unwrap().to_string();
+        assert_eq!(parsed("2023-04-05"), "2023-04-05 00:00:00");
+        assert_eq!(parsed("05.04.2023"), "2023-04-05 00:00:00");
+        assert_eq!(parsed("05/04/2023"), "2023-04-05 00:00:00");
+        assert_eq!(parsed("05.04.2023 14:30"), "2023-04-05 14:30:00");
+        assert_eq!(parsed("2023-04-05T14:30:15"), "2023-04-05 14:30:15");
+        assert!(parse_date("5 kwietnia").is_err());
+        assert!(parse_date("31.02.2023").is_err());
+    }
+
+    #[test]
+    fn mapping_prefers_exact_headers() {
+        let source = ImportSource::from_csv(
+            "Cena;Data;Klient;Tel.;Cena końcowa;Numer seryjny;Model urządzenia;Coś\n",
+        )
+        .unwrap();
+        let mapping = source.guess_mapping();
+        assert_eq!(mapping[&ImportField::FinalPrice], 4);
+        assert_eq!(mapping[&ImportField::Date], 1);
+        assert_eq!(mapping[&ImportField::CustomerName], 2);
+        assert_eq!(mapping[&ImportField::CustomerPhone], 3);
+        assert_eq!(mapping[&ImportField::DeviceSerialNumber], 5);
+        assert_eq!(mapping[&ImportField::DeviceModel], 6);
+        assert!(!mapping.contains_key(&ImportField::PrognosisPrice));
+        assert!(!mapping.values().any(|index| *index == 0 || *index == 7));
+    }
+
+    #[test]
+    fn preview_lines_and_duplicates() {
+        let source = ImportSource::from_csv(
+            "data,klient,numer seryjny,uwagi,cena końcowa\n\
+             2023-01-02,Jan,SN1,\"pierwsza\ndruga linia\",\n\
+             2023-01-03,Ewa,SN2,,\"1.234,50\"\n\
+             2023-01-02,Jan,sn1,,\n\
+             2023-01-04,Ola,SN9,,\n\
+             zła data,Ola,,,\n",
+        )
+        .unwrap();
+        let mapping = source.guess_mapping();
+        let mut existing = RepairContractEntry {
+            path: Default::default(),
+            model: Default::default(),
+        };
+        existing.model.date = parse_date("2023-01-04").unwrap();
+        existing.model.info.device.serial_number = "SN9".to_owned();
+        let rows = preview(&source, &mapping, &[existing]);
+        let lines = rows.iter().map(|row| row.line).collect::<Vec<_>>();
+        // the quoted note spans lines 2 and 3
+        assert_eq!(lines, [2, 4, 5, 6, 7]);
+        assert!(rows[0].importable());
+        assert_eq!(
+            rows[0].result.as_ref().unwrap().info.notes,
+            "pierwsza\ndruga linia"
+        );
+        let protocol = rows[1].result.as_ref().unwrap().final_protocol.clone();
+        assert_eq!(protocol.unwrap().final_price, Decimal::new(123450, 2));
+        // same serial number (case insensitive) and day as an earlier row
+        assert!(rows[2].duplicate);
+        // same as a contract already in the archive
+        assert!(rows[3].duplicate);
+        assert!(rows[4].result.as_ref().unwrap_err().contains("zła data"));
+    }
+
+    #[test]
+    fn encodings() {
+        let text = "klient;uwagi\nŁukasz Żółć;ąęśń\n";
+        let (cp1250, _, _) = encoding_rs::WINDOWS_1250.encode(text);
+        assert!(std::str::from_utf8(&cp1250).is_err());
+        assert_eq!(decode(&cp1250), text);
+        assert_eq!(decode(text.as_bytes()), text);
+        let with_bom = [b"\xEF\xBB\xBF".as_slice(), text.as_bytes()].concat();
+        assert_eq!(decode(&with_bom), text);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn import_checks_the_archive_for_duplicates() {
+        let dir = crate::testing::TempDir::default();
+        let db = crate::db::Database::new(dir.path().to_owned());
+        let mut existing = RepairContract {
+            date: parse_date("2023-01-04").unwrap(),
+            ..Default::default()
+        };
+        existing.info.device.serial_number = "SN9".to_owned();
+        db.create_entry(existing).await.unwrap();
+        let source = ImportSource::from_csv(
+            "data,klient,numer seryjny\n\
+             2023-01-04,Ola,sn9\n\
+             2023-01-05,Ola,SN9\n\
+             2023-01-05,Ola,SN9\n",
+        )
+        .unwrap();
+        // previewed before the archive was loaded, nothing looks like a duplicate yet
+        let rows = preview(&source, &source.guess_mapping(), &[]);
+        assert!(!rows[0].duplicate);
+        let summary = import(&db, rows).await.unwrap();
+        assert_eq!((summary.imported, summary.skipped_duplicates), (1, 2));
+        assert!(summary.failed.is_empty());
+        assert_eq!(db.get_entries().await.unwrap().len(), 2);
+    }
+}
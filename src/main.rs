@@ -3,24 +3,31 @@ use anyhow::{
     Context,
     Result,
 };
-use iced::{
-    pure::Application,
-    Settings,
-};
+use clap::Parser;
+use iced::pure::Application;
 use tracing_subscriber::{
+    filter::LevelFilter,
     fmt,
     prelude::__tracing_subscriber_SubscriberExt,
-    util::SubscriberInitExt,
     EnvFilter,
+    Layer,
 };
 
 pub mod app;
+pub mod backup;
+pub mod cli;
 pub type AppTime = chrono::NaiveDateTime;
 pub fn now() -> AppTime {
     chrono::Local::now().naive_local()
 }
 const FS_CONCURRENCY_LIMIT: usize = 128;
 pub mod db;
+pub mod export;
+pub mod import;
+pub mod migrations;
+pub mod reports;
+pub mod settings;
+pub mod storage;
 pub mod filesystem {
     use std::path::PathBuf;
 
@@ -38,12 +45,23 @@ pub mod filesystem {
     }
 }
 fn main() -> Result<()> {
-    let logs_dir = filesystem::base_directory()?.join("logs");
+    let cli = cli::Cli::parse();
+    // commands print their results to stdout, so the console only gets warnings there
+    let console_level = match cli.command {
+        Some(_) => LevelFilter::WARN,
+        None => LevelFilter::TRACE,
+    };
+    let settings = cli.settings()?;
+    let logs_dir = settings.logs_dir()?;
     let file_appender = tracing_appender::rolling::daily(&logs_dir, "log.txt");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
     let subscriber = tracing_subscriber::registry()
         .with(EnvFilter::from_default_env().add_directive(tracing::Level::TRACE.into()))
-        .with(fmt::Layer::new().with_writer(std::io::stdout))
+        .with(
+            fmt::Layer::new()
+                .with_writer(std::io::stderr)
+                .with_filter(console_level),
+        )
         .with(
             fmt::Layer::new()
                 .compact()
@@ -52,6 +70,41 @@ fn main() -> Result<()> {
         );
     tracing::subscriber::set_global_default(subscriber)
         .context("Unable to set a global subscriber")?;
-    app::ArchiwumZ::run(Settings::default()).context("błąd??")?;
+    match cli.command {
+        Some(command) => cli::run(settings, command)?,
+        None => app::ArchiwumZ::run(iced::Settings::with_flags(settings)).context("błąd??")?,
+    }
     Ok(())
 }
+
+/// scratch folders for the tests that work on archives
+#[cfg(test)]
+pub mod testing {
+    use std::path::{
+        Path,
+        PathBuf,
+    };
+
+    /// removed together with its contents when dropped
+    pub struct TempDir(PathBuf);
+
+    impl Default for TempDir {
+        fn default() -> Self {
+            let dir = std::env::temp_dir().join(format!("archiwumz-{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).expect("tworzenie folderu tymczasowego");
+            Self(dir)
+        }
+    }
+
+    impl TempDir {
+        pub fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+use toml::Value;
+
+/// version of the contract document written by this build, bump it together with a new
+/// entry in [`MIGRATIONS`] whenever a change to [`crate::models::RepairContract`] would make
+/// older files fail to deserialize
+pub const SCHEMA_VERSION: i64 = 3;
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+type Migration = fn(Value) -> Result<Value>;
+
+/// `MIGRATIONS[n]` upgrades a document from version `n + 1` to `n + 2`
+const MIGRATIONS: [Migration; (SCHEMA_VERSION - 1) as usize] = [unwrap_entry, tag_customer];
+
+/// documents without a version predate versioning and count as version 1
+pub fn document_version(document: &Value) -> i64 {
+    document
+        .get(SCHEMA_VERSION_KEY)
+        .and_then(Value::as_integer)
+        .unwrap_or(1)
+}
+
+/// runs every migration the document needs, the result carries the current version
+pub fn upgrade(mut document: Value) -> Result<Value> {
+    let version = document_version(&document);
+    if version > SCHEMA_VERSION {
+        bail!("zlecenie zapisane w wersji {version}, a ten program obsługuje do wersji {SCHEMA_VERSION} - zaktualizuj program");
+    }
+    for (index, migration) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .skip((version - 1).max(0) as usize)
+    {
+        document = migration(document)
+            .with_context(|| format!("migracja zlecenia z wersji {}", index + 1))?;
+    }
+    set_version(&mut document)?;
+    Ok(document)
+}
+
+pub fn set_version(document: &mut Value) -> Result<()> {
+    document
+        .as_table_mut()
+        .context("zlecenie nie jest tabelą TOML")?
+        .insert(
+            SCHEMA_VERSION_KEY.to_owned(),
+            Value::Integer(SCHEMA_VERSION),
+        );
+    Ok(())
+}
+
+/// 1 -> 2: early builds wrote the whole archive entry (`path` and a `[model]` table)
+/// instead of the contract itself
+fn unwrap_entry(document: Value) -> Result<Value> {
+    match document {
+        Value::Table(mut table) if !table.contains_key("id") && table.contains_key("model") => {
+            table.remove("model").context("brak tabeli [model]")
+        }
+        document => Ok(document),
+    }
+}
+
+/// 2 -> 3: the customer was untagged and told apart by the presence of `tax_number`
+fn tag_customer(mut document: Value) -> Result<Value> {
+    if let Some(Value::Table(customer)) = document
+        .get_mut("info")
+        .and_then(|info| info.get_mut("customer"))
+    {
+        if !customer.contains_key("type") {
+            let kind = match customer.contains_key("tax_number") {
+                true => "company",
+                false => "private",
+            };
+            customer.insert("type".to_owned(), Value::String(kind.to_owned()));
+        }
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        Company,
+        Customer,
+        RepairContract,
+    };
+
+    fn company_contract() -> RepairContract {
+        let mut contract = RepairContract::default();
+        contract.info.customer = Customer::Company(Company {
+            name: "Serwis".to_owned(),
+            tax_number: "1234567890".to_owned(),
+            phone: "600100200".to_owned(),
+        });
+        contract
+    }
+
+    fn current(contract: &RepairContract) -> Value {
+        toml::from_str(&contract.to_toml().unwrap()).unwrap()
+    }
+
+    /// the contract as written before the customer got its `type` tag
+    fn v2(contract: &RepairContract) -> Value {
+        let mut document = current(contract);
+        document["info"]["customer"]
+            .as_table_mut()
+            .unwrap()
+            .remove("type");
+        document[SCHEMA_VERSION_KEY] = Value::Integer(2);
+        document
+    }
+
+    /// the whole archive entry, without any version
+    fn v1(contract: &RepairContract) -> Value {
+        let mut model = v2(contract);
+        model.as_table_mut().unwrap().remove(SCHEMA_VERSION_KEY);
+        let mut entry = toml::value::Table::new();
+        entry.insert(
+            "path".to_owned(),
+            Value::String("2020-01-01 12:00:00.repair-contract.toml".to_owned()),
+        );
+        entry.insert("model".to_owned(), model);
+        Value::Table(entry)
+    }
+
+    fn customer_type(document: &Value) -> Option<&str> {
+        document["info"]["customer"].get("type")?.as_str()
+    }
+
+    #[test]
+    fn unwrap_entry_takes_the_model() {
+        let contract = company_contract();
+        let unwrapped = unwrap_entry(v1(&contract)).unwrap();
+        assert!(unwrapped.get("path").is_none());
+        assert_eq!(unwrapped["id"].as_str(), Some(contract.id.to_string().as_str()));
+        // a contract that already is one is left alone
+        assert_eq!(unwrap_entry(v2(&contract)).unwrap(), v2(&contract));
+    }
+
+    #[test]
+    fn tag_customer_tells_companies_apart() {
+        let company = tag_customer(v2(&company_contract())).unwrap();
+        assert_eq!(customer_type(&company), Some("company"));
+        let private = tag_customer(v2(&RepairContract::default())).unwrap();
+        assert_eq!(customer_type(&private), Some("private"));
+        // an existing tag wins over the heuristic
+        let mut tagged = v2(&company_contract());
+        tagged["info"]["customer"]
+            .as_table_mut()
+            .unwrap()
+            .insert("type".to_owned(), Value::String("private".to_owned()));
+        let tagged = tag_customer(tagged).unwrap();
+        assert_eq!(customer_type(&tagged), Some("private"));
+    }
+
+    #[test]
+    fn upgrade_to_current_version() {
+        let contract = company_contract();
+        for document in [v1(&contract), v2(&contract)] {
+            assert!(document_version(&document) < SCHEMA_VERSION);
+            let upgraded = upgrade(document).unwrap();
+            assert_eq!(document_version(&upgraded), SCHEMA_VERSION);
+            assert_eq!(upgraded, current(&contract));
+            let upgraded = upgraded.try_into::<RepairContract>().unwrap();
+            assert_eq!(upgraded.id, contract.id);
+            assert_eq!(upgraded.info.customer.tax_number(), Some("1234567890"));
+        }
+    }
+
+    #[test]
+    fn current_document_is_untouched() {
+        let document = current(&company_contract());
+        assert_eq!(document_version(&document), SCHEMA_VERSION);
+        assert_eq!(upgrade(document.clone()).unwrap(), document);
+
+        let mut newer = document;
+        newer[SCHEMA_VERSION_KEY] = Value::Integer(SCHEMA_VERSION + 1);
+        assert!(upgrade(newer).is_err());
+    }
+}
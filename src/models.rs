@@ -7,6 +7,7 @@ use serde::{
 use uuid::Uuid;
 
 use derive_more::From;
+use iced_forms_derive::IcedFormDerive;
 use std::default::Default;
 use std::{
     path::Path,
@@ -71,23 +72,69 @@ pub trait Validate: Sized {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+/// error of [`Validate::check`] shown under the field at `path`, e.g. `info.customer.phone`
+fn invalid_field(path: &str, message: &str) -> anyhow::Error {
+    match path.parse() {
+        Ok(selector) => iced_forms::FieldError::new(selector, message).into(),
+        Err(e) => anyhow::Error::new(e).context(message.to_owned()),
+    }
+}
+
+/// [`Validate::check`] as a document validator of a form editing `T`, see
+/// [`iced_forms::Validators::document`]; errors that don't come from [`invalid_field`] are
+/// shown for the whole form and values that don't deserialize are left to their fields
+pub fn form_errors<T>(document: &serde_json::Value) -> Vec<iced_forms::FieldError>
+where
+    T: Validate + serde::de::DeserializeOwned,
+{
+    serde_json::from_value::<T>(document.clone())
+        .ok()
+        .and_then(|value| value.check().err())
+        .map(|e| match e.downcast::<iced_forms::FieldError>() {
+            Ok(error) => error,
+            Err(e) => iced_forms::FieldError::new(Default::default(), format!("{e:#}")),
+        })
+        .into_iter()
+        .collect()
+}
+
+/// rules of a form editing `T`, checked while typing; attach them once, where the form is
+/// opened
+pub fn form_validators<T>() -> iced_forms::Validators
+where
+    T: Validate + serde::de::DeserializeOwned + 'static,
+{
+    iced_forms::Validators::default().document(form_errors::<T>)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IcedFormDerive)]
 pub struct Company {
+    #[iced_form(label = "nazwa")]
     pub name: String,
+    #[iced_form(label = "NIP")]
     pub tax_number: String,
+    #[iced_form(label = "telefon")]
     pub phone: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IcedFormDerive)]
 pub struct PrivateCustomer {
+    #[iced_form(label = "imię i nazwisko")]
     pub name: String,
+    #[iced_form(label = "telefon")]
     pub phone: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, From)]
-#[serde(untagged)]
+/// the `type` key decides the variant, files written before it was introduced get it
+/// from [`crate::migrations`]
+#[derive(Serialize, Deserialize, Debug, Clone, From, IcedFormDerive)]
+#[serde(tag = "type")]
 pub enum Customer {
+    #[serde(rename = "private")]
+    #[iced_form(label = "osoba prywatna")]
     PrivateCustomer(PrivateCustomer),
+    #[serde(rename = "company")]
+    #[iced_form(label = "firma")]
     Company(Company),
 }
 
@@ -97,17 +144,23 @@ impl Default for Customer {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IcedFormDerive)]
 pub struct ReplacementPart {
+    #[iced_form(label = "kod")]
     pub id: String,
+    #[iced_form(label = "nazwa")]
     pub name: String,
+    #[iced_form(label = "cena")]
     pub price: Decimal,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IcedFormDerive)]
 pub struct PerformedRepair {
+    #[iced_form(label = "kod")]
     pub id: String,
+    #[iced_form(label = "nazwa")]
     pub name: String,
+    #[iced_form(label = "cena")]
     pub price: Decimal,
 }
 
@@ -118,12 +171,21 @@ pub mod protocols {
 
     use super::*;
 
-    #[derive(Serialize, Deserialize, Debug, Clone)]
+    /// filled in at the handover, see [`RepairContract::final_protocol`]
+    #[derive(Serialize, Deserialize, Debug, Clone, IcedFormDerive)]
     pub struct FinalProtocol {
+        #[iced_form(label = "data wydania", placeholder = "data wydania")]
         pub date: AppTime,
+        #[iced_form(label = "cena końcowa", order = 1)]
         pub final_price: Decimal,
+        #[iced_form(label = "wykonane naprawy")]
         pub performed_repairs: Vec<PerformedRepair>,
+        #[iced_form(label = "wymienione części")]
         pub parts_replaced: Vec<ReplacementPart>,
+        /// customer signature confirming the device was collected
+        #[serde(default)]
+        #[iced_form(skip)]
+        pub customer_signature: Option<Signature>,
     }
 
     impl Default for FinalProtocol {
@@ -133,6 +195,7 @@ pub mod protocols {
                 final_price: Default::default(),
                 performed_repairs: Default::default(),
                 parts_replaced: Default::default(),
+                customer_signature: Default::default(),
             }
         }
     }
@@ -153,15 +216,109 @@ impl Default for ReplacementDevice {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, From, Default)]
+pub mod signature {
+    use std::fmt::Write;
+
+    use super::*;
+
+    pub type SignaturePoint = (f32, f32);
+    pub type SignatureStroke = Vec<SignaturePoint>;
+    /// width and height of the pad, the points of the strokes are within it
+    pub const PAD_SIZE: (u16, u16) = (500, 150);
+
+    /// handwritten signature stored as vector strokes,
+    /// serialized as SVG path data (`M x,y L x,y ...`) so it can be embedded in documents directly
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct Signature {
+        pub strokes: Vec<SignatureStroke>,
+    }
+
+    impl Signature {
+        pub fn is_empty(&self) -> bool {
+            self.strokes.iter().all(|stroke| stroke.is_empty())
+        }
+
+        pub fn to_svg_path(&self) -> String {
+            let mut path = String::new();
+            for stroke in self.strokes.iter().filter(|stroke| !stroke.is_empty()) {
+                for (index, (x, y)) in stroke.iter().enumerate() {
+                    let command = if index == 0 { 'M' } else { 'L' };
+                    if !path.is_empty() {
+                        path.push(' ');
+                    }
+                    write!(path, "{command}{x:.1},{y:.1}").expect("writing to a string");
+                }
+            }
+            path
+        }
+
+        /// standalone SVG document, ready to be embedded in printed documents
+        pub fn to_svg(&self, width: f32, height: f32) -> String {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}"><path d="{}" fill="none" stroke="black" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"/></svg>"#,
+                self.to_svg_path()
+            )
+        }
+    }
+
+    impl FromStr for Signature {
+        type Err = anyhow::Error;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let mut strokes: Vec<SignatureStroke> = vec![];
+            for command in s.split_whitespace() {
+                let mut chars = command.chars();
+                let kind = chars.next();
+                let point = chars.as_str();
+                let (x, y) = point
+                    .split_once(',')
+                    .with_context(|| format!("zły punkt podpisu [{command}]"))?;
+                let point = (
+                    x.parse()
+                        .with_context(|| format!("zła współrzędna [{x}]"))?,
+                    y.parse()
+                        .with_context(|| format!("zła współrzędna [{y}]"))?,
+                );
+                match (kind, strokes.last_mut()) {
+                    (Some('M'), _) => strokes.push(vec![point]),
+                    (Some('L'), Some(stroke)) => stroke.push(point),
+                    _ => anyhow::bail!("nieznane polecenie podpisu [{command}]"),
+                }
+            }
+            Ok(Self { strokes })
+        }
+    }
+
+    impl Serialize for Signature {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&self.to_svg_path())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Signature {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            String::deserialize(deserializer)?
+                .parse()
+                .map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+pub use signature::Signature;
+
+#[derive(Serialize, Deserialize, Debug, Clone, From, Default, IcedFormDerive)]
 pub struct Device {
+    #[iced_form(label = "model")]
     pub model_name: String,
+    #[iced_form(label = "numer seryjny")]
     pub serial_number: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, IcedFormDerive)]
 pub struct ClientContactEvent {
+    #[iced_form(label = "data")]
     pub date: AppTime,
+    #[iced_form(label = "notatka", multiline)]
     pub note: String,
 }
 
@@ -174,36 +331,90 @@ impl Default for ClientContactEvent {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, IcedFormDerive)]
 pub struct RepairContractInfo {
+    #[iced_form(label = "klient")]
     pub customer: Customer,
+    /// device left for repair
+    #[serde(default)]
+    #[iced_form(label = "urządzenie")]
+    pub device: Device,
+    #[iced_form(label = "czas naprawy (dni robocze)", order = 1, group = "wycena")]
     pub expected_repair_time_work_days: i64,
+    #[iced_form(
+        label = "wstępna cena",
+        order = 1,
+        group = "wycena",
+        help = "orientacyjna, ostateczną podaje protokół odbioru"
+    )]
     pub prognosis_price: Decimal,
+    #[iced_form(label = "opis usterki")]
     pub description: Vec<String>,
+    #[iced_form(label = "uwagi", multiline, order = 2)]
     pub notes: String,
+    #[iced_form(label = "widoczne uszkodzenia")]
     pub visible_damages: Vec<String>,
 }
 
-/// this is the main app model
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// this is the main app model; the form shows what is filled in at the intake, the rest has
+/// its own pages
+#[derive(Serialize, Deserialize, Debug, Clone, IcedFormDerive)]
 pub struct RepairContract {
+    /// see [`crate::migrations`]
+    #[serde(default)]
+    #[iced_form(skip)]
+    pub schema_version: i64,
+    #[iced_form(label = "numer zlecenia", readonly, order = -2)]
     pub id: Uuid,
+    #[iced_form(label = "data przyjęcia", placeholder = "data przyjęcia", order = -1)]
     pub date: AppTime,
+    #[iced_form(label = "zlecenie")]
     pub info: RepairContractInfo,
+    #[iced_form(label = "kontakt z klientem", order = 1)]
     pub client_contact_events: Vec<ClientContactEvent>,
+    #[iced_form(skip)]
     pub replacement_device: Option<ReplacementDevice>,
+    /// filled in at the handover
+    #[iced_form(skip)]
     pub final_protocol: Option<protocols::FinalProtocol>,
+    /// customer signature accepting the intake terms
+    #[serde(default)]
+    #[iced_form(skip)]
+    pub customer_signature: Option<Signature>,
+    /// bumped on every save, a mismatch means someone else saved the contract in the meantime
+    #[serde(default)]
+    #[iced_form(skip)]
+    pub revision: u64,
+    /// hidden from the default list, e.g. jobs closed years ago
+    #[serde(default)]
+    #[iced_form(skip)]
+    pub archived: bool,
+    /// set while the contract sits in the trash
+    #[serde(default)]
+    #[iced_form(skip)]
+    pub deleted: Option<Deletion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Deletion {
+    pub date: AppTime,
+    pub reason: String,
 }
 
 impl Default for RepairContract {
     fn default() -> Self {
         Self {
+            schema_version: crate::migrations::SCHEMA_VERSION,
             id: uuid::Uuid::new_v4(),
             date: crate::now(),
             info: Default::default(),
             client_contact_events: Default::default(),
             replacement_device: Default::default(),
             final_protocol: Default::default(),
+            customer_signature: Default::default(),
+            revision: Default::default(),
+            archived: Default::default(),
+            deleted: Default::default(),
         }
     }
 }
@@ -211,15 +422,203 @@ impl Default for RepairContract {
 impl FromStr for RepairContract {
     type Err = anyhow::Error;
 
+    /// older documents are upgraded before typed deserialization
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        toml::from_str(s).with_context(|| "parsing contents")
+        let document = toml::from_str::<toml::Value>(s).with_context(|| "parsing contents")?;
+        crate::migrations::upgrade(document)?
+            .try_into()
+            .with_context(|| "parsing contents")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ContractStatus {
+    Open,
+    Closed,
+}
+
+impl std::fmt::Display for ContractStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContractStatus::Open => "otwarte",
+            ContractStatus::Closed => "zamknięte",
+        }
+        .fmt(f)
+    }
+}
+
+impl Customer {
+    pub fn name(&self) -> &str {
+        match self {
+            Customer::PrivateCustomer(customer) => &customer.name,
+            Customer::Company(company) => &company.name,
+        }
+    }
+
+    pub fn phone(&self) -> &str {
+        match self {
+            Customer::PrivateCustomer(customer) => &customer.phone,
+            Customer::Company(company) => &company.phone,
+        }
+    }
+
+    pub fn tax_number(&self) -> Option<&str> {
+        match self {
+            Customer::PrivateCustomer(_) => None,
+            Customer::Company(company) => Some(&company.tax_number),
+        }
+    }
+}
+
+/// what a contract needs before it's saved for the first time
+impl Validate for RepairContract {
+    fn check(&self) -> Result<()> {
+        let customer = &self.info.customer;
+        if customer.name().trim().is_empty() {
+            return Err(invalid_field("info.customer.name", "podaj nazwę klienta"));
+        }
+        if customer.phone().trim().is_empty() {
+            return Err(invalid_field(
+                "info.customer.phone",
+                "podaj telefon klienta",
+            ));
+        }
+        if self.info.expected_repair_time_work_days < 0 {
+            return Err(invalid_field(
+                "info.expected_repair_time_work_days",
+                "czas naprawy nie może być ujemny",
+            ));
+        }
+        if self.info.prognosis_price.is_sign_negative() {
+            return Err(invalid_field(
+                "info.prognosis_price",
+                "cena nie może być ujemna",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Validate for protocols::FinalProtocol {
+    fn check(&self) -> Result<()> {
+        if self.final_price.is_sign_negative() {
+            return Err(invalid_field("final_price", "cena nie może być ujemna"));
+        }
+        let repairs = self
+            .performed_repairs
+            .iter()
+            .enumerate()
+            .map(|(index, repair)| ("performed_repairs", index, &repair.name, repair.price));
+        let parts = self
+            .parts_replaced
+            .iter()
+            .enumerate()
+            .map(|(index, part)| ("parts_replaced", index, &part.name, part.price));
+        for (field, index, name, price) in repairs.chain(parts) {
+            if name.trim().is_empty() {
+                return Err(invalid_field(&format!("{field}[{index}].name"), "podaj nazwę"));
+            }
+            if price.is_sign_negative() {
+                return Err(invalid_field(
+                    &format!("{field}[{index}].price"),
+                    "cena nie może być ujemna",
+                ));
+            }
+        }
+        Ok(())
     }
 }
 
 impl RepairContract {
+    /// a contract is closed once the final protocol has been filled in
+    pub fn status(&self) -> ContractStatus {
+        match self.final_protocol {
+            Some(_) => ContractStatus::Closed,
+            None => ContractStatus::Open,
+        }
+    }
+
+    /// toml refuses plain values placed after tables, so the model is routed through [`toml::Value`]
+    /// which always emits tables last
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        let mut document = toml::Value::try_from(self)
+            .with_context(|| format!("serializacja modelu {self:#?}"))?;
+        crate::migrations::set_version(&mut document)?;
+        toml::to_string_pretty(&document).with_context(|| format!("serializacja modelu {self:#?}"))
+    }
+
     pub fn from_file(path: &Path) -> anyhow::Result<Self> {
         std::fs::read_to_string(path)
             .with_context(|| format!("reading {path:?}"))
             .and_then(|contents| Self::from_str(&contents))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_round_trip() {
+        let signature = Signature {
+            strokes: vec![vec![(1.5, 2.0), (10.0, 20.5), (30.0, 12.5)], vec![(50.0, 60.0)]],
+        };
+        assert_eq!(signature.to_svg_path(), "M1.5,2.0 L10.0,20.5 L30.0,12.5 M50.0,60.0");
+        assert_eq!(signature.to_svg_path().parse::<Signature>().unwrap(), signature);
+        assert!("L1.0,2.0".parse::<Signature>().is_err());
+
+        let contract = RepairContract {
+            customer_signature: Some(signature.clone()),
+            final_protocol: Some(protocols::FinalProtocol {
+                customer_signature: Some(signature.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let read = contract.to_toml().unwrap().parse::<RepairContract>().unwrap();
+        assert_eq!(read.customer_signature, Some(signature.clone()));
+        assert_eq!(read.final_protocol.unwrap().customer_signature, Some(signature));
+    }
+
+    #[test]
+    fn contract_form_edits_reach_nested_fields() {
+        use iced_forms::IcedForm;
+        let mut contract = RepairContract {
+            revision: 3,
+            ..Default::default()
+        };
+        let mut set = |path: &str, value: serde_json::Value| {
+            let selector = path.parse::<iced_forms::Selector>().unwrap();
+            IcedForm::<()>::set_at(&mut contract, selector.segments(), value)
+        };
+        set("info.device.serial_number", serde_json::json!("SN1")).unwrap();
+        set("date", serde_json::json!("2023-01-04T10:00:00")).unwrap();
+        assert!(set("date", serde_json::json!("wczoraj")).is_err());
+        assert_eq!(contract.info.device.serial_number, "SN1");
+        assert_eq!(contract.date.to_string(), "2023-01-04 10:00:00");
+        assert_eq!(contract.revision, 3);
+    }
+
+    #[test]
+    fn final_protocol_errors_point_at_their_fields() {
+        let at = |path: &str| path.parse::<iced_forms::Selector>().unwrap();
+        let errors = |protocol: &protocols::FinalProtocol| {
+            form_errors::<protocols::FinalProtocol>(&serde_json::to_value(protocol).unwrap())
+                .iter()
+                .map(|error| error.selector.clone())
+                .collect::<Vec<_>>()
+        };
+        let mut protocol = protocols::FinalProtocol {
+            performed_repairs: vec![PerformedRepair {
+                name: "wymiana ekranu".to_owned(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(errors(&protocol).is_empty());
+        protocol.parts_replaced.push(ReplacementPart::default());
+        assert_eq!(errors(&protocol), [at("parts_replaced[0].name")]);
+        protocol.performed_repairs[0].price = Decimal::NEGATIVE_ONE;
+        assert_eq!(errors(&protocol), [at("performed_repairs[0].price")]);
+    }
+}
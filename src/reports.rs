@@ -0,0 +1,216 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::Path,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use chrono::Datelike;
+use itertools::Itertools;
+use rust_decimal::Decimal;
+
+use crate::{
+    db::RepairContractEntry,
+    AppTime,
+};
+
+const TOP_DEVICE_MODELS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Month {
+    pub year: i32,
+    pub month: u32,
+}
+
+impl From<&AppTime> for Month {
+    fn from(date: &AppTime) -> Self {
+        Self {
+            year: date.year(),
+            month: date.month(),
+        }
+    }
+}
+
+impl Display for Month {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MonthlyStats {
+    pub opened: usize,
+    pub closed: usize,
+    /// sum of [`crate::models::protocols::FinalProtocol::final_price`] of jobs closed that month
+    pub revenue: Decimal,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub monthly: BTreeMap<Month, MonthlyStats>,
+    /// average time between accepting the device and the final protocol
+    pub average_turnaround_days: Option<f64>,
+    pub top_device_models: Vec<(String, usize)>,
+    pub parts_total: Decimal,
+    pub labour_total: Decimal,
+}
+
+impl Report {
+    pub fn from_entries(entries: &[RepairContractEntry]) -> Self {
+        let mut report = Self::default();
+        let mut turnarounds = vec![];
+        for contract in entries.iter().map(|entry| &entry.model) {
+            report
+                .monthly
+                .entry(Month::from(&contract.date))
+                .or_default()
+                .opened += 1;
+            if let Some(protocol) = &contract.final_protocol {
+                let stats = report
+                    .monthly
+                    .entry(Month::from(&protocol.date))
+                    .or_default();
+                stats.closed += 1;
+                stats.revenue += protocol.final_price;
+                turnarounds.push((protocol.date - contract.date).num_minutes() as f64 / 60. / 24.);
+                report.parts_total += protocol
+                    .parts_replaced
+                    .iter()
+                    .map(|part| part.price)
+                    .sum::<Decimal>();
+                report.labour_total += protocol
+                    .performed_repairs
+                    .iter()
+                    .map(|repair| repair.price)
+                    .sum::<Decimal>();
+            }
+        }
+        report.average_turnaround_days = (!turnarounds.is_empty())
+            .then(|| turnarounds.iter().sum::<f64>() / turnarounds.len() as f64);
+        report.top_device_models = entries
+            .iter()
+            .map(|entry| entry.model.info.device.model_name.trim())
+            .filter(|model_name| !model_name.is_empty())
+            .counts()
+            .into_iter()
+            .map(|(model_name, count)| (model_name.to_owned(), count))
+            .sorted_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)))
+            .take(TOP_DEVICE_MODELS)
+            .collect();
+        report
+    }
+
+    /// long format (`sekcja`, `klucz`, `wartość`) so every statistic fits in a single sheet
+    pub fn write_csv(&self, path: &Path) -> Result<()> {
+        let mut writer = csv::Writer::from_path(path)
+            .with_context(|| format!("tworzenie pliku raportu {path:?}"))?;
+        writer.write_record(["sekcja", "klucz", "wartość"])?;
+        for (month, stats) in &self.monthly {
+            let month = month.to_string();
+            writer.write_record(["otwarte zlecenia", &month, &stats.opened.to_string()])?;
+            writer.write_record(["zamknięte zlecenia", &month, &stats.closed.to_string()])?;
+            writer.write_record(["przychód", &month, &stats.revenue.to_string()])?;
+        }
+        if let Some(days) = self.average_turnaround_days {
+            writer.write_record(["średni czas naprawy", "dni", &format!("{days:.1}")])?;
+        }
+        for (model_name, count) in &self.top_device_models {
+            writer.write_record(["najczęstsze modele", model_name, &count.to_string()])?;
+        }
+        writer.write_record(["podział przychodu", "części", &self.parts_total.to_string()])?;
+        writer.write_record([
+            "podział przychodu",
+            "robocizna",
+            &self.labour_total.to_string(),
+        ])?;
+        writer
+            .flush()
+            .with_context(|| format!("zapisywanie raportu {path:?}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        protocols::FinalProtocol,
+        PerformedRepair,
+        RepairContract,
+        ReplacementPart,
+    };
+
+    fn date(date: &str) -> AppTime {
+        format!("{date}T12:00:00").parse().unwrap()
+    }
+
+    /// `closed` is the date of the final protocol, the labour and the parts price
+    fn entry(
+        accepted: &str,
+        device: &str,
+        closed: Option<(&str, i64, i64)>,
+    ) -> RepairContractEntry {
+        let mut model = RepairContract {
+            date: date(accepted),
+            ..Default::default()
+        };
+        model.info.device.model_name = device.to_owned();
+        model.final_protocol = closed.map(|(closed, labour, parts)| FinalProtocol {
+            date: date(closed),
+            final_price: Decimal::from(labour + parts),
+            performed_repairs: vec![PerformedRepair {
+                id: "r".to_owned(),
+                name: "naprawa".to_owned(),
+                price: Decimal::from(labour),
+            }],
+            parts_replaced: vec![ReplacementPart {
+                id: "c".to_owned(),
+                name: "część".to_owned(),
+                price: Decimal::from(parts),
+            }],
+            customer_signature: None,
+        });
+        RepairContractEntry {
+            path: Default::default(),
+            model,
+        }
+    }
+
+    #[test]
+    fn monthly_grouping_and_totals() {
+        let report = Report::from_entries(&[
+            entry("2024-01-10", "X1", Some(("2024-01-12", 100, 50))),
+            entry("2024-01-30", "X1", Some(("2024-02-03", 200, 0))),
+            entry("2024-02-01", " Y2 ", None),
+            entry("2024-03-05", "", None),
+        ]);
+        let month = |year, month| report.monthly[&Month { year, month }].clone();
+        assert_eq!(report.monthly.len(), 3);
+        let january = month(2024, 1);
+        assert_eq!((january.opened, january.closed), (2, 1));
+        assert_eq!(january.revenue, Decimal::from(150));
+        // closed jobs count in the month of the final protocol
+        let february = month(2024, 2);
+        assert_eq!((february.opened, february.closed), (1, 1));
+        assert_eq!(february.revenue, Decimal::from(200));
+        assert_eq!(month(2024, 3).revenue, Decimal::ZERO);
+
+        assert_eq!(report.labour_total, Decimal::from(300));
+        assert_eq!(report.parts_total, Decimal::from(50));
+        assert_eq!(report.average_turnaround_days, Some(3.));
+        assert_eq!(
+            report.top_device_models,
+            vec![("X1".to_owned(), 2), ("Y2".to_owned(), 1)]
+        );
+    }
+
+    #[test]
+    fn empty_archive() {
+        let report = Report::from_entries(&[]);
+        assert!(report.monthly.is_empty());
+        assert_eq!(report.average_turnaround_days, None);
+        assert_eq!(report.parts_total, Decimal::ZERO);
+    }
+}
@@ -0,0 +1,378 @@
+use std::path::PathBuf;
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+use directories::ProjectDirs;
+use rust_decimal::Decimal;
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    export::ContractSort,
+    models::RepairContract,
+};
+
+const SETTINGS_FILE_NAME: &str = "settings.toml";
+const MAX_RECENT_ARCHIVES: usize = 5;
+/// name of the archive kept in [`AppSettings::archive_dir`]
+pub const DEFAULT_ARCHIVE_NAME: &str = "domyślne";
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct CompanyDetails {
+    pub name: String,
+    pub address: String,
+    pub tax_number: String,
+    pub phone: String,
+    pub email: String,
+}
+
+/// values prefilled in every new contract
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct DefaultPrices {
+    pub prognosis_price: Decimal,
+    pub expected_repair_time_work_days: i64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    /// when missing backups go to the `kopie` folder in the data dir
+    pub dir: Option<PathBuf>,
+    /// a new backup is made once the newest one is older than that
+    pub interval_hours: u64,
+    /// number of backups kept per archive, older ones are removed
+    pub keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: None,
+            interval_hours: 24,
+            keep: 14,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+pub struct NamedArchive {
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+/// contents of the settings file
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct AppSettings {
+    /// folder with the contracts, when missing the default location is used
+    pub archive_dir: Option<PathBuf>,
+    pub logs_dir: Option<PathBuf>,
+    pub company: CompanyDetails,
+    pub default_prices: DefaultPrices,
+    pub backup: BackupSettings,
+    /// archives other than the default one, e.g. a second business or a test archive
+    pub archives: Vec<NamedArchive>,
+    /// names of recently opened archives, most recent first; the first one is opened on start
+    pub recent_archives: Vec<String>,
+    /// last order chosen on the contracts list
+    pub contracts_sort: ContractSort,
+}
+
+/// values given on the command line or in the environment, they win over the settings file
+#[derive(Debug, Clone, Default)]
+pub struct SettingsOverrides {
+    /// name of one of the configured archives
+    pub archive: Option<String>,
+    pub archive_dir: Option<PathBuf>,
+    pub logs_dir: Option<PathBuf>,
+    pub company_name: Option<String>,
+    pub company_address: Option<String>,
+    pub company_tax_number: Option<String>,
+    pub company_phone: Option<String>,
+    pub company_email: Option<String>,
+    pub prognosis_price: Option<Decimal>,
+    pub expected_repair_time_work_days: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub path: PathBuf,
+    pub values: AppSettings,
+    pub overrides: SettingsOverrides,
+}
+
+fn project_dirs() -> Result<ProjectDirs> {
+    ProjectDirs::from("", "", "archiwumz").context("nie udało się ustalić folderu domowego")
+}
+
+pub fn default_settings_path() -> Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join(SETTINGS_FILE_NAME))
+}
+
+impl Settings {
+    /// reads the settings file, a missing file means default settings
+    pub fn load(path: Option<PathBuf>, overrides: SettingsOverrides) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None => default_settings_path()?,
+        };
+        let values = match path.exists() {
+            true => {
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("odczytywanie ustawień z {path:?}"))?;
+                toml::from_str(&content)
+                    .with_context(|| format!("parsowanie ustawień z {path:?}"))?
+            }
+            false => AppSettings::default(),
+        };
+        Ok(Self {
+            path,
+            values,
+            overrides,
+        })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir).with_context(|| format!("tworzenie folderu {dir:?}"))?;
+        }
+        let content = toml::Value::try_from(&self.values)
+            .and_then(|value| toml::to_string_pretty(&value))
+            .context("serializacja ustawień")?;
+        std::fs::write(&self.path, content)
+            .with_context(|| format!("zapisywanie ustawień do {:?}", self.path))
+    }
+
+    /// folder for everything the app produces: the archive, logs, reports and exports.
+    /// installations that already keep `archiwum` next to the executable keep using it
+    pub fn data_dir(&self) -> Result<PathBuf> {
+        let legacy = crate::filesystem::base_directory()?;
+        match legacy.join("archiwum").exists() {
+            true => Ok(legacy),
+            false => Ok(project_dirs()?.data_dir().to_owned()),
+        }
+    }
+
+    fn resolve(
+        &self,
+        overridden: &Option<PathBuf>,
+        configured: &Option<PathBuf>,
+        default: &str,
+    ) -> Result<PathBuf> {
+        match overridden.as_ref().or(configured.as_ref()) {
+            Some(dir) => Ok(dir.clone()),
+            None => Ok(self.data_dir()?.join(default)),
+        }
+    }
+
+    /// the default archive followed by the ones added in settings
+    pub fn archives(&self) -> Result<Vec<NamedArchive>> {
+        let default = NamedArchive {
+            name: DEFAULT_ARCHIVE_NAME.to_owned(),
+            dir: self.resolve(&None, &self.values.archive_dir, "archiwum")?,
+        };
+        Ok(std::iter::once(default)
+            .chain(self.values.archives.iter().cloned())
+            .collect())
+    }
+
+    /// archive names with the recently used ones first
+    pub fn archive_names(&self) -> Vec<String> {
+        let names = std::iter::once(DEFAULT_ARCHIVE_NAME).chain(
+            self.values
+                .archives
+                .iter()
+                .map(|archive| archive.name.as_str()),
+        );
+        let recent = self
+            .values
+            .recent_archives
+            .iter()
+            .map(String::as_str)
+            .filter(|name| names.clone().any(|other| other == *name));
+        let mut ordered = recent.map(str::to_owned).collect::<Vec<_>>();
+        for name in names {
+            if !ordered.iter().any(|other| other == name) {
+                ordered.push(name.to_owned());
+            }
+        }
+        ordered
+    }
+
+    /// archive to open: the one given on the command line, otherwise the most recently used one
+    pub fn current_archive(&self) -> Result<NamedArchive> {
+        if let Some(dir) = &self.overrides.archive_dir {
+            return Ok(NamedArchive {
+                name: dir.display().to_string(),
+                dir: dir.clone(),
+            });
+        }
+        let mut archives = self.archives()?;
+        if let Some(name) = &self.overrides.archive {
+            return archives
+                .into_iter()
+                .find(|archive| &archive.name == name)
+                .with_context(|| format!("nie ma archiwum o nazwie [{name}]"));
+        }
+        let recent = self
+            .values
+            .recent_archives
+            .first()
+            .and_then(|name| archives.iter().position(|archive| &archive.name == name));
+        Ok(archives.swap_remove(recent.unwrap_or_default()))
+    }
+
+    pub fn archive_dir(&self) -> Result<PathBuf> {
+        Ok(self.current_archive()?.dir)
+    }
+
+    /// remembers `name` as the most recently used archive, command line overrides stop applying
+    pub fn open_archive(&mut self, name: &str) {
+        let recent = &mut self.values.recent_archives;
+        recent.retain(|other| other != name);
+        recent.insert(0, name.to_owned());
+        recent.truncate(MAX_RECENT_ARCHIVES);
+        self.overrides.archive = None;
+        self.overrides.archive_dir = None;
+    }
+
+    pub fn add_archive(&mut self, archive: NamedArchive) -> Result<()> {
+        let name = archive.name.trim();
+        if name.is_empty() || archive.dir.as_os_str().is_empty() {
+            bail!("archiwum musi mieć nazwę i folder");
+        }
+        if self.archive_names().iter().any(|other| other == name) {
+            bail!("archiwum o nazwie [{name}] już istnieje");
+        }
+        self.values.archives.push(NamedArchive {
+            name: name.to_owned(),
+            dir: archive.dir,
+        });
+        Ok(())
+    }
+
+    pub fn remove_archive(&mut self, name: &str) {
+        self.values.archives.retain(|archive| archive.name != name);
+        self.values.recent_archives.retain(|other| other != name);
+    }
+
+    pub fn logs_dir(&self) -> Result<PathBuf> {
+        self.resolve(&self.overrides.logs_dir, &self.values.logs_dir, "logs")
+    }
+
+    /// subfolder of the data dir, created when missing
+    pub fn output_dir(&self, name: &str) -> Result<PathBuf> {
+        let dir = self.data_dir()?.join(name);
+        std::fs::create_dir_all(&dir).with_context(|| format!("tworzenie folderu {dir:?}"))?;
+        Ok(dir)
+    }
+
+    /// backups of every archive go to their own subfolder
+    pub fn backup_dir(&self, archive: &NamedArchive) -> Result<PathBuf> {
+        let dir = match &self.values.backup.dir {
+            Some(dir) => dir.clone(),
+            None => self.data_dir()?.join("kopie"),
+        };
+        let name = archive
+            .name
+            .chars()
+            .map(|c| match c.is_alphanumeric() || c == '-' {
+                true => c,
+                false => '_',
+            })
+            .collect::<String>();
+        Ok(dir.join(name))
+    }
+
+    /// company printed on the contract documents
+    pub fn company(&self) -> CompanyDetails {
+        let overrides = &self.overrides;
+        let company = &self.values.company;
+        let pick = |overridden: &Option<String>, configured: &String| {
+            overridden.clone().unwrap_or_else(|| configured.clone())
+        };
+        CompanyDetails {
+            name: pick(&overrides.company_name, &company.name),
+            address: pick(&overrides.company_address, &company.address),
+            tax_number: pick(&overrides.company_tax_number, &company.tax_number),
+            phone: pick(&overrides.company_phone, &company.phone),
+            email: pick(&overrides.company_email, &company.email),
+        }
+    }
+
+    pub fn default_prices(&self) -> DefaultPrices {
+        let configured = &self.values.default_prices;
+        DefaultPrices {
+            prognosis_price: self
+                .overrides
+                .prognosis_price
+                .unwrap_or(configured.prognosis_price),
+            expected_repair_time_work_days: self
+                .overrides
+                .expected_repair_time_work_days
+                .unwrap_or(configured.expected_repair_time_work_days),
+        }
+    }
+
+    pub fn new_contract(&self) -> RepairContract {
+        let prices = self.default_prices();
+        let mut contract = RepairContract::default();
+        contract.info.prognosis_price = prices.prognosis_price;
+        contract.info.expected_repair_time_work_days = prices.expected_repair_time_work_days;
+        contract
+    }
+}
+
+/// optional paths in [`AppSettings`], as JSON pointers
+const PATH_FIELDS: [&str; 3] = ["/archive_dir", "/logs_dir", "/backup/dir"];
+
+/// the archive list and the list order have their own controls so they are left out of the
+/// settings form
+pub fn to_form(mut value: serde_json::Value) -> serde_json::Value {
+    if let Some(value) = value.as_object_mut() {
+        value.remove("archives");
+        value.remove("recent_archives");
+        value.remove("contracts_sort");
+    }
+    value
+}
+
+/// a path left empty means the default location, same as an unchecked one
+pub fn from_form(mut value: serde_json::Value) -> serde_json::Value {
+    for pointer in PATH_FIELDS {
+        if let Some(path) = value
+            .pointer_mut(pointer)
+            .filter(|path| path.as_str().map(str::trim) == Some(""))
+        {
+            *path = serde_json::Value::Null;
+        }
+    }
+    value
+}
+
+/// rules of the settings form, checked while typing
+pub fn form_validators() -> iced_forms::Validators {
+    let at_least_one = |value: &serde_json::Value| match value.as_u64() {
+        Some(0) => Err("musi wynosić co najmniej 1".to_owned()),
+        _ => Ok(()),
+    };
+    iced_forms::Validators::default()
+        .field(
+            "backup.interval_hours".parse().expect("poprawna ścieżka"),
+            at_least_one,
+        )
+        .field(
+            "backup.keep".parse().expect("poprawna ścieżka"),
+            at_least_one,
+        )
+}
@@ -0,0 +1,291 @@
+use std::{
+    path::Path,
+    sync::Arc,
+};
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+use async_trait::async_trait;
+use tracing::instrument;
+
+use crate::{
+    db::{
+        Database,
+        RepairContractEntry,
+    },
+    models::RepairContract,
+};
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// where the contracts live; the folder of TOML files is the default, a SQLite database
+/// is picked by the extension of the archive path
+#[async_trait]
+pub trait Storage: std::fmt::Debug + Send + Sync {
+    /// contracts that are not in the trash
+    async fn get_entries(&self) -> Result<Vec<RepairContractEntry>>;
+    /// contracts with [`RepairContract::deleted`] set go straight to the trash
+    async fn create_entry(&self, model: RepairContract) -> Result<RepairContractEntry>;
+    /// fails with [`crate::db::Conflict`] when the stored revision differs from the entry's
+    async fn update_entry(&self, entry: RepairContractEntry) -> Result<RepairContractEntry>;
+    /// soft delete, the contract stays recoverable with the given reason attached; like
+    /// restoring and purging it fails while someone else holds a lease on the contract
+    async fn delete_entry(&self, entry: &RepairContractEntry, reason: &str) -> Result<()>;
+    /// deleted contracts, most recently deleted first
+    async fn get_trash(&self) -> Result<Vec<RepairContractEntry>>;
+    /// brings a contract back from the trash
+    async fn restore_entry(&self, entry: &RepairContractEntry) -> Result<RepairContractEntry>;
+    /// removes a contract from the trash for good
+    async fn purge_entry(&self, entry: &RepairContractEntry) -> Result<()>;
+    /// marks the contract as being edited by this app and returns its current content;
+    /// fails when another app holds a fresh [`crate::db::Lease`]
+    async fn acquire_lease(&self, path: &Path) -> Result<RepairContractEntry>;
+    /// keeps the lease from going stale during a long edit
+    async fn refresh_lease(&self, path: &Path) -> Result<()>;
+    async fn release_lease(&self, path: &Path) -> Result<()>;
+}
+
+#[async_trait]
+impl Storage for Database {
+    async fn get_entries(&self) -> Result<Vec<RepairContractEntry>> {
+        Database::get_entries(self).await
+    }
+
+    async fn create_entry(&self, model: RepairContract) -> Result<RepairContractEntry> {
+        Database::create_entry(self, model).await
+    }
+
+    async fn update_entry(&self, entry: RepairContractEntry) -> Result<RepairContractEntry> {
+        Database::update_entry(self, entry).await
+    }
+
+    async fn delete_entry(&self, entry: &RepairContractEntry, reason: &str) -> Result<()> {
+        Database::delete_entry(self, entry, reason)
+            .await
+            .map(|_| ())
+    }
+
+    async fn get_trash(&self) -> Result<Vec<RepairContractEntry>> {
+        Database::get_trash(self).await
+    }
+
+    async fn restore_entry(&self, entry: &RepairContractEntry) -> Result<RepairContractEntry> {
+        Database::restore_entry(self, entry).await
+    }
+
+    async fn purge_entry(&self, entry: &RepairContractEntry) -> Result<()> {
+        Database::purge_entry(self, entry).await
+    }
+
+    async fn acquire_lease(&self, path: &Path) -> Result<RepairContractEntry> {
+        Database::acquire_lease(self, path).await
+    }
+
+    async fn refresh_lease(&self, path: &Path) -> Result<()> {
+        Database::refresh_lease(self, path).await
+    }
+
+    async fn release_lease(&self, path: &Path) -> Result<()> {
+        Database::release_lease(self, path).await
+    }
+}
+
+/// stands in for an archive that could not be opened, every operation reports why
+#[derive(Debug)]
+pub struct Unavailable(pub String);
+
+impl Unavailable {
+    fn error<T>(&self) -> Result<T> {
+        bail!("archiwum jest niedostępne :: {}", self.0)
+    }
+}
+
+#[async_trait]
+impl Storage for Unavailable {
+    async fn get_entries(&self) -> Result<Vec<RepairContractEntry>> {
+        self.error()
+    }
+
+    async fn create_entry(&self, _: RepairContract) -> Result<RepairContractEntry> {
+        self.error()
+    }
+
+    async fn update_entry(&self, _: RepairContractEntry) -> Result<RepairContractEntry> {
+        self.error()
+    }
+
+    async fn delete_entry(&self, _: &RepairContractEntry, _: &str) -> Result<()> {
+        self.error()
+    }
+
+    async fn get_trash(&self) -> Result<Vec<RepairContractEntry>> {
+        self.error()
+    }
+
+    async fn restore_entry(&self, _: &RepairContractEntry) -> Result<RepairContractEntry> {
+        self.error()
+    }
+
+    async fn purge_entry(&self, _: &RepairContractEntry) -> Result<()> {
+        self.error()
+    }
+
+    async fn acquire_lease(&self, _: &Path) -> Result<RepairContractEntry> {
+        self.error()
+    }
+
+    async fn refresh_lease(&self, _: &Path) -> Result<()> {
+        self.error()
+    }
+
+    async fn release_lease(&self, _: &Path) -> Result<()> {
+        self.error()
+    }
+}
+
+/// SQLite databases are recognized by the file extension, anything else is a TOML folder
+pub fn is_sqlite(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("sqlite" | "sqlite3" | "db")
+    )
+}
+
+pub fn open(path: &Path) -> Result<Arc<dyn Storage>> {
+    match is_sqlite(path) {
+        #[cfg(feature = "sqlite")]
+        true => Ok(Arc::new(sqlite::SqliteStorage::open(path)?)),
+        #[cfg(not(feature = "sqlite"))]
+        true => bail!("obsługa SQLite nie została wkompilowana (funkcja `sqlite`) :: {path:?}"),
+        false => Ok(Arc::new(open_folder(path)?)),
+    }
+}
+
+/// the TOML folder, for operations that only make sense on files
+pub fn open_folder(path: &Path) -> Result<Database> {
+    if is_sqlite(path) {
+        bail!("to polecenie działa tylko na archiwum w postaci folderu :: {path:?}");
+    }
+    std::fs::create_dir_all(path)
+        .with_context(|| format!("tworzenie folderu archiwum {path:?}"))?;
+    match path.is_dir() {
+        true => Ok(Database::new(path.to_owned())),
+        false => bail!("archiwum {path:?} nie jest folderem"),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrationSummary {
+    pub copied: usize,
+    /// contracts with the same id already present in the target
+    pub skipped: usize,
+}
+
+/// every contract of the storage, the ones in the trash included
+async fn all_entries(storage: &dyn Storage) -> Result<Vec<RepairContractEntry>> {
+    let mut entries = storage.get_entries().await?;
+    entries.extend(storage.get_trash().await?);
+    Ok(entries)
+}
+
+/// copies every contract from `from` to `to`, the trash included; ids and revisions are kept
+/// so the migration can be repeated (or run back the other way) without creating duplicates
+#[instrument]
+pub async fn migrate(from: &dyn Storage, to: &dyn Storage) -> Result<MigrationSummary> {
+    let existing = all_entries(to)
+        .await?
+        .into_iter()
+        .map(|entry| entry.model.id)
+        .collect::<std::collections::HashSet<_>>();
+    let mut summary = MigrationSummary::default();
+    for entry in all_entries(from).await? {
+        match existing.contains(&entry.model.id) {
+            true => summary.skipped += 1,
+            false => {
+                to.create_entry(entry.model).await?;
+                summary.copied += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    /// ids of the contracts and of the ones in the trash
+    async fn ids(storage: &dyn Storage) -> (Vec<uuid::Uuid>, Vec<uuid::Uuid>) {
+        let ids = |entries: Vec<RepairContractEntry>| {
+            let mut ids = entries
+                .into_iter()
+                .map(|entry| entry.model.id)
+                .collect::<Vec<_>>();
+            ids.sort();
+            ids
+        };
+        (
+            ids(storage.get_entries().await.unwrap()),
+            ids(storage.get_trash().await.unwrap()),
+        )
+    }
+
+    /// an archive with one contract in use and one in the trash
+    async fn archive(dir: &Path) -> Arc<dyn Storage> {
+        let storage = open(dir).unwrap();
+        storage.create_entry(Default::default()).await.unwrap();
+        let trashed = storage.create_entry(Default::default()).await.unwrap();
+        storage.delete_entry(&trashed, "pomyłka").await.unwrap();
+        storage
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn migrate_with_trash() {
+        let (from_dir, to_dir) = (TempDir::default(), TempDir::default());
+        let from = archive(from_dir.path()).await;
+        let to = open(to_dir.path()).unwrap();
+        let summary = migrate(from.as_ref(), to.as_ref()).await.unwrap();
+        assert_eq!((summary.copied, summary.skipped), (2, 0));
+        assert_eq!(ids(to.as_ref()).await, ids(from.as_ref()).await);
+        let trashed = &to.get_trash().await.unwrap()[0];
+        assert_eq!(trashed.model.deleted.as_ref().unwrap().reason, "pomyłka");
+        // the trashed contract counts as already migrated
+        let summary = migrate(from.as_ref(), to.as_ref()).await.unwrap();
+        assert_eq!((summary.copied, summary.skipped), (0, 2));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn migrate_through_sqlite() {
+        let (folder_dir, database_dir, back_dir) =
+            (TempDir::default(), TempDir::default(), TempDir::default());
+        let folder = archive(folder_dir.path()).await;
+        let database = open(&database_dir.path().join("archiwum.sqlite")).unwrap();
+        migrate(folder.as_ref(), database.as_ref()).await.unwrap();
+        assert_eq!(ids(database.as_ref()).await, ids(folder.as_ref()).await);
+        // soft deleted rows are found by id and not created again
+        let summary = migrate(folder.as_ref(), database.as_ref()).await.unwrap();
+        assert_eq!((summary.copied, summary.skipped), (0, 2));
+
+        let back = open(back_dir.path()).unwrap();
+        migrate(database.as_ref(), back.as_ref()).await.unwrap();
+        assert_eq!(ids(back.as_ref()).await, ids(folder.as_ref()).await);
+
+        let entry = database.get_entries().await.unwrap().remove(0);
+        database.update_entry(entry.clone()).await.unwrap();
+        let error = database.update_entry(entry.clone()).await.unwrap_err();
+        assert!(error.downcast_ref::<crate::db::Conflict>().is_some(), "{error:#}");
+        database.acquire_lease(&entry.path).await.unwrap();
+        database.refresh_lease(&entry.path).await.unwrap();
+        database.release_lease(&entry.path).await.unwrap();
+        assert!(database.purge_entry(&entry).await.is_err());
+        let trashed = database.get_trash().await.unwrap().remove(0);
+        database.purge_entry(&trashed).await.unwrap();
+        assert!(database.get_trash().await.unwrap().is_empty());
+    }
+}
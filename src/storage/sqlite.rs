@@ -0,0 +1,337 @@
+use std::{
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use async_trait::async_trait;
+use rusqlite::{
+    params,
+    Connection,
+    OptionalExtension,
+    Transaction,
+    TransactionBehavior,
+};
+use tracing::instrument;
+
+use super::Storage;
+use crate::{
+    db::{
+        Conflict,
+        Lease,
+        RepairContractEntry,
+    },
+    models::{
+        Deletion,
+        RepairContract,
+    },
+};
+
+/// contracts are stored as the same TOML the folder archive uses; `deleted` splits the archive
+/// from the trash, so that listing one doesn't parse the other
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS repair_contracts (
+    id TEXT PRIMARY KEY NOT NULL,
+    date TEXT NOT NULL,
+    revision INTEGER NOT NULL,
+    deleted INTEGER NOT NULL,
+    contents TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS repair_contracts_deleted_date ON repair_contracts (deleted, date);
+CREATE TABLE IF NOT EXISTS leases (
+    id TEXT PRIMARY KEY NOT NULL,
+    holder TEXT NOT NULL,
+    acquired TEXT NOT NULL
+);
+";
+
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    path: PathBuf,
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<Self> {
+        let connection =
+            Connection::open(path).with_context(|| format!("otwieranie bazy {path:?}"))?;
+        connection
+            .execute_batch(SCHEMA)
+            .with_context(|| format!("tworzenie tabel w {path:?}"))?;
+        tracing::info!("program używa archiwum z bazy [{path:?}]");
+        Ok(Self {
+            path: path.to_owned(),
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    /// entries have no file of their own, the path points into the database for display
+    fn entry(&self, model: RepairContract) -> RepairContractEntry {
+        RepairContractEntry {
+            path: PathBuf::from(format!("{}#{}", self.path.display(), model.id)),
+            model,
+        }
+    }
+
+    /// the id of the contract an [`Self::entry`] path points at
+    fn id_of(path: &Path) -> Result<String> {
+        path.to_str()
+            .and_then(|path| path.rsplit_once('#'))
+            .map(|(_, id)| id.to_owned())
+            .with_context(|| format!("{path:?} nie wskazuje zlecenia w bazie"))
+    }
+
+    fn with_connection<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        tokio::task::block_in_place(|| {
+            let connection = self
+                .connection
+                .lock()
+                .map_err(|_| anyhow::anyhow!("połączenie z bazą zostało uszkodzone"))?;
+            f(&connection)
+        })
+    }
+
+    /// runs `f` in a transaction that takes the write lock up front, so no one else can write
+    /// between what `f` reads and what it writes
+    fn with_transaction<T>(&self, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+        self.with_connection(|connection| {
+            let transaction = Transaction::new_unchecked(connection, TransactionBehavior::Immediate)
+                .context("rozpoczynanie transakcji")?;
+            let result = f(&transaction)?;
+            transaction.commit().context("zatwierdzanie transakcji")?;
+            Ok(result)
+        })
+    }
+
+    fn write(connection: &Connection, model: &RepairContract) -> Result<()> {
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO repair_contracts (id, date, revision, deleted, contents)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    model.id.to_string(),
+                    model.date.to_string(),
+                    model.revision as i64,
+                    model.deleted.is_some(),
+                    model.to_toml()?,
+                ],
+            )
+            .with_context(|| format!("zapisywanie zlecenia {}", model.id))?;
+        Ok(())
+    }
+
+    fn read(connection: &Connection, id: &str) -> Result<Option<RepairContract>> {
+        connection
+            .query_row(
+                "SELECT contents FROM repair_contracts WHERE id = ?1",
+                [id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .with_context(|| format!("odczytywanie zlecenia {id}"))?
+            .map(|contents| contents.parse())
+            .transpose()
+    }
+
+    /// the contracts in the trash or the ones out of it
+    fn read_all(connection: &Connection, deleted: bool) -> Result<Vec<RepairContract>> {
+        let mut statement = connection
+            .prepare("SELECT id, contents FROM repair_contracts WHERE deleted = ?1 ORDER BY date")
+            .context("odczytywanie zleceń")?;
+        let rows = statement
+            .query_map([deleted], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .context("odczytywanie zleceń")?;
+        rows.map(|row| {
+            let (id, contents) = row.context("odczytywanie zleceń")?;
+            contents
+                .parse::<RepairContract>()
+                .with_context(|| format!("parsowanie zlecenia {id}"))
+        })
+        .collect()
+    }
+
+    fn read_lease(connection: &Connection, id: &str) -> Result<Option<Lease>> {
+        connection
+            .query_row(
+                "SELECT holder, acquired FROM leases WHERE id = ?1",
+                [id],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()
+            .with_context(|| format!("odczytywanie blokady zlecenia {id}"))?
+            .map(|(holder, acquired)| {
+                Ok(Lease {
+                    holder,
+                    acquired: acquired
+                        .parse()
+                        .with_context(|| format!("zła data blokady [{acquired}]"))?,
+                })
+            })
+            .transpose()
+    }
+
+    /// fails when someone else holds a fresh lease on the contract
+    fn ensure_free(connection: &Connection, id: &str, ours: &Lease) -> Result<()> {
+        match Self::read_lease(connection, id)? {
+            Some(lease) if lease.holder != ours.holder && !lease.is_stale() => anyhow::bail!(
+                "zlecenie jest edytowane przez {} od {}",
+                lease.holder,
+                lease.acquired
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// takes the lease unless someone else holds a fresh one; run in a transaction so two
+    /// machines sharing the database can't both get it
+    fn take_lease(connection: &Connection, id: &str) -> Result<()> {
+        let ours = Lease::ours();
+        Self::ensure_free(connection, id, &ours)?;
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO leases (id, holder, acquired) VALUES (?1, ?2, ?3)",
+                params![
+                    id,
+                    ours.holder,
+                    ours.acquired.format("%Y-%m-%dT%H:%M:%S%.f").to_string()
+                ],
+            )
+            .with_context(|| format!("zapisywanie blokady zlecenia {id}"))?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    #[instrument(skip(self))]
+    async fn get_entries(&self) -> Result<Vec<RepairContractEntry>> {
+        let models = self.with_connection(|connection| Self::read_all(connection, false))?;
+        Ok(models.into_iter().map(|model| self.entry(model)).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn create_entry(&self, model: RepairContract) -> Result<RepairContractEntry> {
+        self.with_transaction(|connection| {
+            if Self::read(connection, &model.id.to_string())?.is_some() {
+                anyhow::bail!("zlecenie {} już istnieje", model.id);
+            }
+            Self::write(connection, &model)
+        })?;
+        Ok(self.entry(model))
+    }
+
+    #[instrument(skip(self))]
+    async fn update_entry(&self, entry: RepairContractEntry) -> Result<RepairContractEntry> {
+        let mut entry = entry;
+        self.with_transaction(|connection| {
+            let theirs = Self::read(connection, &entry.model.id.to_string())?
+                .with_context(|| format!("nie ma zlecenia {}", entry.model.id))?;
+            if theirs.revision != entry.model.revision {
+                return Err(Conflict {
+                    theirs: self.entry(theirs),
+                    mine: entry.clone(),
+                }
+                .into());
+            }
+            entry.model.revision += 1;
+            Self::write(connection, &entry.model)
+        })?;
+        Ok(entry)
+    }
+
+    /// the row stays, marked as deleted, and is skipped by [`Storage::get_entries`]
+    #[instrument(skip(self))]
+    async fn delete_entry(&self, entry: &RepairContractEntry, reason: &str) -> Result<()> {
+        self.with_transaction(|connection| {
+            Self::ensure_free(connection, &entry.model.id.to_string(), &Lease::ours())?;
+            let mut model = Self::read(connection, &entry.model.id.to_string())?
+                .with_context(|| format!("nie ma zlecenia {}", entry.model.id))?;
+            model.deleted = Some(Deletion {
+                date: crate::now(),
+                reason: reason.to_owned(),
+            });
+            Self::write(connection, &model)
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn get_trash(&self) -> Result<Vec<RepairContractEntry>> {
+        let mut models = self.with_connection(|connection| Self::read_all(connection, true))?;
+        models.sort_by_key(|model| {
+            std::cmp::Reverse(model.deleted.as_ref().map(|deleted| deleted.date))
+        });
+        Ok(models.into_iter().map(|model| self.entry(model)).collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn restore_entry(&self, entry: &RepairContractEntry) -> Result<RepairContractEntry> {
+        let model = self.with_transaction(|connection| {
+            Self::ensure_free(connection, &entry.model.id.to_string(), &Lease::ours())?;
+            let mut model = Self::read(connection, &entry.model.id.to_string())?
+                .with_context(|| format!("nie ma zlecenia {}", entry.model.id))?;
+            model.deleted = None;
+            Self::write(connection, &model)?;
+            Ok(model)
+        })?;
+        Ok(self.entry(model))
+    }
+
+    #[instrument(skip(self))]
+    async fn purge_entry(&self, entry: &RepairContractEntry) -> Result<()> {
+        self.with_transaction(|connection| {
+            let id = entry.model.id.to_string();
+            Self::ensure_free(connection, &id, &Lease::ours())?;
+            match Self::read(connection, &id)? {
+                Some(model) if model.deleted.is_some() => {
+                    connection
+                        .execute("DELETE FROM repair_contracts WHERE id = ?1", [&id])
+                        .with_context(|| format!("usuwanie zlecenia {id}"))?;
+                    Ok(())
+                }
+                _ => anyhow::bail!("tylko zlecenia z kosza mogą zostać usunięte na zawsze"),
+            }
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn acquire_lease(&self, path: &Path) -> Result<RepairContractEntry> {
+        let id = Self::id_of(path)?;
+        let model = self.with_transaction(|connection| {
+            Self::take_lease(connection, &id)?;
+            Self::read(connection, &id)?.with_context(|| format!("nie ma zlecenia {id}"))
+        })?;
+        Ok(self.entry(model))
+    }
+
+    #[instrument(skip(self))]
+    async fn refresh_lease(&self, path: &Path) -> Result<()> {
+        let id = Self::id_of(path)?;
+        self.with_transaction(|connection| Self::take_lease(connection, &id))
+    }
+
+    /// removes the lease, unless it has been taken over by someone else in the meantime
+    #[instrument(skip(self))]
+    async fn release_lease(&self, path: &Path) -> Result<()> {
+        let id = Self::id_of(path)?;
+        self.with_connection(|connection| {
+            connection
+                .execute(
+                    "DELETE FROM leases WHERE id = ?1 AND holder = ?2",
+                    params![id, Lease::ours().holder],
+                )
+                .with_context(|| format!("usuwanie blokady zlecenia {id}"))?;
+            Ok(())
+        })
+    }
+}